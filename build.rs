@@ -1,5 +1,8 @@
+#[cfg(windows)]
 use std::path::Path;
+#[cfg(windows)]
 use std::fs::File;
+#[cfg(windows)]
 use std::io::{BufWriter, Write};
 
 fn main() {
@@ -1,15 +1,245 @@
-use rusqlite::{Connection, Result};
-use std::path::PathBuf;
+use rusqlite::{Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+/// 主币种：标题栏、日历等汇总只统计主币种，其他币种单独列出
+pub const DEFAULT_CURRENCY: &str = "CNY";
+/// 指定数据库所在目录的环境变量（如同步盘中的文件夹），未设置时使用本地数据目录
+const DATA_DIR_ENV: &str = "JZ_DATA_DIR";
+
+fn default_currency() -> String {
+    DEFAULT_CURRENCY.to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Record {
+    #[serde(default)]
     pub id: i64,
     pub date: String,
     pub boss: String,
     pub income: f64,
     pub duration: Option<f64>,   // 服务时长(小时)，支持小数
     pub game: Option<String>,    // 游戏名称
+    #[serde(default)]
     pub settled: bool,           // 是否结清
+    #[serde(default = "default_currency")]
+    pub currency: String,        // 币种代码，如 CNY、USD
+    #[serde(default)]
+    pub tags: Vec<String>,       // 自定义标签，如“老客户”
+    #[serde(default)]
+    pub created_at: Option<String>,  // 录入时间（本地时间 YYYY-MM-DD HH:MM:SS），新记录为 None 时由数据库填入
+    #[serde(default)]
+    pub order_index: Option<i64>,    // 同一天内的手动排序，越大越靠前；None 表示按录入时间
+}
+
+/// 空记录：主币种、未结清，其余字段为空，新建记录时只填需要的字段
+impl Default for Record {
+    fn default() -> Self {
+        Self {
+            id: 0,
+            date: String::new(),
+            boss: String::new(),
+            income: 0.0,
+            duration: None,
+            game: None,
+            settled: false,
+            currency: default_currency(),
+            tags: Vec::new(),
+            created_at: None,
+            order_index: None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Record {
+    /// 测试用记录：只指定日期、老板和收入
+    pub fn sample(date: &str, boss: &str, income: f64) -> Self {
+        Self { date: date.to_string(), boss: boss.to_string(), income, ..Self::default() }
+    }
+}
+
+/// 记录模板：常客的固定老板、游戏、单价和时长，一键填入输入行
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub id: i64,
+    pub name: String,
+    pub boss: String,
+    pub game: Option<String>,
+    pub income: f64,
+    pub duration: Option<f64>,
+}
+
+/// 老板的计费偏好：按时长估算收入时使用
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BossRate {
+    pub rate: f64,        // 每小时单价，0 表示未设置
+    pub round_step: f64,  // 建议收入取整到的步长（如 10、50、100），0 表示不取整
+}
+
+/// 游戏的默认时长和单价：选中游戏时预填输入框
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GameDefault {
+    pub duration: f64,  // 默认时长（小时），0 表示未设置
+    pub rate: f64,      // 每小时单价，老板没有设置时薪时使用，0 表示未设置
+}
+
+/// 数据检查发现的问题类型
+#[derive(Debug, Clone, PartialEq)]
+pub enum IssueKind {
+    EmptyBoss,                          // 老板名为空或只有空白
+    InvalidIncome(Option<f64>),         // 收入为空（NaN 写入后会变成 NULL）、非有限数或负数
+    BadDate { fixed: Option<String> },  // 日期不是 YYYY-MM-DD，能识别时附带修正后的日期
+}
+
+/// 数据检查发现的一条问题记录
+#[derive(Debug, Clone)]
+pub struct IntegrityIssue {
+    pub record_id: i64,
+    pub date: String,
+    pub boss: String,
+    pub kind: IssueKind,
+}
+
+/// 尝试把常见的日期写法（2024/6/1、2024-6-1、2024.06.01、20240601）规范为 YYYY-MM-DD
+pub fn normalize_date(s: &str) -> Option<String> {
+    let s = s.trim();
+    ["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d", "%Y%m%d"]
+        .iter()
+        .find_map(|fmt| chrono::NaiveDate::parse_from_str(s, fmt).ok())
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+/// 金额保留两位小数：浮点数累加会产生 8500.0000000001 这样的误差，合计结果统一经过它再显示
+pub fn round2(x: f64) -> f64 {
+    (x * 100.0).round() / 100.0
+}
+
+/// 规范老板/游戏名称，避免因空白或全半角不同产生近似重复的名字：
+/// 全角空格和全角字母数字转为半角，去掉首尾空白，中间连续空白合并为一个，
+/// 两个汉字之间的空白直接去掉（“小 王” → “小王”，“Tom  Lee” → “Tom Lee”）
+pub fn normalize_name(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut pending_space = false;
+    for c in s.chars().map(to_half_width) {
+        if c.is_whitespace() {
+            pending_space = !out.is_empty();
+            continue;
+        }
+        if pending_space && !(out.chars().next_back().is_some_and(is_cjk) && is_cjk(c)) {
+            out.push(' ');
+        }
+        pending_space = false;
+        out.push(c);
+    }
+    out
+}
+
+/// 全角空格、全角 ASCII 字符转为对应的半角字符
+fn to_half_width(c: char) -> char {
+    match c {
+        '\u{3000}' => ' ',
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// 操作记录中的记录描述：日期 老板 金额 币种
+fn describe_record(r: &Record) -> String {
+    format!("{} {} {:.2} {}", r.date, r.boss, r.income, r.currency)
+}
+
+/// 中日韩文字（汉字、假名、谚文等）
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{2E80}'..='\u{9FFF}' | '\u{AC00}'..='\u{D7AF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+/// 按“日切时间”计算某个时刻归属的账目日期：早于 day_start_hour 点的时刻算作前一天
+/// 例如日切为 4 点时，6 月 1 日 01:30 归属 5 月 31 日，因此会计入 5 月（1 月 1 日凌晨同理计入上一年）
+/// created_at 使用本地时间存储，同样可以用它换算
+pub fn effective_date(time: chrono::NaiveDateTime, day_start_hour: u32) -> chrono::NaiveDate {
+    (time - chrono::Duration::hours(i64::from(day_start_hour.min(23)))).date()
+}
+
+/// 查询结果中多个标签之间的分隔符（标签内容不会包含控制字符）
+const TAG_SEPARATOR: char = '\u{1f}';
+
+/// 某年的日期区间（YYYY-01-01 ~ YYYY-12-31）
+pub fn year_range(year: i32) -> (String, String) {
+    (format!("{}-01-01", year), format!("{}-12-31", year))
+}
+
+/// 某月的日期区间（YYYY-MM-01 ~ YYYY-MM-31），日期按字符串比较，31 可覆盖任意月份
+pub fn month_range(year: i32, month: u32) -> (String, String) {
+    (format!("{}-{:02}-01", year, month), format!("{}-{:02}-31", year, month))
+}
+
+/// 查询记录的 SQL，filter 为 WHERE 子句
+fn records_sql(filter: &str) -> String {
+    format!(
+        "SELECT id, date, boss, income, duration, game, settled, currency,
+                (SELECT group_concat(tag, char(31)) FROM tags WHERE record_id = records.id),
+                created_at, order_index
+         FROM records {} ORDER BY date DESC, order_index DESC, created_at DESC, id DESC",
+        filter
+    )
+}
+
+/// 单日统计（日历热力图用）
+#[derive(Debug, Clone)]
+pub struct DayStat {
+    pub day: u32,      // 几号
+    pub income: f64,
+    pub hours: f64,    // 服务总时长
+    pub count: i64,    // 单数
+}
+
+/// 数据概况（关于弹窗用）
+#[derive(Debug, Clone)]
+pub struct DbStats {
+    pub count: i64,                  // 记录条数
+    pub first_date: Option<String>,  // 最早的记录日期，没有记录时为 None
+    pub last_date: Option<String>,
+    pub file_size: u64,              // 数据库文件大小（含 -wal），字节
+}
+
+/// 一次计时（从开始到结束，暂停的时间不计入时长）
+#[derive(Debug, Clone)]
+pub struct TimerSession {
+    pub start: String,   // 开始时间 YYYY-MM-DD HH:MM:SS
+    pub end: String,     // 结束时间
+    pub seconds: i64,    // 实际计时秒数
+    pub linked_record_id: Option<i64>,  // 用计时结果填入时长后添加的记录
+}
+
+/// 一步数据库迁移：给已有的表添加一列
+struct Migration {
+    table: &'static str,
+    column: &'static str,
+    definition: &'static str,
+}
+
+/// 按顺序执行的迁移，第 i 步完成后 user_version 为 i + 1；只能在末尾追加
+const MIGRATIONS: &[Migration] = &[
+    // duration: 服务时长(小时)
+    Migration { table: "records", column: "duration", definition: "INTEGER" },
+    // game: 游戏名称
+    Migration { table: "records", column: "game", definition: "TEXT" },
+    // settled: 是否结清，默认0(false)
+    Migration { table: "records", column: "settled", definition: "INTEGER DEFAULT 0" },
+    // currency: 币种，旧数据默认人民币
+    Migration { table: "records", column: "currency", definition: "TEXT NOT NULL DEFAULT 'CNY'" },
+    // order_index: 同一天内拖动排序的顺序，未排序过的为 NULL
+    Migration { table: "records", column: "order_index", definition: "INTEGER" },
+];
+
+/// 一条操作记录
+pub struct AuditEntry {
+    pub ts: String,              // 操作时间 YYYY-MM-DD HH:MM:SS
+    pub action: String,          // 添加、修改、删除、结清等
+    pub record_id: Option<i64>,  // 涉及的记录，批量操作为空
+    pub details: String,
 }
 
 pub struct Database {
@@ -17,25 +247,180 @@ pub struct Database {
 }
 
 impl Database {
-    pub fn new() -> Result<Self> {
+    /// 打开数据库；加密数据库需要传入密码，密码错误时返回 NotADatabase 错误
+    pub fn open(passphrase: Option<&str>) -> Result<Self> {
         let db_path = Self::get_db_path();
         if let Some(parent) = db_path.parent() {
             std::fs::create_dir_all(parent).ok();
         }
-        let conn = Connection::open(&db_path)?;
+        let conn = Self::connect(&db_path, passphrase)?;
         let db = Database { conn };
         db.init()?;
         Ok(db)
     }
 
+    fn connect(path: &Path, passphrase: Option<&str>) -> Result<Connection> {
+        let conn = Connection::open(path)?;
+        // PRAGMA key 必须在任何读写之前执行
+        if let Some(key) = passphrase {
+            conn.pragma_update(None, "key", key)?;
+        }
+        // WAL 模式允许读写并发，忙等 5 秒后再报“database is locked”
+        // 注意：WAL 会在数据库旁生成 records.db-wal / records.db-shm，备份前需先 checkpoint
+        conn.query_row("PRAGMA journal_mode=WAL", [], |_| Ok(()))?;
+        conn.busy_timeout(std::time::Duration::from_millis(5000))?;
+        Ok(conn)
+    }
+
+    /// 把损坏的数据库文件（连同 -wal/-shm）改名移到一旁，下次打开时新建空库
+    /// 返回移走后的主文件路径，原文件保留以便手动抢救
+    pub fn move_aside() -> std::io::Result<PathBuf> {
+        let db_path = Self::get_db_path();
+        let suffix = format!("corrupt-{}", chrono::Local::now().format("%Y%m%d-%H%M%S"));
+        let moved = db_path.with_extension(format!("db.{}", suffix));
+        std::fs::rename(&db_path, &moved)?;
+        for ext in ["db-wal", "db-shm"] {
+            let side = db_path.with_extension(ext);
+            if side.exists() {
+                let _ = std::fs::rename(&side, db_path.with_extension(format!("{}.{}", ext, suffix)));
+            }
+        }
+        Ok(moved)
+    }
+
+    /// 数据库文件是否已加密：明文 SQLite 文件以 "SQLite format 3\0" 开头，加密后文件头为随机数据
+    pub fn is_encrypted() -> bool {
+        use std::io::Read;
+        let mut header = [0u8; 16];
+        match std::fs::File::open(Self::get_db_path()) {
+            Ok(mut file) => file.read_exact(&mut header).is_ok() && &header != b"SQLite format 3\0",
+            Err(_) => false,
+        }
+    }
+
+    /// 用单独的连接验证数据库密码，不影响当前连接
+    pub fn check_password(passphrase: &str) -> bool {
+        Self::connect(&Self::get_db_path(), Some(passphrase))
+            .and_then(|conn| conn.query_row("SELECT count(*) FROM sqlite_master", [], |_| Ok(())))
+            .is_ok()
+    }
+
+    /// 设置或修改密码：已加密的数据库直接 rekey；明文数据库导出为加密副本后替换原文件
+    #[cfg(feature = "encryption")]
+    pub fn set_password(&mut self, passphrase: &str) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        if Self::is_encrypted() {
+            self.conn.pragma_update(None, "rekey", passphrase)?;
+            return Ok(());
+        }
+
+        let db_path = Self::get_db_path();
+        let tmp_path = db_path.with_extension("db.encrypting");
+        let _ = std::fs::remove_file(&tmp_path);
+        self.conn.execute(
+            "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+            rusqlite::params![tmp_path.to_string_lossy(), passphrase],
+        )?;
+        let exported = self.conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()));
+        self.conn.execute_batch("DETACH DATABASE encrypted")?;
+        if let Err(e) = exported {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(e.into());
+        }
+
+        self.replace_file(&db_path, &tmp_path, Some(passphrase), None)
+    }
+
+    /// 关闭当前连接，用 tmp_path 替换 db_path 后以 new_key 重新打开
+    /// 任何一步失败都回到磁盘上的数据库文件（替换失败时仍是原文件，用 old_key 打开），
+    /// 不会停留在内存占位连接上，否则之后的记录退出时全部丢失
+    #[cfg(any(feature = "encryption", test))]
+    fn replace_file(
+        &mut self,
+        db_path: &Path,
+        tmp_path: &Path,
+        new_key: Option<&str>,
+        old_key: Option<&str>,
+    ) -> std::result::Result<(), Box<dyn std::error::Error>> {
+        // 关闭原连接（会合并并删除 -wal/-shm）后再替换文件；关闭失败时原连接会被交还
+        let old = std::mem::replace(&mut self.conn, Connection::open_in_memory()?);
+        if let Err((conn, e)) = old.close() {
+            self.conn = conn;
+            let _ = std::fs::remove_file(tmp_path);
+            return Err(e.into());
+        }
+        let replaced = std::fs::rename(tmp_path, db_path);
+        if replaced.is_err() {
+            let _ = std::fs::remove_file(tmp_path);
+        }
+        let key = if replaced.is_ok() { new_key } else { old_key };
+        if let Err(e) = self.reopen(db_path, key) {
+            // 退回原密钥；仍打不开时也保留指向文件的连接，之后的写入会报错而不是悄悄写进内存
+            if self.reopen(db_path, old_key).is_err() {
+                if let Ok(conn) = Connection::open(db_path) {
+                    self.conn = conn;
+                }
+            }
+            return Err(e.into());
+        }
+        replaced.map_err(|e| format!("无法替换数据库文件：{}", e).into())
+    }
+
+    #[cfg(any(feature = "encryption", test))]
+    fn reopen(&mut self, path: &Path, passphrase: Option<&str>) -> Result<()> {
+        self.conn = Self::connect(path, passphrase)?;
+        self.init()
+    }
+
+    /// 数据库所在目录：设置了 JZ_DATA_DIR 时为该目录，否则为本地数据目录下的 jz
+    /// 目录不存在时由 open 创建
+    pub fn data_dir() -> PathBuf {
+        match std::env::var_os(DATA_DIR_ENV) {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => {
+                let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+                path.push("jz");
+                path
+            }
+        }
+    }
+
     fn get_db_path() -> PathBuf {
-        let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
-        path.push("jz");
-        path.push("records.db");
-        path
+        Self::data_dir().join("records.db")
+    }
+
+    /// 按 `PRAGMA user_version` 依次执行尚未应用的迁移，每步一个事务
+    /// 版本号记录之前的旧库可能已经有这些列，添加前先检查，已存在时只更新版本号
+    fn migrate(&self) -> Result<()> {
+        let version: usize = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let tx = self.conn.unchecked_transaction()?;
+            if !self.has_column(migration.table, migration.column)? {
+                self.conn.execute(
+                    &format!("ALTER TABLE {} ADD COLUMN {} {}", migration.table, migration.column, migration.definition),
+                    [],
+                )?;
+            }
+            self.conn.pragma_update(None, "user_version", i + 1)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    fn has_column(&self, table: &str, column: &str) -> Result<bool> {
+        let mut stmt = self.conn.prepare(&format!("PRAGMA table_info({})", table))?;
+        let names = stmt.query_map([], |row| row.get::<_, String>(1))?;
+        for name in names {
+            if name? == column {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
     fn init(&self) -> Result<()> {
+        // 启用外键，删除记录时级联删除其标签
+        self.conn.execute_batch("PRAGMA foreign_keys = ON")?;
+
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS records (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -47,43 +432,286 @@ impl Database {
             [],
         )?;
 
-        // 数据库迁移：添加新列（兼容旧数据）
-        // duration: 服务时长(小时)
-        let _ = self.conn.execute("ALTER TABLE records ADD COLUMN duration INTEGER", []);
-        // game: 游戏名称
-        let _ = self.conn.execute("ALTER TABLE records ADD COLUMN game TEXT", []);
-        // settled: 是否结清，默认0(false)
-        let _ = self.conn.execute("ALTER TABLE records ADD COLUMN settled INTEGER DEFAULT 0", []);
+        self.migrate()?;
+
+        // 索引：按日期区间查询、按老板汇总
+        self.conn.execute("CREATE INDEX IF NOT EXISTS idx_records_date ON records(date)", [])?;
+        self.conn.execute("CREATE INDEX IF NOT EXISTS idx_records_boss ON records(boss)", [])?;
+
+        // 标签：记录与标签多对多
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS tags (
+                record_id INTEGER NOT NULL REFERENCES records(id) ON DELETE CASCADE,
+                tag TEXT NOT NULL,
+                PRIMARY KEY (record_id, tag)
+            )",
+            [],
+        )?;
+        self.conn.execute("CREATE INDEX IF NOT EXISTS idx_tags_tag ON tags(tag)", [])?;
+
+        // 老板备注：每个老板一条
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS boss_notes (
+                boss TEXT PRIMARY KEY,
+                note TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // 老板计费偏好：时薪和建议收入的取整步长
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS boss_rates (
+                boss TEXT PRIMARY KEY,
+                rate REAL NOT NULL DEFAULT 0,
+                round_step REAL NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // 游戏默认值：常见单局时长和单价
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS game_defaults (
+                game TEXT PRIMARY KEY,
+                default_duration REAL NOT NULL DEFAULT 0,
+                default_rate REAL NOT NULL DEFAULT 0
+            )",
+            [],
+        )?;
+
+        // 计时记录：每次结束计时写入一条
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS timer_sessions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                start TEXT NOT NULL,
+                end TEXT NOT NULL,
+                seconds INTEGER NOT NULL,
+                linked_record_id INTEGER
+            )",
+            [],
+        )?;
+
+        // 操作记录：与对应的修改在同一事务中写入
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts TEXT NOT NULL DEFAULT (datetime('now', 'localtime')),
+                action TEXT NOT NULL,
+                record_id INTEGER,
+                details TEXT NOT NULL DEFAULT ''
+            )",
+            [],
+        )?;
+
+        // 模板：名称唯一，同名保存时覆盖
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS templates (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE,
+                boss TEXT NOT NULL,
+                game TEXT,
+                income REAL NOT NULL,
+                duration REAL
+            )",
+            [],
+        )?;
 
         Ok(())
     }
 
-    pub fn add_record(
-        &self,
-        date: &str,
-        boss: &str,
-        income: f64,
-        duration: Option<f64>,
-        game: Option<&str>,
-        settled: bool,
-    ) -> Result<()> {
+    /// 将 WAL 中的内容写回主数据库文件并清空 WAL，打开数据目录供手动备份前调用
+    pub fn checkpoint(&self) -> Result<()> {
+        self.conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+    }
+
+    /// 添加记录并写入操作记录，返回新记录的 id
+    pub fn add_record(&self, r: &Record) -> Result<i64> {
+        let tx = self.conn.unchecked_transaction()?;
+        let id = self.insert_record(r)?;
+        self.audit("添加", Some(id), &describe_record(r))?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// 插入一条记录及其标签（忽略 `r.id`，由数据库自动分配），调用方负责事务
+    fn insert_record(&self, r: &Record) -> Result<i64> {
         self.conn.execute(
-            "INSERT INTO records (date, boss, income, duration, game, settled) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            rusqlite::params![date, boss, income, duration, game, settled as i32],
+            "INSERT INTO records (date, boss, income, duration, game, settled, currency, created_at, order_index)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, COALESCE(?8, datetime('now', 'localtime')),
+                     COALESCE(?9, (SELECT MAX(order_index) + 1 FROM records WHERE date = ?1)))",
+            rusqlite::params![r.date, r.boss, r.income, r.duration, r.game, r.settled as i32, r.currency, r.created_at, r.order_index],
+        )?;
+        let id = self.conn.last_insert_rowid();
+        for tag in &r.tags {
+            self.add_tag(id, tag)?;
+        }
+        Ok(id)
+    }
+
+    /// 保存一次计时，返回其 id
+    pub fn add_timer_session(&self, start: &str, end: &str, seconds: i64) -> Result<i64> {
+        self.conn.execute(
+            "INSERT INTO timer_sessions (start, end, seconds) VALUES (?1, ?2, ?3)",
+            rusqlite::params![start, end, seconds],
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// 把计时关联到用它填入时长的记录
+    pub fn link_timer_session(&self, id: i64, record_id: i64) -> Result<()> {
+        self.conn.execute(
+            "UPDATE timer_sessions SET linked_record_id = ?1 WHERE id = ?2",
+            [record_id, id],
         )?;
         Ok(())
     }
 
+    /// 所有计时记录，最近的在前
+    pub fn list_timer_sessions(&self) -> Result<Vec<TimerSession>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT start, end, seconds, linked_record_id FROM timer_sessions ORDER BY start DESC, id DESC",
+        )?;
+        let sessions = stmt.query_map([], |row| {
+            Ok(TimerSession {
+                start: row.get(0)?,
+                end: row.get(1)?,
+                seconds: row.get(2)?,
+                linked_record_id: row.get(3)?,
+            })
+        })?;
+        sessions.collect()
+    }
+
+    /// 重排同一天的记录：ids 为从上到下的显示顺序，不属于该日期的 id 会被忽略
+    pub fn reorder(&self, date: &str, ids: &[i64]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for (pos, id) in ids.iter().enumerate() {
+            tx.execute(
+                "UPDATE records SET order_index = ?1 WHERE id = ?2 AND date = ?3",
+                rusqlite::params![(ids.len() - pos) as i64, id, date],
+            )?;
+        }
+        tx.commit()
+    }
+
+    /// 删除记录（标签由外键级联删除）
     pub fn delete_record(&self, id: i64) -> Result<()> {
-        self.conn.execute("DELETE FROM records WHERE id = ?1", [id])?;
+        let tx = self.conn.unchecked_transaction()?;
+        let details = self.describe_record_id(id)?;
+        if self.conn.execute("DELETE FROM records WHERE id = ?1", [id])? > 0 {
+            self.audit("删除", Some(id), &details)?;
+        }
+        tx.commit()
+    }
+
+    /// 写入一条操作记录，在修改所在的事务中调用
+    fn audit(&self, action: &str, record_id: Option<i64>, details: &str) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO audit_log (action, record_id, details) VALUES (?1, ?2, ?3)",
+            rusqlite::params![action, record_id, details],
+        )?;
         Ok(())
     }
 
-    pub fn get_all_records(&self) -> Result<Vec<Record>> {
+    /// 写入一条与具体记录无关的操作记录（如数据库恢复）
+    pub fn log_event(&self, action: &str, details: &str) -> Result<()> {
+        self.audit(action, None, details)
+    }
+
+    /// 按 id 读出记录的简要描述，记录不存在时为空
+    fn describe_record_id(&self, id: i64) -> Result<String> {
+        self.conn
+            .query_row(
+                "SELECT date, boss, income, currency FROM records WHERE id = ?1",
+                [id],
+                |row| Ok(format!("{} {} {:.2} {}", row.get::<_, String>(0)?, row.get::<_, String>(1)?,
+                    row.get::<_, f64>(2)?, row.get::<_, String>(3)?)),
+            )
+            .optional()
+            .map(Option::unwrap_or_default)
+    }
+
+    /// 最近的操作记录，新的在前
+    pub fn recent_audit(&self, limit: usize) -> Result<Vec<AuditEntry>> {
         let mut stmt = self.conn.prepare(
-            "SELECT id, date, boss, income, duration, game, settled FROM records ORDER BY date DESC, id DESC"
+            "SELECT ts, action, record_id, details FROM audit_log ORDER BY id DESC LIMIT ?1",
+        )?;
+        let entries = stmt.query_map([limit as i64], |row| {
+            Ok(AuditEntry {
+                ts: row.get(0)?,
+                action: row.get(1)?,
+                record_id: row.get(2)?,
+                details: row.get(3)?,
+            })
+        })?;
+        entries.collect()
+    }
+
+    /// 清除 days 天之前的操作记录，返回删除条数
+    pub fn purge_audit(&self, days: u32) -> Result<usize> {
+        self.conn.execute(
+            "DELETE FROM audit_log WHERE ts < datetime('now', 'localtime', ?1)",
+            [format!("-{} days", days)],
+        )
+    }
+
+    /// 给记录添加标签，已有相同标签时忽略
+    pub fn add_tag(&self, record_id: i64, tag: &str) -> Result<()> {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            return Ok(());
+        }
+        self.conn.execute(
+            "INSERT OR IGNORE INTO tags (record_id, tag) VALUES (?1, ?2)",
+            rusqlite::params![record_id, tag],
+        )?;
+        Ok(())
+    }
+
+    /// 移除记录的某个标签
+    pub fn remove_tag(&self, record_id: i64, tag: &str) -> Result<()> {
+        self.conn.execute(
+            "DELETE FROM tags WHERE record_id = ?1 AND tag = ?2",
+            rusqlite::params![record_id, tag],
         )?;
-        let records = stmt.query_map([], |row| {
+        Ok(())
+    }
+
+    /// 获取带有某个标签的全部记录
+    pub fn records_with_tag(&self, tag: &str) -> Result<Vec<Record>> {
+        self.query_records("WHERE id IN (SELECT record_id FROM tags WHERE tag = ?1)", [tag])
+    }
+
+    /// 获取所有标签（用于自动补全），按使用次数降序
+    pub fn get_all_tags(&self) -> Vec<String> {
+        self.query_names("SELECT tag FROM tags GROUP BY tag ORDER BY COUNT(*) DESC, tag")
+    }
+
+    pub fn get_all_records(&self) -> Result<Vec<Record>> {
+        self.query_records("", [])
+    }
+
+    /// 所有未结清的记录，按时间从早到晚
+    pub fn unsettled_records(&self) -> Result<Vec<Record>> {
+        let mut records = self.query_records("WHERE COALESCE(settled, 0) = 0", [])?;
+        records.reverse();
+        Ok(records)
+    }
+
+    /// 获取某月的记录
+    pub fn get_records_for_month(&self, year: i32, month: u32) -> Result<Vec<Record>> {
+        let (start, end) = month_range(year, month);
+        self.query_records("WHERE date BETWEEN ?1 AND ?2", [start, end])
+    }
+
+    /// 获取日期区间内（含首尾）的记录
+    pub fn get_records_between(&self, start: &str, end: &str) -> Result<Vec<Record>> {
+        self.query_records("WHERE date BETWEEN ?1 AND ?2", [start, end])
+    }
+
+    /// 按条件查询记录，按日期倒序，同一天内先按手动排序，再按录入时间倒序
+    fn query_records(&self, filter: &str, params: impl rusqlite::Params) -> Result<Vec<Record>> {
+        let mut stmt = self.conn.prepare(&records_sql(filter))?;
+        let records = stmt.query_map(params, |row| {
             Ok(Record {
                 id: row.get(0)?,
                 date: row.get(1)?,
@@ -92,65 +720,726 @@ impl Database {
                 duration: row.get(4)?,
                 game: row.get(5)?,
                 settled: row.get::<_, Option<i32>>(6)?.unwrap_or(0) != 0,
+                currency: row.get::<_, Option<String>>(7)?.unwrap_or_else(default_currency),
+                tags: row.get::<_, Option<String>>(8)?
+                    .map(|t| t.split(TAG_SEPARATOR).map(str::to_string).collect())
+                    .unwrap_or_default(),
+                created_at: row.get(9)?,
+                order_index: row.get(10)?,
             })
         })?;
         records.collect()
     }
 
-    /// 计算某个老板的结余（累计收入）
+    /// 计算某个老板的结余（主币种累计收入）
     pub fn get_boss_balance(&self, boss: &str) -> f64 {
         self.conn
             .query_row(
-                "SELECT COALESCE(SUM(income), 0) FROM records WHERE boss = ?1",
-                [boss],
+                "SELECT COALESCE(SUM(income), 0) FROM records WHERE boss = ?1 AND currency = ?2",
+                [boss, DEFAULT_CURRENCY],
                 |row| row.get(0),
             )
+            .map(round2)
             .unwrap_or(0.0)
     }
 
-    /// 计算总结余
-    pub fn get_total_balance(&self) -> f64 {
+    /// 全部记录条数
+    pub fn record_count(&self) -> i64 {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM records", [], |row| row.get(0))
+            .unwrap_or(0)
+    }
+
+    /// 记录条数、日期范围和数据库文件大小
+    pub fn stats(&self) -> Result<DbStats> {
+        let (count, first_date, last_date) = self.conn.query_row(
+            "SELECT COUNT(*), MIN(date), MAX(date) FROM records",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let db_path = Self::get_db_path();
+        let file_size = [db_path.clone(), db_path.with_extension("db-wal")]
+            .iter()
+            .filter_map(|p| std::fs::metadata(p).ok())
+            .map(|m| m.len())
+            .sum();
+        Ok(DbStats { count, first_date, last_date, file_size })
+    }
+
+    /// 某个老板的记录条数
+    pub fn get_boss_record_count(&self, boss: &str) -> i64 {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM records WHERE boss = ?1", [boss], |row| row.get(0))
+            .unwrap_or(0)
+    }
+
+    /// 老板有记录的月份（YYYY-MM），最近的在前
+    pub fn get_boss_months(&self, boss: &str) -> Vec<String> {
+        let Ok(mut stmt) = self.conn.prepare(
+            "SELECT DISTINCT substr(date, 1, 7) AS month FROM records WHERE boss = ?1 ORDER BY month DESC",
+        ) else {
+            return Vec::new();
+        };
+        stmt.query_map([boss], |row| row.get(0))
+            .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 获取老板备注，没有备注时返回 None
+    pub fn get_boss_note(&self, boss: &str) -> Option<String> {
+        self.conn
+            .query_row("SELECT note FROM boss_notes WHERE boss = ?1", [boss], |row| row.get(0))
+            .ok()
+    }
+
+    /// 设置老板备注，备注为空时删除
+    pub fn set_boss_note(&self, boss: &str, note: &str) -> Result<()> {
+        let note = note.trim();
+        if note.is_empty() {
+            self.conn.execute("DELETE FROM boss_notes WHERE boss = ?1", [boss])?;
+        } else {
+            self.conn.execute(
+                "INSERT INTO boss_notes (boss, note) VALUES (?1, ?2)
+                 ON CONFLICT(boss) DO UPDATE SET note = excluded.note",
+                [boss, note],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 有备注的老板（联想列表中显示标记）
+    pub fn get_bosses_with_notes(&self) -> Vec<String> {
+        self.query_names("SELECT boss FROM boss_notes")
+    }
+
+    /// 保存老板的计费偏好，单价和取整都为 0 时删除
+    pub fn set_boss_rate(&self, boss: &str, rate: &BossRate) -> Result<()> {
+        if rate.rate <= 0.0 && rate.round_step <= 0.0 {
+            self.conn.execute("DELETE FROM boss_rates WHERE boss = ?1", [boss])?;
+        } else {
+            self.conn.execute(
+                "INSERT INTO boss_rates (boss, rate, round_step) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(boss) DO UPDATE SET rate = excluded.rate, round_step = excluded.round_step",
+                rusqlite::params![boss, rate.rate.max(0.0), rate.round_step.max(0.0)],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 所有设置了计费偏好的老板
+    pub fn get_boss_rates(&self) -> HashMap<String, BossRate> {
+        let Ok(mut stmt) = self.conn.prepare("SELECT boss, rate, round_step FROM boss_rates") else {
+            return HashMap::new();
+        };
+        stmt.query_map([], |row| Ok((row.get(0)?, BossRate { rate: row.get(1)?, round_step: row.get(2)? })))
+            .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 保存游戏的默认时长和单价，都为 0 时删除
+    pub fn set_game_default(&self, game: &str, default: &GameDefault) -> Result<()> {
+        if default.duration <= 0.0 && default.rate <= 0.0 {
+            self.conn.execute("DELETE FROM game_defaults WHERE game = ?1", [game])?;
+        } else {
+            self.conn.execute(
+                "INSERT INTO game_defaults (game, default_duration, default_rate) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(game) DO UPDATE SET default_duration = excluded.default_duration, default_rate = excluded.default_rate",
+                rusqlite::params![game, default.duration.max(0.0), default.rate.max(0.0)],
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 获取游戏的默认值，没有设置时返回 None
+    pub fn get_game_default(&self, game: &str) -> Option<GameDefault> {
+        self.conn.query_row(
+            "SELECT default_duration, default_rate FROM game_defaults WHERE game = ?1",
+            [game],
+            |row| Ok(GameDefault { duration: row.get(0)?, rate: row.get(1)? }),
+        ).ok()
+    }
+
+    /// 老板改名：同时更新记录、模板、备注和计费偏好，返回改动的记录条数
+    /// 新名字已有备注或计费偏好时以旧老板的为准；新名字先经过 normalize_name
+    pub fn rename_boss(&self, old: &str, new: &str) -> Result<usize> {
+        let new = normalize_name(new);
+        let new = new.as_str();
+        let tx = self.conn.unchecked_transaction()?;
+        let changed = self.conn.execute("UPDATE records SET boss = ?1 WHERE boss = ?2", [new, old])?;
+        self.conn.execute("UPDATE templates SET boss = ?1 WHERE boss = ?2", [new, old])?;
+        self.conn.execute("UPDATE OR REPLACE boss_notes SET boss = ?1 WHERE boss = ?2", [new, old])?;
+        self.conn.execute("UPDATE OR REPLACE boss_rates SET boss = ?1 WHERE boss = ?2", [new, old])?;
+        if changed > 0 {
+            self.audit("老板改名", None, &format!("{} → {}（{} 条）", old, new, changed))?;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// 汇总日期区间内（含首尾）的主币种收入
+    /// 使用范围条件而不是 LIKE，以便命中 date 索引
+    fn get_balance_between(&self, start: &str, end: &str) -> f64 {
         self.conn
             .query_row(
-                "SELECT COALESCE(SUM(income), 0) FROM records",
-                [],
+                "SELECT COALESCE(SUM(income), 0) FROM records WHERE date BETWEEN ?1 AND ?2 AND currency = ?3",
+                [start, end, DEFAULT_CURRENCY],
                 |row| row.get(0),
             )
+            .map(round2)
             .unwrap_or(0.0)
     }
 
-    /// 获取所有老板名称（用于自动补全）
+    /// 日期区间内（含首尾）按币种分组的收入，不同币种不会相加
+    pub fn get_balances_by_currency(&self, start: &str, end: &str) -> HashMap<String, f64> {
+        let Ok(mut stmt) = self.conn.prepare(
+            "SELECT currency, SUM(income) FROM records WHERE date BETWEEN ?1 AND ?2 GROUP BY currency"
+        ) else {
+            return HashMap::new();
+        };
+        stmt.query_map([start, end], |row| Ok((row.get(0)?, round2(row.get(1)?))))
+            .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 某月每天的统计，只包含有记录的日期（收入只计主币种）
+    pub fn daily_totals_for_month(&self, year: i32, month: u32) -> Result<Vec<DayStat>> {
+        let (start, end) = month_range(year, month);
+        let mut stmt = self.conn.prepare(
+            "SELECT CAST(substr(date, 9, 2) AS INTEGER),
+                    SUM(CASE WHEN currency = ?3 THEN income ELSE 0 END),
+                    COALESCE(SUM(duration), 0), COUNT(*)
+             FROM records WHERE date BETWEEN ?1 AND ?2 GROUP BY date ORDER BY date"
+        )?;
+        let stats = stmt.query_map([start.as_str(), end.as_str(), DEFAULT_CURRENCY], |row| {
+            Ok(DayStat {
+                day: row.get(0)?,
+                income: round2(row.get(1)?),
+                hours: row.get(2)?,
+                count: row.get(3)?,
+            })
+        })?;
+        stats.collect()
+    }
+
+    /// 某年的收入合计
+    pub fn get_year_balance(&self, year: i32) -> f64 {
+        let (start, end) = year_range(year);
+        self.get_balance_between(&start, &end)
+    }
+
+    /// 某月的收入合计
+    pub fn get_month_balance(&self, year: i32, month: u32) -> f64 {
+        let (start, end) = month_range(year, month);
+        self.get_balance_between(&start, &end)
+    }
+
+    /// 有记录的日期（去重），最近的在前
+    pub fn active_days(&self) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare("SELECT DISTINCT date FROM records ORDER BY date DESC")?;
+        let days = stmt.query_map([], |row| row.get(0))?;
+        days.collect()
+    }
+
+    /// 某天的单数、总时长和主币种收入，未填时长的记录不计入时长
+    pub fn day_stats(&self, date: &str) -> Result<(i64, f64, f64)> {
+        self.conn.query_row(
+            "SELECT COUNT(*),
+                    COALESCE(SUM(duration), 0),
+                    COALESCE(SUM(CASE WHEN currency = ?2 THEN income END), 0)
+             FROM records WHERE date = ?1",
+            rusqlite::params![date, DEFAULT_CURRENCY],
+            |row| Ok((row.get(0)?, row.get(1)?, round2(row.get(2)?))),
+        )
+    }
+
+    /// 全部记录按币种分组的收入合计（标题栏年收入悬停明细中的“累计”）
+    pub fn get_total_balance(&self) -> HashMap<String, f64> {
+        let Ok(mut stmt) = self.conn.prepare("SELECT currency, SUM(income) FROM records GROUP BY currency") else {
+            return HashMap::new();
+        };
+        stmt.query_map([], |row| Ok((row.get(0)?, round2(row.get(1)?))))
+            .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 获取所有老板名称（用于自动补全），查询失败时返回空列表
     pub fn get_all_bosses(&self) -> Vec<String> {
-        let mut stmt = self.conn
-            .prepare("SELECT DISTINCT boss FROM records ORDER BY boss")
-            .unwrap();
-        let bosses = stmt
-            .query_map([], |row| row.get(0))
-            .unwrap()
-            .filter_map(|r| r.ok())
-            .collect();
-        bosses
+        self.query_names("SELECT DISTINCT boss FROM records ORDER BY boss")
     }
 
-    /// 获取所有游戏名称（用于自动补全）
+    /// 获取所有游戏名称（用于自动补全），查询失败时返回空列表
     pub fn get_all_games(&self) -> Vec<String> {
-        let mut stmt = self.conn
-            .prepare("SELECT DISTINCT game FROM records WHERE game IS NOT NULL AND game != '' ORDER BY game")
-            .unwrap();
-        let games = stmt
-            .query_map([], |row| row.get(0))
-            .unwrap()
-            .filter_map(|r| r.ok())
-            .collect();
-        games
+        self.query_names("SELECT DISTINCT game FROM records WHERE game IS NOT NULL AND game != '' ORDER BY game")
+    }
+
+    /// 最近用过的老板（按最后一次记录的日期和录入时间倒序，同时按名称排序），用于输入为空时的联想
+    pub fn recent_bosses(&self, limit: usize) -> Vec<String> {
+        self.query_names(&format!(
+            "SELECT boss FROM records GROUP BY boss
+             ORDER BY MAX(date) DESC, MAX(created_at) DESC, boss LIMIT {}",
+            limit
+        ))
+    }
+
+    /// 最近用过的游戏，排序规则同 recent_bosses
+    pub fn recent_games(&self, limit: usize) -> Vec<String> {
+        self.query_names(&format!(
+            "SELECT game FROM records WHERE game IS NOT NULL AND game != '' GROUP BY game
+             ORDER BY MAX(date) DESC, MAX(created_at) DESC, game LIMIT {}",
+            limit
+        ))
+    }
+
+    /// 执行返回单列文本的查询，出错时退化为空列表，避免刷新数据时整个界面崩溃
+    fn query_names(&self, sql: &str) -> Vec<String> {
+        let Ok(mut stmt) = self.conn.prepare(sql) else {
+            return Vec::new();
+        };
+        stmt.query_map([], |row| row.get(0))
+            .map(|rows| rows.filter_map(|r| r.ok()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 是否已存在同日期、同老板、同金额的记录（用于添加时的重复提醒）
+    pub fn record_exists(&self, date: &str, boss: &str, income: f64) -> bool {
+        self.conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM records WHERE date = ?1 AND boss = ?2 AND income = ?3)",
+                rusqlite::params![date, boss, income],
+                |row| row.get(0),
+            )
+            .unwrap_or(false)
+    }
+
+    /// 是否已存在相同记录（日期、老板、收入、时长、游戏、币种均一致），导入时据此去重
+    fn is_duplicate(&self, r: &Record) -> Result<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM records WHERE date = ?1 AND boss = ?2 AND income = ?3 AND duration IS ?4 AND game IS ?5 AND currency = ?6)",
+            rusqlite::params![r.date, r.boss, r.income, r.duration, r.game, r.currency],
+            |row| row.get(0),
+        )
+    }
+
+    /// 导出全部记录为 JSON（无损备份），返回导出条数
+    pub fn export_json(&self, path: &Path) -> std::result::Result<usize, Box<dyn std::error::Error>> {
+        let records = self.get_all_records()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(&records)?;
+        std::fs::write(path, json)?;
+        Ok(records.len())
+    }
+
+    /// 从 JSON 备份导入记录，已存在的相同记录会跳过，返回新增条数
+    pub fn import_json(&self, path: &Path) -> std::result::Result<usize, Box<dyn std::error::Error>> {
+        let data = std::fs::read_to_string(path)?;
+        let records: Vec<Record> = serde_json::from_str(&data)?;
+        Ok(self.import_records(records)?)
+    }
+
+    /// 在一个事务中批量导入记录：去掉首尾空白，跳过无效记录和已存在的相同记录，返回新增条数
+    pub fn import_records(&self, records: Vec<Record>) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut imported = 0;
+        for mut r in records {
+            r.boss = normalize_name(&r.boss);
+            r.game = r.game.map(|g| normalize_name(&g)).filter(|g| !g.is_empty());
+            r.tags.retain(|t| !t.trim().is_empty());
+            if r.boss.is_empty() || !r.income.is_finite() || self.is_duplicate(&r)? {
+                continue;
+            }
+            self.insert_record(&r)?;
+            imported += 1;
+        }
+        if imported > 0 {
+            self.audit("导入", None, &format!("{} 条", imported))?;
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    /// 保存模板，同名模板会被覆盖
+    pub fn save_template(&self, t: &Template) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO templates (name, boss, game, income, duration) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(name) DO UPDATE SET boss = excluded.boss, game = excluded.game,
+                 income = excluded.income, duration = excluded.duration",
+            rusqlite::params![t.name, t.boss, t.game, t.income, t.duration],
+        )?;
+        Ok(())
+    }
+
+    /// 获取全部模板，按名称排序
+    pub fn list_templates(&self) -> Result<Vec<Template>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, boss, game, income, duration FROM templates ORDER BY name"
+        )?;
+        let templates = stmt.query_map([], |row| {
+            Ok(Template {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                boss: row.get(2)?,
+                game: row.get(3)?,
+                income: row.get(4)?,
+                duration: row.get(5)?,
+            })
+        })?;
+        templates.collect()
+    }
+
+    pub fn delete_template(&self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM templates WHERE id = ?1", [id])?;
+        Ok(())
+    }
+
+    /// 修改录入时间（YYYY-MM-DD HH:MM:SS），影响同一天内的排序
+    pub fn update_created_at(&self, id: i64, created_at: &str) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let old: Option<String> = self.conn
+            .query_row("SELECT created_at FROM records WHERE id = ?1", [id], |row| row.get(0))
+            .optional()?;
+        self.conn.execute(
+            "UPDATE records SET created_at = ?1 WHERE id = ?2",
+            rusqlite::params![created_at, id],
+        )?;
+        if let Some(old) = old {
+            self.audit("修改", Some(id), &format!("录入时间 {} → {}", old, created_at))?;
+        }
+        tx.commit()
+    }
+
+    /// 修改收入金额，金额未变时不写入也不记审计日志
+    pub fn update_income(&self, id: i64, income: f64) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let old: Option<f64> = self.conn
+            .query_row("SELECT income FROM records WHERE id = ?1", [id], |row| row.get(0))
+            .optional()?;
+        if old == Some(income) {
+            return Ok(());
+        }
+        self.conn.execute(
+            "UPDATE records SET income = ?1 WHERE id = ?2",
+            rusqlite::params![income, id],
+        )?;
+        if let Some(old) = old {
+            self.audit("修改", Some(id), &format!("收入 {:.2} → {:.2}", old, income))?;
+        }
+        tx.commit()
+    }
+
+    /// 扫描全部记录，找出空老板名、异常收入和格式错误的日期，只读不改
+    pub fn validate(&self) -> Result<Vec<IntegrityIssue>> {
+        let mut stmt = self.conn.prepare("SELECT id, date, boss, income FROM records ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                row.get::<_, Option<f64>>(3)?,
+            ))
+        })?;
+
+        let mut issues = Vec::new();
+        for row in rows {
+            let (record_id, date, boss, income) = row?;
+            let mut push = |kind| issues.push(IntegrityIssue {
+                record_id,
+                date: date.clone(),
+                boss: boss.clone(),
+                kind,
+            });
+            if boss.trim().is_empty() {
+                push(IssueKind::EmptyBoss);
+            }
+            if income.is_none_or(|v| !v.is_finite() || v < 0.0) {
+                push(IssueKind::InvalidIncome(income));
+            }
+            let canonical = chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .is_ok_and(|d| d.format("%Y-%m-%d").to_string() == date);
+            if !canonical {
+                push(IssueKind::BadDate { fixed: normalize_date(&date) });
+            }
+        }
+        Ok(issues)
+    }
+
+    /// 批量修正日期（数据检查中用户确认后调用），返回修改条数
+    pub fn fix_dates(&self, fixes: &[(i64, String)]) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut changed = 0;
+        for (id, date) in fixes {
+            if self.conn.execute(
+                "UPDATE records SET date = ?1 WHERE id = ?2",
+                rusqlite::params![date, id],
+            )? > 0 {
+                self.audit("修正日期", Some(*id), date)?;
+                changed += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// 批量结清：boss、period（YYYY-MM 整月或 YYYY-MM-DD 单日）为 None 时不限，返回实际改动的条数
+    pub fn settle_all(&self, boss: Option<&str>, period: Option<&str>) -> Result<usize> {
+        self.set_settled_where(boss, period, true)
+    }
+
+    /// 按老板汇总未结清的主币种收入，只列出有欠款的老板，金额大的在前
+    pub fn unsettled_by_boss(&self) -> Result<Vec<(String, f64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT boss, SUM(income) AS owed FROM records
+             WHERE COALESCE(settled, 0) = 0 AND currency = ?1
+             GROUP BY boss HAVING owed > 0
+             ORDER BY owed DESC, boss",
+        )?;
+        let rows = stmt.query_map([DEFAULT_CURRENCY], |row| Ok((row.get(0)?, round2(row.get(1)?))))?;
+        rows.collect()
+    }
+
+    /// 批量取消结清，范围同 settle_all
+    pub fn unsettle_all(&self, boss: Option<&str>, period: Option<&str>) -> Result<usize> {
+        self.set_settled_where(boss, period, false)
+    }
+
+    fn set_settled_where(&self, boss: Option<&str>, period: Option<&str>, settled: bool) -> Result<usize> {
+        let (start, end) = match period {
+            Some(d) if d.len() == 10 => (Some(d.to_string()), Some(d.to_string())),
+            Some(m) => (Some(format!("{}-01", m)), Some(format!("{}-31", m))),
+            None => (None, None),
+        };
+        let tx = self.conn.unchecked_transaction()?;
+        let changed = tx.execute(
+            "UPDATE records SET settled = ?1
+             WHERE COALESCE(settled, 0) != ?1
+               AND (?2 IS NULL OR boss = ?2)
+               AND (?3 IS NULL OR date BETWEEN ?3 AND ?4)",
+            rusqlite::params![settled as i64, boss, start, end],
+        )?;
+        if changed > 0 {
+            let action = if settled { "批量结清" } else { "批量取消结清" };
+            let period = period.map(|p| if p.len() == 10 { format!("日期 {}", p) } else { format!("月份 {}", p) });
+            let scope = [boss.map(|b| format!("老板 {}", b)), period]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join("，");
+            let details = if scope.is_empty() { format!("{} 条", changed) } else { format!("{} 条（{}）", changed, scope) };
+            self.audit(action, None, &details)?;
+        }
+        tx.commit()?;
+        Ok(changed)
     }
 
     /// 更新结清状态
     pub fn update_settled(&self, id: i64, settled: bool) -> Result<()> {
-        self.conn.execute(
+        let tx = self.conn.unchecked_transaction()?;
+        if self.conn.execute(
             "UPDATE records SET settled = ?1 WHERE id = ?2",
             [settled as i64, id],
-        )?;
-        Ok(())
+        )? > 0 {
+            let details = self.describe_record_id(id)?;
+            self.audit(if settled { "结清" } else { "取消结清" }, Some(id), &details)?;
+        }
+        tx.commit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 加入版本号之前的最初表结构（只有日期、老板、收入和录入时间）
+    fn baseline_db() -> Database {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE records (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                date TEXT NOT NULL,
+                boss TEXT NOT NULL,
+                income REAL NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now', 'localtime'))
+            )",
+        )
+        .unwrap();
+        Database { conn }
+    }
+
+    fn user_version(db: &Database) -> usize {
+        db.conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn round2_removes_float_drift() {
+        assert_ne!(0.1 + 0.2, 0.3);
+        assert_eq!(round2(0.1 + 0.2), 0.3);
+        let sum: f64 = std::iter::repeat_n(0.1, 85_000).sum();
+        assert_eq!(round2(sum), 8500.0);
+        assert_eq!(round2(-0.000_000_1), 0.0);
+    }
+
+    #[test]
+    fn migrate_from_v0_adds_every_column() {
+        let db = baseline_db();
+        assert_eq!(user_version(&db), 0);
+        db.migrate().unwrap();
+        assert_eq!(user_version(&db), MIGRATIONS.len());
+        for m in MIGRATIONS {
+            assert!(db.has_column(m.table, m.column).unwrap(), "缺少列 {}", m.column);
+        }
+    }
+
+    #[test]
+    fn migrate_is_idempotent() {
+        let db = baseline_db();
+        db.migrate().unwrap();
+        // 已是最新版本：不做任何事
+        db.migrate().unwrap();
+        assert_eq!(user_version(&db), MIGRATIONS.len());
+        // 版本号丢失但列已存在（加入版本号之前的旧库）：只补版本号，不重复 ALTER
+        db.conn.pragma_update(None, "user_version", 0).unwrap();
+        db.migrate().unwrap();
+        assert_eq!(user_version(&db), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn migrate_keeps_existing_rows() {
+        let db = baseline_db();
+        db.conn.execute("INSERT INTO records (date, boss, income) VALUES ('2024-06-01', '小王', 100)", []).unwrap();
+        db.migrate().unwrap();
+        let (currency, settled): (String, i64) = db.conn
+            .query_row("SELECT currency, settled FROM records", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(currency, DEFAULT_CURRENCY);
+        assert_eq!(settled, 0);
+    }
+
+    /// 完整表结构的内存数据库
+    fn memory_db() -> Database {
+        let db = Database { conn: Connection::open_in_memory().unwrap() };
+        db.init().unwrap();
+        db
+    }
+
+    #[test]
+    fn month_query_uses_date_index() {
+        let db = memory_db();
+        let (start, end) = month_range(2024, 6);
+        let sql = format!("EXPLAIN QUERY PLAN {}", records_sql("WHERE date BETWEEN ?1 AND ?2"));
+        let mut stmt = db.conn.prepare(&sql).unwrap();
+        let plan: Vec<String> = stmt.query_map([start, end], |row| row.get(3))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert!(plan.iter().any(|d| d.contains("idx_records_date")), "{plan:?}");
+        assert!(!plan.iter().any(|d| d.starts_with("SCAN records")), "{plan:?}");
+    }
+
+    #[test]
+    fn day_stats_mixed_durations() {
+        let db = memory_db();
+        assert_eq!(db.day_stats("2024-06-01").unwrap(), (0, 0.0, 0.0));
+
+        db.add_record(&Record { duration: Some(1.5), ..Record::sample("2024-06-01", "小王", 100.0) }).unwrap();
+        db.add_record(&Record::sample("2024-06-01", "老李", 50.0)).unwrap();
+        db.add_record(&Record { duration: Some(2.0), settled: true, ..Record::sample("2024-06-01", "阿张", 0.1) }).unwrap();
+        db.add_record(&Record { duration: Some(3.0), ..Record::sample("2024-06-02", "小王", 999.0) }).unwrap();
+        assert_eq!(db.day_stats("2024-06-01").unwrap(), (3, 3.5, 150.1));
+
+        // 只有未填时长的记录时时长为 0 而不是 NULL
+        assert_eq!(db.day_stats("2024-06-03").unwrap(), (0, 0.0, 0.0));
+        db.add_record(&Record::sample("2024-06-03", "小王", 20.0)).unwrap();
+        assert_eq!(db.day_stats("2024-06-03").unwrap(), (1, 0.0, 20.0));
+    }
+
+    #[test]
+    fn unsettled_by_boss_mixed_rows() {
+        let db = memory_db();
+        db.add_record(&Record::sample("2024-06-01", "小王", 100.0)).unwrap();
+        db.add_record(&Record { settled: true, ..Record::sample("2024-06-02", "小王", 50.0) }).unwrap();
+        db.add_record(&Record::sample("2024-06-03", "小王", 30.0)).unwrap();
+        db.add_record(&Record::sample("2024-06-01", "老李", 200.0)).unwrap();
+        db.add_record(&Record { settled: true, ..Record::sample("2024-06-02", "阿张", 80.0) }).unwrap();
+        // 其他币种不计入
+        db.add_record(&Record { currency: "USD".to_string(), ..Record::sample("2024-06-02", "小王", 10.0) }).unwrap();
+        assert_eq!(
+            db.unsettled_by_boss().unwrap(),
+            vec![("老李".to_string(), 200.0), ("小王".to_string(), 130.0)],
+        );
+    }
+
+    #[test]
+    fn normalize_name_whitespace_and_full_width() {
+        assert_eq!(normalize_name("  小王  "), "小王");
+        assert_eq!(normalize_name("小 王"), "小王");
+        assert_eq!(normalize_name("小\u{3000}王"), "小王");
+        assert_eq!(normalize_name("Tom   Lee"), "Tom Lee");
+        assert_eq!(normalize_name("Tom\u{3000}Lee"), "Tom Lee");
+        assert_eq!(normalize_name("ＡＢＣ１２"), "ABC12");
+        assert_eq!(normalize_name("小王 Tom"), "小王 Tom");
+        assert_eq!(normalize_name("\t\u{3000} "), "");
+    }
+
+    #[test]
+    fn replace_file_failure_keeps_file_connection() {
+        let dir = std::env::temp_dir().join(format!("jz-replace-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("records.db");
+        let _ = std::fs::remove_file(&db_path);
+        let mut db = Database { conn: Database::connect(&db_path, None).unwrap() };
+        db.init().unwrap();
+        db.add_record(&Record::sample("2024-06-01", "小王", 100.0)).unwrap();
+
+        // 加密副本不存在：替换失败，仍使用原文件，之前和之后的记录都在磁盘上
+        assert!(db.replace_file(&db_path, &dir.join("missing.db.encrypting"), Some("key"), None).is_err());
+        assert_eq!(db.conn.path(), Some(db_path.to_str().unwrap()));
+        db.add_record(&Record::sample("2024-06-02", "老李", 50.0)).unwrap();
+        drop(db);
+
+        let reopened = Database { conn: Database::connect(&db_path, None).unwrap() };
+        let count: i64 = reopened.conn.query_row("SELECT count(*) FROM records", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 2);
+        drop(reopened);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rename_boss_normalizes_new_name() {
+        let db = memory_db();
+        db.add_record(&Record::sample("2024-06-01", "王", 100.0)).unwrap();
+        db.add_record(&Record::sample("2024-06-02", "小王", 50.0)).unwrap();
+        assert_eq!(db.rename_boss("王", "小\u{3000} 王").unwrap(), 1);
+        let bosses: Vec<String> = db.get_all_records().unwrap().into_iter().map(|r| r.boss).collect();
+        assert_eq!(bosses, vec!["小王", "小王"]);
+    }
+
+    #[test]
+    fn update_income_skips_unchanged_value() {
+        let db = memory_db();
+        let id = db.add_record(&Record::sample("2024-06-01", "小王", 150.0)).unwrap();
+        let before = db.recent_audit(100).unwrap().len();
+        db.update_income(id, 150.0).unwrap();
+        assert_eq!(db.recent_audit(100).unwrap().len(), before);
+        db.update_income(id, 200.0).unwrap();
+        assert_eq!(db.recent_audit(100).unwrap().len(), before + 1);
+    }
+
+    // 只有这个测试读写 JZ_DATA_DIR，避免并行测试互相影响
+    #[test]
+    fn data_dir_env_override() {
+        let custom = std::env::temp_dir().join("jz-data-dir-test");
+        std::env::set_var(DATA_DIR_ENV, &custom);
+        assert_eq!(Database::data_dir(), custom);
+        assert_eq!(Database::get_db_path(), custom.join("records.db"));
+
+        // 空值视为未设置
+        std::env::set_var(DATA_DIR_ENV, "");
+        let default = Database::data_dir();
+        assert_ne!(default, custom);
+        assert!(default.ends_with("jz"));
+
+        std::env::remove_var(DATA_DIR_ENV);
+        assert_eq!(Database::data_dir(), default);
     }
 }
@@ -0,0 +1,367 @@
+use crate::db::{round2, Record, TimerSession, DEFAULT_CURRENCY};
+use chrono::{Datelike, NaiveDate};
+use printpdf::{IndirectFontRef, Line, Mm, PdfDocument, PdfLayerReference, Point};
+use rust_xlsxwriter::{Format, Workbook, XlsxError};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+
+/// 内嵌的中文字体（站酷快乐体），界面标题和 PDF 账单共用
+pub const CJK_FONT: &[u8] = include_bytes!("../fonts/ZCOOLKuaiLe-Regular.ttf");
+
+/// 导出文件的默认目录（优先下载目录，取不到时退回数据目录）
+pub fn default_export_dir() -> PathBuf {
+    dirs::download_dir().unwrap_or_else(|| {
+        let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("jz");
+        path
+    })
+}
+
+/// CSV 字段转义：含逗号、引号或换行时用双引号包裹
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// 将一条记录格式化为 CSV 行（不含换行），列顺序与导出表头一致
+/// 收入按数据库中的原值写出（不截断小数位），导出后再导入不会丢失精度；只有合计行保留两位小数
+pub fn record_to_csv_line(r: &Record) -> String {
+    let duration = r.duration.map(|d| d.to_string()).unwrap_or_default();
+    format!(
+        "{},{},{},{},{},{},{}",
+        csv_field(&r.date),
+        csv_field(&r.boss),
+        csv_field(r.game.as_deref().unwrap_or("")),
+        duration,
+        r.income,
+        csv_field(&r.currency),
+        if r.settled { "是" } else { "否" },
+    )
+}
+
+/// 记录的合计：总时长（小时）和主币种总收入，与表格底部的合计行一致
+/// 两者都经过 round2，避免浮点累加误差出现在显示和导出中
+pub fn record_totals(records: &[Record]) -> (f64, f64) {
+    let hours: f64 = records.iter().filter_map(|r| r.duration).sum();
+    let income = records.iter()
+        .filter(|r| r.currency == DEFAULT_CURRENCY)
+        .map(|r| r.income)
+        .sum();
+    (round2(hours), round2(income))
+}
+
+/// 平均时薪：只统计填了时长的主币种记录，没有这样的记录时为 None
+pub fn average_hourly(records: &[Record]) -> Option<f64> {
+    let (hours, income) = records.iter()
+        .filter(|r| r.currency == DEFAULT_CURRENCY)
+        .filter_map(|r| r.duration.filter(|d| *d > 0.0).map(|d| (d, r.income)))
+        .fold((0.0, 0.0), |(h, i), (d, income)| (h + d, i + income));
+    (hours > 0.0).then(|| income / hours)
+}
+
+/// 将记录导出为 CSV
+/// 首行以 `#` 开头记录筛选条件，文件带 UTF-8 BOM 以便 Excel 正确识别中文
+/// summary 为 true 时在数据后空一行，追加合计和平均时薪两行
+pub fn export_csv(records: &[Record], path: &Path, filter_desc: &str, summary: bool) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writer.write_all("\u{feff}".as_bytes())?;
+    writeln!(writer, "# 筛选：{}", filter_desc)?;
+    writeln!(writer, "日期,老板,游戏,时长,收入,币种,结清")?;
+    for r in records {
+        writeln!(writer, "{}", record_to_csv_line(r))?;
+    }
+    if summary {
+        let (hours, income) = record_totals(records);
+        writeln!(writer)?;
+        writeln!(writer, "合计,,,{},{:.2},{},", hours, income, DEFAULT_CURRENCY)?;
+        let average = average_hourly(records).map(|v| format!("{:.2}", v)).unwrap_or_default();
+        writeln!(writer, "平均时薪,,,,{},{},", average, DEFAULT_CURRENCY)?;
+    }
+    writer.flush()
+}
+
+/// 按月份分组，每月写一个 `jz-YYYY-MM.csv` 到指定目录，没有记录的月份不生成文件
+/// 日期无法解析的记录（如旧数据或手改数据库）单独写入 `jz-日期无效.csv`，返回写入的文件数
+pub fn export_csv_by_month(records: &[Record], dir: &Path, summary: bool) -> std::io::Result<usize> {
+    let mut by_month: BTreeMap<(i32, u32), Vec<Record>> = BTreeMap::new();
+    let mut invalid = Vec::new();
+    for r in records {
+        match NaiveDate::parse_from_str(&r.date, "%Y-%m-%d") {
+            Ok(date) => by_month.entry((date.year(), date.month())).or_default().push(r.clone()),
+            Err(_) => invalid.push(r.clone()),
+        }
+    }
+    for ((year, month), group) in &by_month {
+        export_csv(group, &dir.join(format!("jz-{}-{:02}.csv", year, month)), &format!("{}年{}月", year, month), summary)?;
+    }
+    if !invalid.is_empty() {
+        export_csv(&invalid, &dir.join("jz-日期无效.csv"), "日期无效", summary)?;
+    }
+    Ok(by_month.len() + usize::from(!invalid.is_empty()))
+}
+
+/// 将计时记录导出为 CSV（带 UTF-8 BOM），时长同时给出秒数和小时数
+pub fn export_timer_sessions_csv(sessions: &[TimerSession], path: &Path) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    writer.write_all("\u{feff}".as_bytes())?;
+    writeln!(writer, "开始,结束,秒数,小时,关联记录")?;
+    for s in sessions {
+        writeln!(
+            writer,
+            "{},{},{},{:.2},{}",
+            csv_field(&s.start),
+            csv_field(&s.end),
+            s.seconds,
+            s.seconds as f64 / 3600.0,
+            s.linked_record_id.map(|id| id.to_string()).unwrap_or_default(),
+        )?;
+    }
+    writer.flush()
+}
+
+/// 将图表截图（RGBA 像素）保存为 PNG
+pub fn export_png(rgba: &[u8], width: u32, height: u32, path: &Path) -> image::ImageResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    image::save_buffer_with_format(path, rgba, width, height, image::ExtendedColorType::Rgba8, image::ImageFormat::Png)
+}
+
+/// Excel 数字格式：按设置的币种符号和小数位数，如 `"$"#,##0`、`"¥"#,##0.00`
+/// 符号放在双引号中按原样显示，符号中的双引号会被去掉
+fn xlsx_money_format(symbol: &str, decimals: usize) -> String {
+    let symbol = symbol.replace('"', "");
+    let prefix = if symbol.is_empty() { String::new() } else { format!("\"{}\"", symbol) };
+    let frac = if decimals > 0 { format!(".{}", "0".repeat(decimals)) } else { String::new() };
+    format!("{}#,##0{}", prefix, frac)
+}
+
+/// 将记录导出为 Excel 工作簿
+/// 首行为冻结的表头，收入列使用货币格式（非主币种不带符号），末尾追加公式合计行
+/// 收入合计只统计主币种，避免不同币种相加；货币符号和小数位数与界面设置一致
+pub fn export_xlsx(records: &[Record], path: &Path, symbol: &str, decimals: usize) -> Result<(), XlsxError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut workbook = Workbook::new();
+    let sheet = workbook.add_worksheet();
+
+    let header_format = Format::new().set_bold();
+    let money_num_format = xlsx_money_format(symbol, decimals);
+    let money_format = Format::new().set_num_format(&money_num_format);
+    let plain_money_format = Format::new().set_num_format(xlsx_money_format("", decimals));
+    let total_money_format = Format::new().set_bold().set_num_format(&money_num_format);
+    let bold = Format::new().set_bold();
+
+    let headers = ["日期", "老板", "游戏", "时长", "收入", "币种", "结清"];
+    for (col, title) in headers.iter().enumerate() {
+        sheet.write_string_with_format(0, col as u16, *title, &header_format)?;
+    }
+    sheet.set_freeze_panes(1, 0)?;
+    sheet.set_column_width(0, 12)?;
+    sheet.set_column_width(1, 14)?;
+    sheet.set_column_width(2, 16)?;
+    sheet.set_column_width(4, 14)?;
+
+    for (i, r) in records.iter().enumerate() {
+        let row = i as u32 + 1;
+        sheet.write_string(row, 0, &r.date)?;
+        sheet.write_string(row, 1, &r.boss)?;
+        sheet.write_string(row, 2, r.game.as_deref().unwrap_or(""))?;
+        if let Some(d) = r.duration {
+            sheet.write_number(row, 3, d)?;
+        }
+        let format = if r.currency == DEFAULT_CURRENCY { &money_format } else { &plain_money_format };
+        sheet.write_number_with_format(row, 4, r.income, format)?;
+        sheet.write_string(row, 5, &r.currency)?;
+        sheet.write_string(row, 6, if r.settled { "是" } else { "否" })?;
+    }
+
+    // 合计行
+    let total_row = records.len() as u32 + 1;
+    sheet.write_string_with_format(total_row, 0, "合计", &bold)?;
+    if !records.is_empty() {
+        sheet.write_formula_with_format(total_row, 3, format!("=SUM(D2:D{})", total_row).as_str(), &bold)?;
+        sheet.write_formula_with_format(total_row, 4, format!("=SUMIF(F2:F{0},\"{1}\",E2:E{0})", total_row, DEFAULT_CURRENCY).as_str(), &total_money_format)?;
+    }
+
+    workbook.save(path)
+}
+
+// A4 纵向页面尺寸和边距（毫米）
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const PAGE_MARGIN: f32 = 20.0;
+const ROW_HEIGHT: f32 = 7.0;
+
+/// 账单表格各列的横坐标：日期、游戏、时长、收入、结清
+const INVOICE_COLUMNS: [f32; 5] = [20.0, 48.0, 108.0, 132.0, 172.0];
+
+/// 在页面上画一条横线
+fn draw_rule(layer: &PdfLayerReference, y: f32) {
+    layer.add_line(Line {
+        points: vec![
+            (Point::new(Mm(PAGE_MARGIN), Mm(y)), false),
+            (Point::new(Mm(PAGE_WIDTH - PAGE_MARGIN), Mm(y)), false),
+        ],
+        is_closed: false,
+    });
+}
+
+fn draw_row(layer: &PdfLayerReference, font: &IndirectFontRef, y: f32, cells: [&str; 5]) {
+    for (x, text) in INVOICE_COLUMNS.iter().zip(cells) {
+        layer.use_text(text, 10.0, Mm(*x), Mm(y), font);
+    }
+}
+
+/// 导出某老板某月的 PDF 账单：逐单列出服务记录，末尾按币种给出合计和已收/未收
+/// month 为 YYYY-MM，records 可以包含其他老板或月份的记录，会在这里筛选
+pub fn export_boss_invoice(records: &[Record], boss: &str, month: &str, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut rows: Vec<&Record> = records.iter()
+        .filter(|r| r.boss == boss && r.date.starts_with(month))
+        .collect();
+    rows.sort_by(|a, b| a.date.cmp(&b.date).then(a.id.cmp(&b.id)));
+
+    let title = format!("{} 月度账单", crate::APP_TITLE);
+    let (doc, page, layer) = PdfDocument::new(&title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "账单");
+    let font = doc.add_external_font(Cursor::new(CJK_FONT))?;
+    let mut layer = doc.get_page(page).get_layer(layer);
+
+    let mut y = PAGE_HEIGHT - PAGE_MARGIN;
+    layer.use_text(&title, 18.0, Mm(PAGE_MARGIN), Mm(y), &font);
+    y -= 10.0;
+    layer.use_text(format!("老板：{}    月份：{}", boss, month), 12.0, Mm(PAGE_MARGIN), Mm(y), &font);
+    y -= 6.0;
+    let generated = chrono::Local::now().format("%Y-%m-%d %H:%M").to_string();
+    layer.use_text(format!("生成日期：{}", generated), 9.0, Mm(PAGE_MARGIN), Mm(y), &font);
+    y -= 10.0;
+
+    let header = ["日期", "游戏", "时长(h)", "收入", "结清"];
+    draw_row(&layer, &font, y, header);
+    draw_rule(&layer, y - 2.0);
+    y -= ROW_HEIGHT + 1.0;
+
+    // 合计：币种 -> (已收, 未收)
+    let mut totals: BTreeMap<&str, (f64, f64)> = BTreeMap::new();
+    let mut hours = 0.0;
+    for r in &rows {
+        // 写满一页后换页并重复表头
+        if y < PAGE_MARGIN {
+            let (page, new_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "账单");
+            layer = doc.get_page(page).get_layer(new_layer);
+            y = PAGE_HEIGHT - PAGE_MARGIN;
+            draw_row(&layer, &font, y, header);
+            draw_rule(&layer, y - 2.0);
+            y -= ROW_HEIGHT + 1.0;
+        }
+        let duration = r.duration.map(|d| format!("{:.1}", d)).unwrap_or_default();
+        let income = format!("{:.2} {}", r.income, r.currency);
+        let settled = if r.settled { "是" } else { "否" };
+        draw_row(&layer, &font, y, [&r.date, r.game.as_deref().unwrap_or(""), &duration, &income, settled]);
+        y -= ROW_HEIGHT;
+
+        hours += r.duration.unwrap_or(0.0);
+        let entry = totals.entry(r.currency.as_str()).or_default();
+        if r.settled {
+            entry.0 += r.income;
+        } else {
+            entry.1 += r.income;
+        }
+    }
+
+    // 合计区需要的高度不够时换页
+    let summary_height = ROW_HEIGHT * (totals.len() as f32 + 2.0);
+    if y - summary_height < PAGE_MARGIN {
+        let (page, new_layer) = doc.add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "账单");
+        layer = doc.get_page(page).get_layer(new_layer);
+        y = PAGE_HEIGHT - PAGE_MARGIN;
+    }
+    draw_rule(&layer, y + ROW_HEIGHT - 2.0);
+    layer.use_text(format!("共 {} 单，{:.1} 小时", rows.len(), hours), 11.0, Mm(PAGE_MARGIN), Mm(y), &font);
+    y -= ROW_HEIGHT;
+    // 主币种排在最前
+    let mut currencies: Vec<_> = totals.into_iter().collect();
+    currencies.sort_by_key(|(c, _)| *c != DEFAULT_CURRENCY);
+    for (currency, (settled, unsettled)) in currencies {
+        let line = format!(
+            "合计 {:.2} {}    已收 {:.2}    未收 {:.2}",
+            settled + unsettled, currency, settled, unsettled
+        );
+        layer.use_text(line, 11.0, Mm(PAGE_MARGIN), Mm(y), &font);
+        y -= ROW_HEIGHT;
+    }
+
+    doc.save(&mut BufWriter::new(File::create(path)?))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_have_no_float_drift() {
+        let records = vec![
+            Record { duration: Some(1.1), ..Record::sample("2024-06-01", "小王", 0.1) },
+            Record { duration: Some(2.2), ..Record::sample("2024-06-01", "小王", 0.2) },
+        ];
+        let (hours, income) = record_totals(&records);
+        assert_eq!(income, 0.3);
+        assert_eq!(hours, 3.3);
+        assert_eq!(format!("{}", income), "0.3");
+    }
+
+    #[test]
+    fn xlsx_money_format_follows_settings() {
+        assert_eq!(xlsx_money_format("¥", 2), "\"¥\"#,##0.00");
+        assert_eq!(xlsx_money_format("$", 0), "\"$\"#,##0");
+        assert_eq!(xlsx_money_format("", 3), "#,##0.000");
+        assert_eq!(xlsx_money_format("a\"b", 1), "\"ab\"#,##0.0");
+    }
+
+    #[test]
+    fn csv_line_keeps_stored_precision() {
+        let line = record_to_csv_line(&Record::sample("2024-06-01", "小王", 12.345));
+        assert_eq!(line, "2024-06-01,小王,,,12.345,CNY,否");
+    }
+
+    #[test]
+    fn by_month_puts_bad_dates_in_own_file() {
+        let dir = std::env::temp_dir().join(format!("jz-export-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let dated = |date: &str| Record::sample(date, "小王", 10.0);
+        let records = vec![
+            dated("2024-05-31"),
+            dated("2024-06-01"),
+            dated("2024-06-30"),
+            dated("2024-13-01"),
+            dated("2024-06"),
+            dated(""),
+        ];
+        assert_eq!(export_csv_by_month(&records, &dir, false).unwrap(), 3);
+
+        let lines = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap().lines().count();
+        // 筛选说明 + 表头 + 记录行
+        assert_eq!(lines("jz-2024-05.csv"), 3);
+        assert_eq!(lines("jz-2024-06.csv"), 4);
+        assert_eq!(lines("jz-日期无效.csv"), 5);
+        assert!(!dir.join("jz-2024-13.csv").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
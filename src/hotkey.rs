@@ -0,0 +1,59 @@
+//! 全局快捷键：在其他程序中按下后把窗口带到前台并聚焦老板输入框
+//! 平台后端不可用（如 Wayland 下没有 X11）时创建失败，调用方提示后忽略即可
+
+use eframe::egui::{self, ViewportCommand};
+use global_hotkey::hotkey::HotKey;
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+/// 快捷键已按下，等待 App 在下一帧聚焦输入框
+static TRIGGERED: AtomicBool = AtomicBool::new(false);
+/// 事件回调只能设置一次（库内部为 OnceLock），更换组合键时沿用
+static HANDLER: Once = Once::new();
+
+pub struct GlobalHotkey {
+    manager: GlobalHotKeyManager,
+    hotkey: HotKey,
+    combo: String,  // 注册时的组合键文本，设置变化时据此判断是否重新注册
+}
+
+impl GlobalHotkey {
+    /// 注册组合键，格式如 "Ctrl+Alt+J"
+    pub fn new(ctx: &egui::Context, combo: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let hotkey: HotKey = combo.parse()?;
+        let manager = GlobalHotKeyManager::new()?;
+        manager.register(hotkey)?;
+
+        // 窗口隐藏或最小化时 update 不一定运行，直接向窗口发送命令
+        HANDLER.call_once(|| {
+            let ctx = ctx.clone();
+            GlobalHotKeyEvent::set_event_handler(Some(move |event: GlobalHotKeyEvent| {
+                if event.state() == HotKeyState::Pressed {
+                    TRIGGERED.store(true, Ordering::SeqCst);
+                    ctx.send_viewport_cmd(ViewportCommand::Visible(true));
+                    ctx.send_viewport_cmd(ViewportCommand::Minimized(false));
+                    ctx.send_viewport_cmd(ViewportCommand::Focus);
+                    ctx.request_repaint();
+                }
+            }));
+        });
+
+        Ok(Self { manager, hotkey, combo: combo.to_string() })
+    }
+
+    pub fn combo(&self) -> &str {
+        &self.combo
+    }
+
+    /// 取出并清除“已按下”状态
+    pub fn take_triggered(&self) -> bool {
+        TRIGGERED.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl Drop for GlobalHotkey {
+    fn drop(&mut self) {
+        let _ = self.manager.unregister(self.hotkey);
+    }
+}
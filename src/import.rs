@@ -0,0 +1,309 @@
+use crate::db::{Record, DEFAULT_CURRENCY};
+use chrono::NaiveDate;
+
+/// 本程序导出 CSV 时在末尾追加的合计行（见 export::export_csv）的首列，导入时跳过
+const SUMMARY_LABELS: [&str; 2] = ["合计", "平均时薪"];
+
+/// 解析 CSV 文本：支持双引号包裹、字段内逗号/换行和 "" 转义，自动去掉 UTF-8 BOM，跳过空行
+/// 开头以 `#` 起始的注释行（如导出时写入的筛选条件）一并跳过
+pub fn parse_csv(text: &str) -> Vec<Vec<String>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                if row.iter().any(|f| !f.trim().is_empty()) {
+                    rows.push(std::mem::take(&mut row));
+                } else {
+                    row.clear();
+                }
+            }
+            _ => field.push(c),
+        }
+    }
+    row.push(field);
+    if row.iter().any(|f| !f.trim().is_empty()) {
+        rows.push(row);
+    }
+    let comments = rows.iter()
+        .take_while(|r| r.first().is_some_and(|f| f.trim_start().starts_with('#')))
+        .count();
+    rows.drain(..comments);
+    rows
+}
+
+/// 去掉本程序导出的 CSV 末尾的合计和平均时薪行，它们不是记录
+pub fn strip_summary_rows(rows: &mut Vec<Vec<String>>) {
+    while rows.last().and_then(|r| r.first()).is_some_and(|f| SUMMARY_LABELS.contains(&f.trim())) {
+        rows.pop();
+    }
+}
+
+/// 源文件中的日期写法
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateFormat {
+    Ymd,  // YYYY-MM-DD / YYYY/MM/DD
+    Mdy,  // MM-DD-YYYY / MM/DD/YYYY
+    Dmy,  // DD-MM-YYYY / DD/MM/YYYY
+}
+
+impl DateFormat {
+    pub const ALL: [DateFormat; 3] = [DateFormat::Ymd, DateFormat::Mdy, DateFormat::Dmy];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DateFormat::Ymd => "YYYY/MM/DD",
+            DateFormat::Mdy => "MM-DD-YYYY",
+            DateFormat::Dmy => "DD/MM/YYYY",
+        }
+    }
+
+    /// 解析日期并规范为 YYYY-MM-DD；分隔符可以是 - / .，日期后的时间部分会被忽略
+    pub fn parse(self, s: &str) -> Option<String> {
+        let date_part = s.split_whitespace().next()?.replace(['/', '.'], "-");
+        let fmt = match self {
+            DateFormat::Ymd => "%Y-%m-%d",
+            DateFormat::Mdy => "%m-%d-%Y",
+            DateFormat::Dmy => "%d-%m-%Y",
+        };
+        NaiveDate::parse_from_str(&date_part, fmt)
+            .ok()
+            .map(|d| d.format("%Y-%m-%d").to_string())
+    }
+}
+
+/// 源文件各列对应到记录字段（列下标），日期、老板、金额必填
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnMapping {
+    pub date: Option<usize>,
+    pub boss: Option<usize>,
+    pub income: Option<usize>,
+    pub game: Option<usize>,
+    pub duration: Option<usize>,
+    pub currency: Option<usize>,  // 未映射或为空时为主币种
+    pub settled: Option<usize>,   // 未映射或为空时为未结清
+}
+
+impl ColumnMapping {
+    /// 根据表头名称猜测映射，猜不到的留空由用户选择
+    pub fn guess(header: &[String]) -> Self {
+        let find = |keys: &[&str]| {
+            header.iter().position(|h| {
+                let h = h.trim().to_lowercase();
+                keys.iter().any(|k| h.contains(k))
+            })
+        };
+        Self {
+            date: find(&["日期", "时间", "date", "time"]),
+            boss: find(&["老板", "客户", "对方", "付款人", "boss", "name", "payer"]),
+            income: find(&["收入", "金额", "amount", "income"]),
+            game: find(&["游戏", "game"]),
+            duration: find(&["时长", "duration", "hours"]),
+            currency: find(&["币种", "货币", "currency"]),
+            settled: find(&["结清", "settled"]),
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.date.is_some() && self.boss.is_some() && self.income.is_some()
+    }
+
+    /// 按映射把一行转换为记录，失败时返回原因
+    pub fn map_row(&self, row: &[String], date_format: DateFormat) -> Result<Record, String> {
+        let cell = |col: Option<usize>| col.and_then(|i| row.get(i)).map(|s| s.trim()).unwrap_or("");
+
+        let date_text = cell(self.date);
+        let date = date_format.parse(date_text).ok_or_else(|| format!("日期无法识别：{}", date_text))?;
+        let boss = cell(self.boss);
+        if boss.is_empty() {
+            return Err("老板为空".to_string());
+        }
+        let income_text = cell(self.income);
+        let income = parse_amount(income_text)
+            .filter(|v| *v > 0.0)
+            .ok_or_else(|| format!("金额无效：{}", income_text))?;
+        let game = Some(cell(self.game)).filter(|g| !g.is_empty()).map(str::to_string);
+        let duration = parse_amount(cell(self.duration).trim_end_matches(['h', 'H']))
+            .filter(|d| *d > 0.0);
+        let currency = match cell(self.currency) {
+            "" => DEFAULT_CURRENCY.to_string(),
+            code => code.to_uppercase(),
+        };
+        let settled_text = cell(self.settled);
+        let settled = parse_settled(settled_text).ok_or_else(|| format!("结清无法识别：{}", settled_text))?;
+
+        Ok(Record {
+            date,
+            boss: boss.to_string(),
+            income,
+            duration,
+            game,
+            settled,
+            currency,
+            ..Record::default()
+        })
+    }
+}
+
+/// 解析结清列：是/否、true/false、1/0、yes/no（不区分大小写），空白视为未结清
+fn parse_settled(s: &str) -> Option<bool> {
+    match s.to_lowercase().as_str() {
+        "是" | "true" | "1" | "yes" | "y" => Some(true),
+        "否" | "false" | "0" | "no" | "n" | "" => Some(false),
+        _ => None,
+    }
+}
+
+/// 解析金额或时长，手动输入和 CSV 导入共用：
+/// 全角数字、小数点和正负号转为半角，忽略货币符号（¥ ￥ $ 元）和空白，如“１５０”“¥1,500”“200元”
+/// 正负号只能在开头，千分位逗号（半角、全角）必须每三位一组；不接受 1e3 这样的指数写法
+/// 处理后仍不是普通小数时返回 None
+pub fn parse_amount(s: &str) -> Option<f64> {
+    let cleaned: String = s.chars()
+        .map(|c| match c {
+            '０'..='９' | '．' | '－' | '＋' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+            '，' => ',',
+            _ => c,
+        })
+        .filter(|c| !matches!(c, '¥' | '￥' | '$' | '元') && !c.is_whitespace())
+        .collect();
+    let (negative, unsigned) = match cleaned.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, cleaned.strip_prefix('+').unwrap_or(&cleaned)),
+    };
+    if !unsigned.chars().all(|c| c.is_ascii_digit() || c == ',' || c == '.') {
+        return None;
+    }
+    let (int_part, frac_part) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if frac_part.contains(',') || !digit_groups_valid(int_part) {
+        return None;
+    }
+    let value = format!("{}.{}", int_part.replace(',', ""), frac_part).parse::<f64>().ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// 整数部分的千分位分组：没有逗号时任意位数，有逗号时首组 1-3 位、其余每组正好 3 位
+fn digit_groups_valid(int_part: &str) -> bool {
+    if !int_part.contains(',') {
+        return true;
+    }
+    let mut groups = int_part.split(',');
+    groups.next().is_some_and(|first| (1..=3).contains(&first.len())) && groups.all(|g| g.len() == 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_amount_full_width() {
+        assert_eq!(parse_amount("１５０"), Some(150.0));
+        assert_eq!(parse_amount("１２．５"), Some(12.5));
+        assert_eq!(parse_amount("－３"), Some(-3.0));
+    }
+
+    #[test]
+    fn parse_amount_commas_and_symbols() {
+        assert_eq!(parse_amount("1,500"), Some(1500.0));
+        assert_eq!(parse_amount("1，500"), Some(1500.0));
+        assert_eq!(parse_amount(" ¥1,500.50 "), Some(1500.5));
+        assert_eq!(parse_amount("￥２００元"), Some(200.0));
+        assert_eq!(parse_amount("+80"), Some(80.0));
+        assert_eq!(parse_amount("-1,234,567.5"), Some(-1234567.5));
+        assert_eq!(parse_amount("1234567"), Some(1234567.0));
+        assert_eq!(parse_amount(".5"), Some(0.5));
+    }
+
+    #[test]
+    fn parse_amount_rejects_garbage() {
+        assert_eq!(parse_amount(""), None);
+        assert_eq!(parse_amount("abc"), None);
+        assert_eq!(parse_amount("12a"), None);
+        assert_eq!(parse_amount("1.2.3"), None);
+        assert_eq!(parse_amount("inf"), None);
+        assert_eq!(parse_amount("NaN"), None);
+        assert_eq!(parse_amount("1+2"), None);
+        assert_eq!(parse_amount("5-"), None);
+        assert_eq!(parse_amount("--5"), None);
+        assert_eq!(parse_amount("1,5,0,0"), None);
+        assert_eq!(parse_amount("1,50"), None);
+        assert_eq!(parse_amount(",500"), None);
+        assert_eq!(parse_amount("1.500,5"), None);
+        assert_eq!(parse_amount("12e3"), None);
+        assert_eq!(parse_amount("1E2"), None);
+        assert_eq!(parse_amount("-"), None);
+        assert_eq!(parse_amount("."), None);
+    }
+
+    #[test]
+    fn exported_csv_imports_cleanly() {
+        let dir = std::env::temp_dir().join(format!("jz-import-test-{}", std::process::id()));
+        let path = dir.join("export.csv");
+        let records = vec![
+            Record { game: Some("王者".to_string()), duration: Some(1.5), ..Record::sample("2024-06-01", "小王", 150.0) },
+            Record::sample("2024-06-02", "老李, Tom", 80.0),
+            Record { settled: true, currency: "USD".to_string(), ..Record::sample("2024-06-03", "阿张", 20.0) },
+        ];
+        crate::export::export_csv(&records, &path, "2024年6月, 小王", true).unwrap();
+
+        let mut rows = parse_csv(&std::fs::read_to_string(&path).unwrap());
+        strip_summary_rows(&mut rows);
+        let mapping = ColumnMapping::guess(&rows[0]);
+        assert!(mapping.is_complete());
+        let imported: Vec<Record> = rows[1..].iter()
+            .map(|row| mapping.map_row(row, DateFormat::Ymd).unwrap())
+            .collect();
+        assert_eq!(imported.len(), 3);
+        assert_eq!(imported[0].game.as_deref(), Some("王者"));
+        assert_eq!(imported[0].duration, Some(1.5));
+        assert_eq!(imported[1].boss, "老李, Tom");
+        assert_eq!(imported[1].income, 80.0);
+        assert_eq!((imported[1].currency.as_str(), imported[1].settled), (DEFAULT_CURRENCY, false));
+        assert_eq!((imported[2].currency.as_str(), imported[2].settled), ("USD", true));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn map_row_parses_settled_and_currency() {
+        let mapping = ColumnMapping {
+            date: Some(0), boss: Some(1), income: Some(2), currency: Some(3), settled: Some(4), ..ColumnMapping::default()
+        };
+        let row = |currency: &str, settled: &str| -> Vec<String> {
+            ["2024-06-01", "小王", "100", currency, settled].iter().map(|s| s.to_string()).collect()
+        };
+        let record = mapping.map_row(&row("usd", "TRUE"), DateFormat::Ymd).unwrap();
+        assert_eq!((record.currency.as_str(), record.settled), ("USD", true));
+        let record = mapping.map_row(&row("", "0"), DateFormat::Ymd).unwrap();
+        assert_eq!((record.currency.as_str(), record.settled), (DEFAULT_CURRENCY, false));
+        assert!(mapping.map_row(&row("CNY", "也许"), DateFormat::Ymd).is_err());
+    }
+
+    #[test]
+    fn map_row_accepts_full_width_income_and_duration() {
+        let mapping = ColumnMapping { date: Some(0), boss: Some(1), income: Some(2), duration: Some(3), ..ColumnMapping::default() };
+        let row: Vec<String> = ["2024-06-01", "小王", "１５０", "１．５h"].iter().map(|s| s.to_string()).collect();
+        let record = mapping.map_row(&row, DateFormat::Ymd).unwrap();
+        assert_eq!(record.income, 150.0);
+        assert_eq!(record.duration, Some(1.5));
+    }
+}
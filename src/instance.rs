@@ -0,0 +1,80 @@
+//! 单实例唤醒：重复启动时写入一个标记文件，已运行的实例发现后把窗口带到前台并删除它
+//! 标记文件一段时间内没有被删除，说明已运行的实例没有响应，由调用方提示用户
+
+use crate::db::Database;
+use eframe::egui::{self, ViewportCommand};
+use std::path::PathBuf;
+use std::sync::Once;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const POLL: Duration = Duration::from_millis(300);
+/// 等待已运行的实例响应的时间
+const ACTIVATE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+static WATCH: Once = Once::new();
+
+/// 唤醒标记和单实例锁在同一目录，只会唤醒打开同一个数据库的实例
+fn activate_file_path() -> PathBuf {
+    Database::data_dir().join(".activate")
+}
+
+/// 请求已运行的实例显示窗口，对方在超时前响应时返回 true
+pub fn request_activation() -> bool {
+    let path = activate_file_path();
+    if std::fs::write(&path, b"").is_err() {
+        return false;
+    }
+    let deadline = Instant::now() + ACTIVATE_TIMEOUT;
+    while Instant::now() < deadline {
+        thread::sleep(POLL);
+        if !path.exists() {
+            return true;
+        }
+    }
+    let _ = std::fs::remove_file(&path);
+    false
+}
+
+/// 启动后台线程等待唤醒请求，多次调用只启动一次
+/// 窗口隐藏或最小化时 update 不一定运行，直接向窗口发送命令
+pub fn start_watch(ctx: &egui::Context) {
+    WATCH.call_once(|| {
+        let path = activate_file_path();
+        // 上次异常退出时可能留下的标记
+        let _ = std::fs::remove_file(&path);
+        let ctx = ctx.clone();
+        thread::spawn(move || loop {
+            thread::sleep(POLL);
+            if path.exists() && std::fs::remove_file(&path).is_ok() {
+                ctx.send_viewport_cmd(ViewportCommand::Visible(true));
+                ctx.send_viewport_cmd(ViewportCommand::Minimized(false));
+                ctx.send_viewport_cmd(ViewportCommand::Focus);
+                ctx.request_repaint();
+            }
+        });
+    });
+}
+
+/// 已运行的实例没有响应时提示用户（仅 Windows 弹出系统对话框）
+pub fn show_already_running() {
+    let text = format!(
+        "{}已在运行，但没有响应。\n请在任务栏或托盘中找到它，或在任务管理器中结束后重新打开。",
+        crate::APP_TITLE,
+    );
+    #[cfg(windows)]
+    {
+        #[link(name = "user32")]
+        extern "system" {
+            fn MessageBoxW(hwnd: *mut std::ffi::c_void, text: *const u16, caption: *const u16, kind: u32) -> i32;
+        }
+        const MB_ICONINFORMATION: u32 = 0x40;
+        let wide = |s: &str| s.encode_utf16().chain(std::iter::once(0)).collect::<Vec<u16>>();
+        let (text, caption) = (wide(&text), wide(crate::APP_TITLE));
+        unsafe {
+            MessageBoxW(std::ptr::null_mut(), text.as_ptr(), caption.as_ptr(), MB_ICONINFORMATION);
+        }
+    }
+    #[cfg(not(windows))]
+    eprintln!("{}", text);
+}
@@ -1,10 +1,25 @@
 #![windows_subsystem = "windows"]
 
 mod db;
+mod export;
+mod hotkey;
+mod import;
+mod instance;
+mod settings;
+mod suspend;
+mod timer;
+#[cfg(windows)]
+mod tray;
 
-use chrono::{Local, NaiveDate, Datelike};
-use db::{Database, Record};
+use chrono::{Local, NaiveDate, Datelike, Weekday};
+use import::{parse_amount, ColumnMapping, DateFormat};
+use db::{effective_date, AuditEntry, DbStats, normalize_date, normalize_name, round2, year_range, BossRate, Database, DayStat, GameDefault, IntegrityIssue, IssueKind, Record, Template, TimerSession, DEFAULT_CURRENCY};
+use settings::{DateDisplay, Density, DurationRounding, Settings, ThemeMode, WeekStart};
+use timer::TimerInstance;
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
 use eframe::egui::{self, Color32, CornerRadius, FontId, RichText, Vec2, Stroke};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use std::fs::File;
 use std::path::PathBuf;
@@ -23,10 +38,19 @@ struct Theme {
     warning_color: Color32,
     disabled_text: Color32,
     disabled_bg: Color32,
+    text_muted: Color32,     // 次要提示文字
+    border_color: Color32,   // 输入框边框
+    hover_bg: Color32,
+    active_bg: Color32,
+    popup_bg: Color32,       // 联想弹窗背景
+    popup_border: Color32,
+    stripe_color: Color32,   // 表格隔行背景
+    combo_text: Color32,     // 下拉框文字
+    is_dark: bool,
 }
 
 impl Theme {
-    fn default() -> Self {
+    fn dark() -> Self {
         Self {
             bg_color: Color32::from_rgb(25, 28, 32),
             card_color: Color32::from_rgb(35, 39, 45),
@@ -39,8 +63,57 @@ impl Theme {
             warning_color: Color32::from_rgb(230, 180, 80),
             disabled_text: Color32::from_rgb(80, 85, 95),
             disabled_bg: Color32::from_rgb(45, 48, 55),
+            text_muted: Color32::from_rgb(100, 105, 115),
+            border_color: Color32::from_rgb(60, 65, 75),
+            hover_bg: Color32::from_rgb(55, 60, 70),
+            active_bg: Color32::from_rgb(50, 55, 65),
+            popup_bg: Color32::from_rgb(50, 55, 65),
+            popup_border: Color32::from_rgb(70, 75, 85),
+            stripe_color: Color32::from_rgb(40, 44, 52),
+            combo_text: Color32::from_rgb(30, 30, 35),
+            is_dark: true,
+        }
+    }
+
+    fn light() -> Self {
+        Self {
+            bg_color: Color32::from_rgb(240, 242, 245),
+            card_color: Color32::from_rgb(255, 255, 255),
+            input_bg: Color32::from_rgb(245, 247, 250),
+            accent_color: Color32::from_rgb(64, 169, 255),
+            green_color: Color32::from_rgb(40, 160, 90),
+            text_primary: Color32::from_rgb(35, 38, 45),
+            text_secondary: Color32::from_rgb(105, 110, 120),
+            danger_color: Color32::from_rgb(210, 60, 60),
+            warning_color: Color32::from_rgb(205, 140, 30),
+            disabled_text: Color32::from_rgb(170, 175, 185),
+            disabled_bg: Color32::from_rgb(230, 232, 236),
+            text_muted: Color32::from_rgb(150, 155, 165),
+            border_color: Color32::from_rgb(210, 214, 220),
+            hover_bg: Color32::from_rgb(232, 236, 242),
+            active_bg: Color32::from_rgb(222, 227, 235),
+            popup_bg: Color32::from_rgb(255, 255, 255),
+            popup_border: Color32::from_rgb(210, 214, 220),
+            stripe_color: Color32::from_rgb(246, 248, 250),
+            combo_text: Color32::from_rgb(35, 38, 45),
+            is_dark: false,
         }
     }
+
+    /// 按设置选择主题，跟随系统时读取 eframe 报告的系统主题（每帧更新，系统切换后即时生效），读不到时用深色
+    fn from_settings(settings: &Settings, ctx: &egui::Context) -> Self {
+        let mut theme = match settings.theme {
+            ThemeMode::Auto => match ctx.system_theme() {
+                Some(egui::Theme::Light) => Self::light(),
+                _ => Self::dark(),
+            },
+            ThemeMode::Dark => Self::dark(),
+            ThemeMode::Light => Self::light(),
+        };
+        let [r, g, b] = settings.accent_color;
+        theme.accent_color = Color32::from_rgb(r, g, b);
+        theme
+    }
 }
 
 // ===== 布局常量配置 =====
@@ -50,6 +123,9 @@ struct LayoutConfig {
     card_rounding: f32,
     card_inner_margin: f32,
     col_spacing: f32,
+    row_height: f32,      // 表格行高
+    input_height: f32,    // 输入框高度
+    input_margin_y: i8,   // 输入框上下内边距，随高度缩放使文字垂直居中
 }
 
 impl LayoutConfig {
@@ -60,16 +136,38 @@ impl LayoutConfig {
             card_rounding: 14.0,
             card_inner_margin: 22.0,
             col_spacing: 10.0,
+            row_height: 44.0,
+            input_height: 40.0,
+            input_margin_y: 8,
+        }
+    }
+
+    /// 按界面密度缩放行高、输入框高度和卡片内边距
+    fn for_density(density: Density) -> Self {
+        let base = Self::default();
+        match density {
+            Density::Compact => Self { row_height: 34.0, input_height: 32.0, input_margin_y: 4, card_inner_margin: 14.0, ..base },
+            Density::Standard => base,
+            Density::Comfortable => Self { row_height: 52.0, input_height: 46.0, input_margin_y: 11, card_inner_margin: 28.0, ..base },
         }
     }
 }
 
+const APP_TITLE: &str = "记账本";
+const DEFAULT_WINDOW_SIZE: [f32; 2] = [980.0, 810.0];
+const MIN_WINDOW_SIZE: [f32; 2] = [960.0, 810.0];
+const DUPLICATE_CONFIRM_WINDOW: Duration = Duration::from_secs(5);  // 重复记录确认有效期
+const CONTENT_WIDTH_RANGE: std::ops::RangeInclusive<f32> = 720.0..=1400.0;  // 内容区宽度范围，自适应时不超过上限
+const MAX_TIMERS: usize = 4;  // 同时存在的计时器上限（底部栏高度有限）
+const MAX_INCOME_PRESETS: usize = 5;  // 收入框旁最多显示的常用金额数
+const MAX_HISTOGRAM_BINS: usize = 30;  // 收入分布的区间数上限，超出部分并入最后一个区间
+const AUDIT_LIMIT: usize = 200;  // 操作记录弹窗显示的最近条数
+
+/// 单实例锁放在数据库所在目录（随 JZ_DATA_DIR 变化），同一个数据库只允许一个实例打开
 fn get_lock_file_path() -> PathBuf {
-    let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
-    path.push("jz");
-    std::fs::create_dir_all(&path).ok();
-    path.push(".lock");
-    path
+    let dir = Database::data_dir();
+    std::fs::create_dir_all(&dir).ok();
+    dir.join(".lock")
 }
 
 fn try_lock() -> Option<File> {
@@ -111,24 +209,39 @@ fn try_lock() -> Option<File> {
 }
 
 fn main() -> eframe::Result<()> {
-    // 确保只运行一个实例
+    // 确保只运行一个实例：已在运行时把它的窗口带到前台，没有响应时提示用户
     let _lock = try_lock();
     if _lock.is_none() {
+        if !instance::request_activation() {
+            instance::show_already_running();
+        }
         return Ok(());
     }
 
+    let settings = Settings::load();
+
+    // 恢复上次的窗口尺寸，不小于最小尺寸
+    let inner_size = settings.window_size
+        .map(|[w, h]| [w.max(MIN_WINDOW_SIZE[0]), h.max(MIN_WINDOW_SIZE[1])])
+        .unwrap_or(DEFAULT_WINDOW_SIZE);
+    let mut viewport = egui::ViewportBuilder::default()
+        .with_inner_size(inner_size)
+        .with_min_inner_size(MIN_WINDOW_SIZE);
+    if let Some(pos) = settings.window_pos {
+        viewport = viewport.with_position(pos);
+    }
+
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default()
-            .with_inner_size([980.0, 810.0])
-            .with_min_inner_size([960.0, 810.0]),
+        viewport,
         ..Default::default()
     };
     eframe::run_native(
-        "记账本",
+        APP_TITLE,
         options,
         Box::new(|cc| {
             setup_fonts(&cc.egui_ctx);
-            Ok(Box::new(App::new()))
+            instance::start_watch(&cc.egui_ctx);
+            Ok(Box::new(Launcher::new(settings)))
         }),
     )
 }
@@ -154,7 +267,7 @@ fn setup_fonts(ctx: &egui::Context) {
     // 内嵌站酷快乐体用于标题
     fonts.font_data.insert(
         "zcool_kuaile".to_owned(),
-        Arc::new(egui::FontData::from_static(include_bytes!("../fonts/ZCOOLKuaiLe-Regular.ttf"))),
+        Arc::new(egui::FontData::from_static(export::CJK_FONT)),
     );
     fonts.families.insert(
         egui::FontFamily::Name("cute".into()),
@@ -164,15 +277,313 @@ fn setup_fonts(ctx: &egui::Context) {
     ctx.set_fonts(fonts);
 }
 
+/// CSV 导入向导的状态
+struct CsvImport {
+    file_name: String,
+    rows: Vec<Vec<String>>,   // 解析出的全部行（含表头）
+    has_header: bool,         // 第一行是否为表头
+    mapping: ColumnMapping,
+    date_format: DateFormat,
+}
+
+impl CsvImport {
+    /// 列名：有表头时使用表头，否则为“第 N 列”
+    fn column_names(&self) -> Vec<String> {
+        let width = self.rows.iter().map(|r| r.len()).max().unwrap_or(0);
+        (0..width)
+            .map(|i| match self.rows.first().and_then(|r| r.get(i)) {
+                Some(name) if self.has_header && !name.trim().is_empty() => name.trim().to_string(),
+                _ => format!("第 {} 列", i + 1),
+            })
+            .collect()
+    }
+
+    fn data_rows(&self) -> &[Vec<String>] {
+        if self.has_header {
+            self.rows.get(1..).unwrap_or_default()
+        } else {
+            &self.rows
+        }
+    }
+
+    /// 按映射解析全部数据行；金额超过单笔上限的行与手动录入一样视为无效
+    fn parse_rows(&self, max_income: f64, money: &MoneyFormat) -> Vec<Result<Record, String>> {
+        self.data_rows().iter()
+            .map(|row| {
+                self.mapping.map_row(row, self.date_format).and_then(|r| {
+                    if r.income > max_income {
+                        Err(format!("金额超过单笔上限 {}", money.money(max_income)))
+                    } else {
+                        Ok(r)
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+/// 设置密码弹窗的输入
+#[cfg(feature = "encryption")]
+#[derive(Default)]
+struct PasswordForm {
+    password: String,
+    confirm: String,  // 再次输入
+}
+
+/// 表格行拖动排序时携带的数据，只能在同一天内移动
+struct DraggedRow {
+    id: i64,
+    date: String,
+}
+
+/// 当前显示联想弹窗的输入框，同一时间只显示一个
+#[derive(Clone, Copy, PartialEq)]
+enum ActiveSuggestion {
+    None,
+    Boss,
+    Game,
+}
+
+/// 计时器行上的操作，绘制完成后统一处理
+#[derive(Clone, Copy)]
+enum TimerAction {
+    Start,
+    Pause,
+    Resume,
+    End,
+    Reset,
+    Fill,    // 用该计时器的时长填入输入框
+    Add,     // 新建一个计时器
+    Remove,
+}
+
+/// 修改录入时间弹窗的状态
+struct CreatedAtEdit {
+    id: i64,
+    summary: String,  // 记录的简要描述，显示在弹窗中
+    text: String,     // YYYY-MM-DD HH:MM[:SS]
+    error: bool,      // 时间格式无效
+}
+
+/// 等待确认的批量结清 / 取消结清
+struct PendingSettle {
+    boss: Option<String>,   // None 表示所有老板
+    period: Option<String>, // YYYY-MM 整月或 YYYY-MM-DD 单日，None 表示不限日期
+    settled: bool,          // true 结清，false 取消结清
+}
+
+impl PendingSettle {
+    /// 确认弹窗中的范围描述
+    fn describe(&self) -> String {
+        let boss = self.boss.as_deref().unwrap_or("所有老板");
+        let period = self.period.as_deref().unwrap_or("全部月份");
+        format!("{} · {}", boss, period)
+    }
+}
+
+/// 老板详情弹窗的状态
+struct BossDetail {
+    name: String,
+    count: i64,          // 记录条数
+    total: f64,          // 累计收入（主币种）
+    note: String,        // 备注输入
+    rename: String,      // 改名输入
+    rate: BossRate,        // 计费偏好输入
+    months: Vec<String>,   // 有记录的月份（YYYY-MM），最近的在前
+    invoice_month: String, // 导出账单选中的月份
+}
+
+/// 游戏设置弹窗中的一行
+struct GameDefaultRow {
+    game: String,
+    default: GameDefault,
+}
+
+/// 启动外壳：数据库已加密时先输入密码，打开失败时显示错误页并允许重试，打开成功后交给 App
+struct Launcher {
+    settings: Settings,
+    app: Option<App>,
+    error: String,         // 最近一次打开数据库的错误
+    needs_password: bool,  // 数据库已加密，需要输入密码
+    passphrase: String,
+    corrupt: bool,         // 数据库文件已损坏，由用户选择如何恢复
+}
+
+impl Launcher {
+    fn new(settings: Settings) -> Self {
+        let needs_password = cfg!(feature = "encryption") && Database::is_encrypted();
+        let mut launcher = Self { settings, app: None, error: String::new(), needs_password, passphrase: String::new(), corrupt: false };
+        if !needs_password {
+            launcher.try_open();
+        }
+        launcher
+    }
+
+    fn try_open(&mut self) {
+        let key = Some(self.passphrase.as_str()).filter(|_| self.needs_password);
+        match Database::open(key) {
+            Ok(db) => {
+                self.passphrase.clear();
+                self.app = Some(App::new(db, self.settings.clone()));
+            }
+            // 文件头损坏（写入中断的常见情况）和加密都会报 NotADatabase，无法完全区分：
+            // 有密码时按密码错误提示，但密码界面上仍可选择恢复；文件头不是加密数据时按损坏处理
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::NotADatabase => {
+                if self.needs_password {
+                    self.error = "密码错误".to_string();
+                } else if Database::is_encrypted() {
+                    self.corrupt = true;
+                    self.error = "无法读取数据库：可能已加密（需使用启用了加密功能的版本），也可能文件头已损坏".to_string();
+                } else {
+                    self.corrupt = true;
+                    self.error = "数据库文件已损坏（可能是上次异常退出导致）".to_string();
+                }
+            }
+            Err(rusqlite::Error::SqliteFailure(e, _)) if e.code == rusqlite::ErrorCode::DatabaseCorrupt => {
+                self.corrupt = true;
+                self.error = "数据库文件已损坏（可能是上次异常退出导致）".to_string();
+            }
+            Err(e) => self.error = format!("数据库打开失败：{}，请检查磁盘空间/权限", e),
+        }
+    }
+
+    /// 把损坏的数据库移到一旁后新建；restore 为 true 时先选择 JSON 备份，新建后导入
+    /// 取消选择备份时什么也不做，所做的处理写入新库的操作记录
+    fn recover(&mut self, restore: bool) {
+        let backup = if restore {
+            let Some(path) = rfd::FileDialog::new()
+                .add_filter("JSON 备份", &["json"])
+                .set_directory(export::default_export_dir())
+                .pick_file()
+            else {
+                return;
+            };
+            Some(path)
+        } else {
+            None
+        };
+        let moved = match Database::move_aside() {
+            Ok(path) => path,
+            Err(e) => {
+                self.error = format!("无法移动损坏的数据库：{}", e);
+                return;
+            }
+        };
+        self.corrupt = false;
+        self.needs_password = false;
+        self.passphrase.clear();
+        self.error.clear();
+        self.try_open();
+        let Some(app) = &mut self.app else {
+            return;
+        };
+        let _ = app.db.log_event("恢复", &format!("损坏的数据库已移到 {}", moved.display()));
+        match backup.map(|path| app.db.import_json(&path)) {
+            Some(Ok(count)) => {
+                let _ = app.db.log_event("恢复", &format!("从备份导入 {} 条记录", count));
+                app.refresh_data();
+                app.show_message(&format!("已从备份恢复 {} 条记录", count), false);
+            }
+            Some(Err(_)) => app.show_message("已新建数据库，但备份导入失败，可稍后在“导入”中重试", true),
+            None => app.show_message(&format!("已新建数据库，损坏的文件保存在 {}", moved.display()), false),
+        }
+    }
+}
+
+impl eframe::App for Launcher {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        if let Some(app) = &mut self.app {
+            app.update(ctx, frame);
+            return;
+        }
+
+        let theme = Theme::from_settings(&self.settings, ctx);
+        egui::CentralPanel::default()
+            .frame(egui::Frame::default().fill(theme.bg_color))
+            .show(ctx, |ui| {
+                ui.add_space(ui.available_height() * 0.35);
+                ui.vertical_centered(|ui| {
+                    let mut submit = false;
+                    if self.needs_password {
+                        ui.label(RichText::new("数据库已加密，请输入密码").size(16.0).color(theme.text_primary));
+                        ui.add_space(12.0);
+                        let input = ui.add(egui::TextEdit::singleline(&mut self.passphrase)
+                            .password(true)
+                            .desired_width(220.0));
+                        input.request_focus();
+                        submit = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        // 文件头损坏时也会被识别为加密，输入什么密码都打不开，这里保留恢复入口
+                        if !self.corrupt {
+                            ui.add_space(4.0);
+                            if ui.small_button("密码正确但打不开？").on_hover_text("文件可能已损坏，可以移到一旁后新建或从备份恢复").clicked() {
+                                self.corrupt = true;
+                            }
+                        }
+                    }
+                    if !self.error.is_empty() {
+                        ui.add_space(8.0);
+                        ui.label(RichText::new(&self.error).size(16.0).color(theme.danger_color));
+                    }
+                    if self.corrupt {
+                        // 不自动处理，由用户选择；两种方式都会保留损坏的文件
+                        ui.add_space(8.0);
+                        ui.label(RichText::new("可以把损坏的文件移到一旁并新建数据库，或新建后从 JSON 备份恢复")
+                            .size(13.0)
+                            .color(theme.text_secondary));
+                        ui.add_space(12.0);
+                        if ui.button("移到一旁并从备份恢复").clicked() {
+                            self.recover(true);
+                        }
+                        if ui.button("移到一旁并新建空数据库").clicked() {
+                            self.recover(false);
+                        }
+                    }
+                    ui.add_space(16.0);
+                    let label = if self.needs_password { "解锁" } else { "重试" };
+                    let retry = egui::Button::new(RichText::new(label).size(14.0).color(Color32::WHITE))
+                        .fill(theme.accent_color)
+                        .corner_radius(CornerRadius::same(6))
+                        .min_size(Vec2::new(80.0, 32.0));
+                    if ui.add(retry).clicked() || submit {
+                        self.try_open();
+                    }
+                });
+            });
+    }
+}
+
 struct App {
     db: Database,
-    records: Vec<Record>,
+    settings: Settings,
+    records: Vec<Record>,        // 选中月份的记录
+    day_stats: (i64, f64, f64),  // 输入日期当天的单数、时长、收入（底部统计用，数据变化时刷新）
+    daily_totals: Vec<DayStat>,  // 选中月份每天的统计（日历热力图）
+    day_filter: Option<String>,  // 日历中选中的日期，表格只显示这一天
+    tag_filter: Option<String>,  // 点击标签后只显示带该标签的记录（不限月份）
+    tag_records: Vec<Record>,    // 带筛选标签的全部记录
+    boss_filter: Option<String>, // 点击常客胶囊后只显示该老板
     total_balance: f64,
-    day_balance: f64,
+    week_balance: f64,           // 本周收入（按设置的每周第一天）
+    streak: u32,                 // 截至今天连续有记录的天数
+    last_seen_date: NaiveDate,   // 上次计算统计时的本地日期，跨过午夜后自动刷新
     month_balance: f64,
-    boss_balances: std::collections::HashMap<String, f64>,
+    record_count: i64,           // 全部记录条数（为零时显示新手引导）
+    unsettled_running: HashMap<i64, f64>,  // 未结清记录 id -> 截至该记录该老板（同币种）累计未结金额
+    prev_month_balance: f64,     // 选中月份上个月的收入（环比）
+    year_by_currency: HashMap<String, f64>,  // 选中年份按币种分组的收入（悬停明细）
+    all_time_by_currency: HashMap<String, f64>,  // 全部记录按币种分组的收入（悬停明细）
     boss_list: Vec<String>,
     game_list: Vec<String>,
+    recent_bosses: Vec<String>,  // 最近用过的老板（输入为空时的联想）
+    recent_games: Vec<String>,
+    tag_list: Vec<String>,       // 已有标签（自动补全）
+    tag_input: String,           // 右键菜单中的新标签输入
+    templates: Vec<Template>,    // 记录模板
+    noted_bosses: HashSet<String>,  // 有备注的老板
+    boss_rates: HashMap<String, BossRate>,  // 老板的时薪和取整偏好（估算收入用）
+    boss_detail: Option<BossDetail>,  // 打开的老板详情弹窗
+    game_settings: Option<Vec<GameDefaultRow>>,  // 打开的游戏设置弹窗
+    template_name_input: String, // 右键菜单中的模板名称输入
 
     // 月结余选择器
     selected_year: i32,
@@ -183,1109 +594,5042 @@ struct App {
     input_income: String,
     input_duration: String,      // 时长输入
     input_game: String,          // 游戏输入
+    input_game_default: Option<GameDefault>,  // 输入游戏的默认时长和单价
     input_settled: bool,         // 是否结清勾选
-    show_boss_suggestions: bool,
-    show_game_suggestions: bool, // 游戏联想显示
+    input_currency: String,      // 币种选择
+    active_suggestion: ActiveSuggestion,  // 显示联想弹窗的输入框
+    selected_suggestion_index: Option<usize>,  // 键盘高亮的联想项
 
-    // 输入验证错误状态
-    input_boss_error: bool,
-    input_income_error: bool,
+    // 输入验证错误：显示在对应输入框下方，获得焦点时清除
+    input_boss_error: Option<String>,
+    input_income_error: Option<String>,
+    input_duration_error: Option<String>,
+    input_date_error: Option<String>,
+
+    // 疑似重复记录：等待再次点击确认（记录键、提示时间）
+    duplicate_pending: Option<(String, Instant)>,
 
     message: String,
     message_is_error: bool,
     message_timer: f32,
 
-    // 计时器
-    timer_running: bool,
-    timer_start_instant: Option<Instant>,
-    timer_accumulated: Duration,
-    timer_ended: bool,  // 是否已结束（结束后才能重置）
+    // 导出选项
+    export_current_view: bool,  // 仅导出当前视图
+    export_summary: bool,       // CSV 末尾追加合计和平均时薪
+    income_presets_text: String,  // 设置中编辑常用金额的文本（空格分隔）
+    money: MoneyFormat,           // 金额显示格式，设置变化时重新生成
+
+    // 弹窗状态
+    show_settings: bool,
+    show_integrity: bool,                   // 数据检查弹窗
+    histogram_all_time: bool,               // 收入分布统计全部记录（否则只统计选中月份）
+    pending_chart_export: Option<(String, egui::Rect)>,  // 等待截图导出的图表（文件名, 屏幕区域）
+    histogram_bin: f64,                     // 收入分布的区间宽度
+    histogram: Vec<(f64, f64, usize)>,      // 收入分布（区间起点、终点、单数），数据或选项变化时重新统计
+    csv_import: Option<CsvImport>,          // CSV 导入向导
+    integrity_issues: Vec<IntegrityIssue>,  // 最近一次数据检查的结果
+    pending_delete: Option<i64>,  // 等待确认删除的记录
+    pending_settle: Option<PendingSettle>,  // 等待确认的批量结清
+    batch_mode: bool,                  // 批量模式：操作列换成勾选框，底部显示所选合计
+    batch_selected: HashSet<i64>,      // 批量模式下勾选的记录 id
+    unsettled_report: Option<Vec<(String, f64)>>,  // 打开的待结算弹窗（老板、未收金额）
+    unsettled_first: bool,        // 表格按结清状态排序，未结清的在前
+    editing_income: Option<i64>,  // 正在表格中修改收入的记录
+    focused_row: Option<i64>,  // 键盘选中的行（上下方向键移动，Delete 删除）
+    table_view: (f32, f32),  // 表格上一帧的滚动位置和可见高度，用于让选中行保持可见
+    created_at_edit: Option<CreatedAtEdit>,  // 打开的修改录入时间弹窗
+    editing_income_text: String,
+
+    // 计时器（至少一个）
+    timers: Vec<TimerInstance>,
+    timer_session_id: Option<i64>,     // 最近一次结束的计时，添加记录时用于关联
+    timer_filled_duration: Option<String>,  // 用计时结果填入的时长，添加时仍相同才关联
+    timer_sessions: Option<Vec<TimerSession>>,  // 打开的计时记录弹窗
+    audit_log: Option<Vec<AuditEntry>>,  // 打开的操作记录弹窗
+    about: Option<DbStats>,  // 打开的关于弹窗
+    data_dir_failed: Option<String>,  // 无法打开数据目录时在关于弹窗中显示的路径，供手动复制
+    audit_purge_days: u32,               // 清除多少天之前的操作记录
+    window_title: String,  // 最近一次设置的窗口标题，变化时才发送命令
+
+    // 设置密码弹窗
+    #[cfg(feature = "encryption")]
+    password_form: Option<PasswordForm>,
+    db_encrypted: bool,  // 数据库已加密，解锁时使用数据库密码
+
+    // 自动锁定
+    locked: bool,
+    last_input_time: f64,  // 最近一次键鼠操作的时间（ctx.input 的 time，秒）
+    unlock_input: String,
+    unlock_error: bool,
+    pin_input: String,  // 设置中新 PIN 的输入，保存后清空
+
+    // 全局快捷键（开启时注册）
+    hotkey: Option<hotkey::GlobalHotkey>,
+    hotkey_failed: Option<String>,  // 注册失败的组合键，组合键修改前不再重试
+
+    // 系统托盘（关闭到托盘时创建）
+    #[cfg(windows)]
+    tray: Option<tray::Tray>,
 }
 
 impl App {
-    fn new() -> Self {
-        let db = Database::new().expect("无法初始化数据库");
-        let records = db.get_all_records().unwrap_or_default();
-        let today = Local::now().date_naive();
-        let total_balance = Self::calc_year_balance(&records, today.year());
-        let day_balance = Self::calc_day_balance(&records, &today.format("%Y-%m-%d").to_string());
-        let month_balance = Self::calc_month_balance(&records, &today.format("%Y-%m").to_string());
-        let boss_balances = Self::calc_boss_balances(&records);
-
-        let boss_list = db.get_all_bosses();
-        let game_list = db.get_all_games();
+    fn new(db: Database, settings: Settings) -> Self {
+        let today = effective_date(Local::now().naive_local(), settings.day_start_hour);
+        // 恢复上次查看的月份，首次运行时为本月
+        let (selected_year, selected_month) = settings.selected_month.unwrap_or((today.year(), today.month()));
+        let mut timers: Vec<TimerInstance> = settings.timers.iter().map(TimerInstance::from_saved).collect();
+        if timers.is_empty() {
+            timers.push(TimerInstance::new("计时"));
+        }
+        let templates = db.list_templates().unwrap_or_default();
+        let income_presets_text = format_presets(&settings.income_presets);
+        let money = MoneyFormat::from_settings(&settings);
 
-        Self {
+        let mut app = Self {
             db,
-            records,
-            total_balance,
-            day_balance,
-            month_balance,
-            boss_balances,
-            boss_list,
-            game_list,
-            selected_year: today.year(),
-            selected_month: today.month(),
+            settings,
+            // 记录和统计由 refresh_data 统一加载
+            records: Vec::new(),
+            day_stats: (0, 0.0, 0.0),
+            daily_totals: Vec::new(),
+            day_filter: None,
+            tag_filter: None,
+            tag_records: Vec::new(),
+            boss_filter: None,
+            total_balance: 0.0,
+            week_balance: 0.0,
+            streak: 0,
+            last_seen_date: today,
+            month_balance: 0.0,
+            record_count: 0,
+            unsettled_running: HashMap::new(),
+            prev_month_balance: 0.0,
+            year_by_currency: HashMap::new(),
+            all_time_by_currency: HashMap::new(),
+            boss_list: Vec::new(),
+            game_list: Vec::new(),
+            recent_bosses: Vec::new(),
+            recent_games: Vec::new(),
+            tag_list: Vec::new(),
+            tag_input: String::new(),
+            templates,
+            noted_bosses: HashSet::new(),
+            boss_rates: HashMap::new(),
+            boss_detail: None,
+            game_settings: None,
+            template_name_input: String::new(),
+            selected_year,
+            selected_month,
             input_date: today,
             input_boss: String::new(),
             input_income: String::new(),
             input_duration: String::new(),
             input_game: String::new(),
+            input_game_default: None,
             input_settled: false,
-            show_boss_suggestions: false,
-            show_game_suggestions: false,
-            input_boss_error: false,
-            input_income_error: false,
+            input_currency: DEFAULT_CURRENCY.to_string(),
+            active_suggestion: ActiveSuggestion::None,
+            selected_suggestion_index: None,
+            input_boss_error: None,
+            input_income_error: None,
+            input_duration_error: None,
+            input_date_error: None,
+            duplicate_pending: None,
             message: String::new(),
             message_is_error: false,
             message_timer: 0.0,
-            timer_running: false,
-            timer_start_instant: None,
-            timer_accumulated: Duration::ZERO,
-            timer_ended: false,
+            export_current_view: true,
+            export_summary: true,
+            income_presets_text,
+            money,
+            show_settings: false,
+            show_integrity: false,
+            histogram_all_time: false,
+            pending_chart_export: None,
+            histogram_bin: 100.0,
+            histogram: Vec::new(),
+            csv_import: None,
+            integrity_issues: Vec::new(),
+            pending_delete: None,
+            pending_settle: None,
+            batch_mode: false,
+            batch_selected: HashSet::new(),
+            unsettled_report: None,
+            unsettled_first: false,
+            editing_income: None,
+            focused_row: None,
+            table_view: (0.0, 0.0),
+            created_at_edit: None,
+            editing_income_text: String::new(),
+            timers,
+            timer_session_id: None,
+            timer_filled_duration: None,
+            timer_sessions: None,
+            audit_log: None,
+            about: None,
+            data_dir_failed: None,
+            audit_purge_days: 90,
+            window_title: APP_TITLE.to_string(),
+            #[cfg(feature = "encryption")]
+            password_form: None,
+            db_encrypted: cfg!(feature = "encryption") && Database::is_encrypted(),
+            locked: false,
+            last_input_time: 0.0,
+            unlock_input: String::new(),
+            unlock_error: false,
+            pin_input: String::new(),
+            hotkey: None,
+            hotkey_failed: None,
+            #[cfg(windows)]
+            tray: None,
+        };
+        app.refresh_data();
+        app.restore_pinned_input();
+        suspend::start_watch();
+        app
+    }
+
+    /// 重新加载选中月份的记录和各项统计（只查询需要的数据，不加载全部历史）
+    fn refresh_data(&mut self) {
+        self.records = self.db
+            .get_records_for_month(self.selected_year, self.selected_month)
+            .unwrap_or_default();
+        self.daily_totals = self.db
+            .daily_totals_for_month(self.selected_year, self.selected_month)
+            .unwrap_or_default();
+        self.total_balance = self.db.get_year_balance(self.selected_year);
+        self.month_balance = self.db.get_month_balance(self.selected_year, self.selected_month);
+        let (prev_year, prev_month) = shift_month(self.selected_year, self.selected_month, -1);
+        self.prev_month_balance = self.db.get_month_balance(prev_year, prev_month);
+        let (year_start, year_end) = year_range(self.selected_year);
+        self.year_by_currency = self.db.get_balances_by_currency(&year_start, &year_end);
+        self.all_time_by_currency = self.db.get_total_balance();
+        self.week_balance = Self::load_week_balance(&self.db, self.today(), self.settings.week_start.weekday());
+        self.streak = current_streak(&self.db.active_days().unwrap_or_default(), self.today());
+        self.record_count = self.db.record_count();
+        self.unsettled_running = unsettled_running_totals(&self.db.unsettled_records().unwrap_or_default());
+        self.refresh_day_data();
+        self.boss_list = self.db.get_all_bosses();
+        self.game_list = self.db.get_all_games();
+        self.recent_bosses = self.db.recent_bosses(self.settings.suggestion_limit());
+        self.recent_games = self.db.recent_games(self.settings.suggestion_limit());
+        self.tag_list = self.db.get_all_tags();
+        self.noted_bosses = self.db.get_bosses_with_notes().into_iter().collect();
+        self.boss_rates = self.db.get_boss_rates();
+        if self.show_integrity {
+            self.run_integrity_check();
+        }
+        if self.unsettled_report.is_some() {
+            self.unsettled_report = Some(self.db.unsettled_by_boss().unwrap_or_default());
+        }
+        self.refresh_histogram();
+        self.remember_selected_month();
+        if let Some(tag) = &self.tag_filter {
+            self.tag_records = self.db.records_with_tag(tag).unwrap_or_default();
         }
     }
 
-    fn calc_boss_balances(records: &[Record]) -> std::collections::HashMap<String, f64> {
-        let mut map = std::collections::HashMap::new();
-        for r in records {
-            *map.entry(r.boss.clone()).or_insert(0.0) += r.income;
+    /// 表格中直接修改收入：校验规则与添加时相同
+    fn update_income(&mut self, id: i64, text: &str) {
+        let Some(income) = parse_income(text) else {
+            self.show_message("请输入有效金额", true);
+            return;
+        };
+        if income > self.settings.max_income {
+            self.show_message(&format!("单笔金额不能超过 {}", self.money.money(self.settings.max_income)), true);
+            return;
+        }
+        match self.db.update_income(id, income) {
+            Ok(_) => {
+                self.show_message("已修改金额", false);
+                self.refresh_data();
+            }
+            Err(_) => self.show_message("修改失败", true),
         }
-        map
     }
 
-    fn calc_day_balance(records: &[Record], date: &str) -> f64 {
-        records.iter()
-            .filter(|r| r.date == date)
-            .map(|r| r.income)
-            .sum()
+    /// 用给定内容填充输入行（日期设为今天），不会直接添加记录
+    fn fill_input(&mut self, boss: &str, game: Option<&str>, income: f64, duration: Option<f64>) {
+        self.clear_input();
+        self.input_date = self.today();
+        self.input_boss = boss.to_string();
+        self.input_game = game.unwrap_or_default().to_string();
+        self.input_income = income.to_string();
+        self.input_duration = duration.map(|d| d.to_string()).unwrap_or_default();
+        self.load_game_default();
+        self.refresh_day_data();
     }
 
-    fn calc_year_balance(records: &[Record], year: i32) -> f64 {
-        let year_str = format!("{}", year);
-        records.iter()
-            .filter(|r| r.date.starts_with(&year_str))
-            .map(|r| r.income)
-            .sum()
+    fn apply_template(&mut self, t: &Template) {
+        self.fill_input(&t.boss, t.game.as_deref(), t.income, t.duration);
     }
 
-    fn calc_month_balance(records: &[Record], year_month: &str) -> f64 {
-        records.iter()
-            .filter(|r| r.date.starts_with(year_month))
-            .map(|r| r.income)
-            .sum()
+    /// 保存修改的录入时间，格式无效时标记错误并保留弹窗
+    fn save_created_at(&mut self) {
+        let Some(edit) = &mut self.created_at_edit else {
+            return;
+        };
+        let Some(time) = parse_datetime(&edit.text) else {
+            edit.error = true;
+            return;
+        };
+        let id = edit.id;
+        self.created_at_edit = None;
+        match self.db.update_created_at(id, &time.format("%Y-%m-%d %H:%M:%S").to_string()) {
+            Ok(_) => {
+                self.show_message("已修改录入时间", false);
+                self.refresh_data();
+            }
+            Err(_) => self.show_message("修改失败", true),
+        }
     }
 
-    fn refresh_data(&mut self) {
-        self.records = self.db.get_all_records().unwrap_or_default();
-        self.total_balance = Self::calc_year_balance(&self.records, self.selected_year);
-        self.day_balance = Self::calc_day_balance(&self.records, &self.input_date.format("%Y-%m-%d").to_string());
-        let year_month = format!("{}-{:02}", self.selected_year, self.selected_month);
-        self.month_balance = Self::calc_month_balance(&self.records, &year_month);
-        self.boss_balances = Self::calc_boss_balances(&self.records);
-        self.boss_list = self.db.get_all_bosses();
-        self.game_list = self.db.get_all_games();
+    /// 复制一条记录到今天：填充输入行，等待修改后点击添加
+    fn copy_to_today(&mut self, r: &Record) {
+        self.fill_input(&r.boss, r.game.as_deref(), r.income, r.duration);
+        self.input_currency = r.currency.clone();
     }
 
-    fn show_message(&mut self, msg: &str, is_error: bool) {
-        self.message = msg.to_string();
-        self.message_is_error = is_error;
-        self.message_timer = 3.0;
+    /// 把拖动的记录移到目标记录的上方或下方（同一天内），并保存新的顺序
+    fn reorder_record(&mut self, dragged_id: i64, target_id: i64, above: bool, date: &str) {
+        // 按完整的当天记录重排，不受表格筛选影响
+        let mut ids: Vec<i64> = self.db.get_records_between(date, date)
+            .unwrap_or_default()
+            .iter()
+            .map(|r| r.id)
+            .filter(|id| *id != dragged_id)
+            .collect();
+        let Some(target_pos) = ids.iter().position(|id| *id == target_id) else {
+            return;
+        };
+        ids.insert(if above { target_pos } else { target_pos + 1 }, dragged_id);
+        match self.db.reorder(date, &ids) {
+            Ok(_) => self.refresh_data(),
+            Err(_) => self.show_message("调整顺序失败", true),
+        }
     }
 
-    fn add_record(&mut self) {
-        const MAX_INCOME: f64 = 100_000.0; // 单笔最大10万
+    /// 将记录的老板、游戏、收入、时长保存为模板
+    fn save_template(&mut self, record: &Record, name: &str) {
+        let name = name.trim();
+        let name = if name.is_empty() { record.boss.as_str() } else { name };
+        let template = Template {
+            id: 0,
+            name: name.to_string(),
+            boss: record.boss.clone(),
+            game: record.game.clone(),
+            income: record.income,
+            duration: record.duration,
+        };
+        match self.db.save_template(&template) {
+            Ok(_) => {
+                self.templates = self.db.list_templates().unwrap_or_default();
+                self.show_message(&format!("已保存模板「{}」", name), false);
+            }
+            Err(_) => self.show_message("保存模板失败", true),
+        }
+    }
 
-        // 重置错误状态
-        self.input_boss_error = false;
-        self.input_income_error = false;
+    fn delete_template(&mut self, id: i64) {
+        if self.db.delete_template(id).is_ok() {
+            self.templates = self.db.list_templates().unwrap_or_default();
+        }
+    }
 
-        // 验证必填项
-        let boss_empty = self.input_boss.trim().is_empty();
-        let income_invalid = self.input_income.trim().parse::<f64>()
-            .map(|v| v <= 0.0 || !v.is_finite())
-            .unwrap_or(true);
+    /// 运行数据检查，只读取不修改
+    fn run_integrity_check(&mut self) {
+        match self.db.validate() {
+            Ok(issues) => self.integrity_issues = issues,
+            Err(_) => self.show_message("数据检查失败", true),
+        }
+    }
 
-        if boss_empty || income_invalid {
-            if boss_empty {
-                self.input_boss_error = true;
-            }
-            if income_invalid {
-                self.input_income_error = true;
+    /// 修正格式错误的日期（用户在数据检查中确认后调用）
+    fn fix_dates(&mut self, fixes: &[(i64, String)]) {
+        match self.db.fix_dates(fixes) {
+            Ok(count) => {
+                self.show_message(&format!("已修复 {} 条日期", count), false);
+                self.refresh_data();
             }
-            // 显示具体的错误提示
-            let msg = match (boss_empty, income_invalid) {
-                (true, true) => "请输入老板名称和收入金额",
-                (true, false) => "请输入老板名称",
-                (false, true) => "请输入有效金额",
-                _ => unreachable!(),
-            };
-            self.show_message(msg, true);
+            Err(_) => self.show_message("修复失败", true),
+        }
+    }
+
+    /// 切换到记录所在的年月，并按当天筛选表格
+    fn jump_to_date(&mut self, date: &str) {
+        let Ok(d) = NaiveDate::parse_from_str(date, "%Y-%m-%d") else {
             return;
+        };
+        self.selected_year = d.year();
+        self.selected_month = d.month();
+        self.day_filter = Some(date.to_string());
+        self.tag_filter = None;
+        self.boss_filter = None;
+        self.refresh_data();
+    }
+
+    /// 打开老板详情弹窗
+    fn open_boss_detail(&mut self, boss: &str) {
+        let months = self.db.get_boss_months(boss);
+        // 默认选中当前查看的月份，该月没有记录时选最近一个月
+        let current = format!("{}-{:02}", self.selected_year, self.selected_month);
+        let invoice_month = if months.contains(&current) {
+            current
+        } else {
+            months.first().cloned().unwrap_or(current)
+        };
+        self.boss_detail = Some(BossDetail {
+            name: boss.to_string(),
+            count: self.db.get_boss_record_count(boss),
+            total: self.db.get_boss_balance(boss),
+            note: self.db.get_boss_note(boss).unwrap_or_default(),
+            rename: boss.to_string(),
+            rate: self.boss_rates.get(boss).copied().unwrap_or_default(),
+            months,
+            invoice_month,
+        });
+    }
+
+    /// 导出老板某月的 PDF 账单
+    fn export_boss_invoice(&mut self, boss: &str, month: &str) {
+        let records = self.db
+            .get_records_between(&format!("{}-01", month), &format!("{}-31", month))
+            .unwrap_or_default();
+        let file_name = format!("账单-{}-{}.pdf", sanitize_file_name(boss), month);
+        let path = export::default_export_dir().join(file_name);
+        match export::export_boss_invoice(&records, boss, month, &path) {
+            Ok(_) => self.show_message(&format!("已导出账单到 {}", path.display()), false),
+            Err(e) => self.show_message(&format!("导出账单失败：{}", e), true),
         }
+    }
 
-        let income: f64 = self.input_income.trim().parse().unwrap();
+    fn save_boss_note(&mut self, boss: &str, note: &str) {
+        match self.db.set_boss_note(boss, note) {
+            Ok(_) => {
+                self.noted_bosses = self.db.get_bosses_with_notes().into_iter().collect();
+                self.show_message("备注已保存", false);
+            }
+            Err(_) => self.show_message("保存备注失败", true),
+        }
+    }
 
-        // 检查单笔金额上限
-        if income > MAX_INCOME {
-            self.input_income_error = true;
-            self.show_message(&format!("单笔金额不能超过 ¥{:.0}", MAX_INCOME), true);
-            return;
+    fn save_boss_rate(&mut self, boss: &str, rate: &BossRate) {
+        match self.db.set_boss_rate(boss, rate) {
+            Ok(_) => {
+                self.boss_rates = self.db.get_boss_rates();
+                self.show_message("计费设置已保存", false);
+            }
+            Err(_) => self.show_message("保存计费设置失败", true),
         }
+    }
 
-        // 解析时长（可为空，支持小数）
-        let duration: Option<f64> = if self.input_duration.trim().is_empty() {
-            None
-        } else {
-            match self.input_duration.trim().parse::<f64>() {
-                Ok(v) if v > 0.0 && v.is_finite() => Some((v * 10.0).round() / 10.0), // 保留一位小数
-                _ => {
-                    self.show_message("请输入有效时长", true);
-                    return;
-                }
+    /// 按时薪和输入的时长估算收入，并按该老板的取整步长取整
+    /// 优先用老板时薪，老板没有设置时用游戏的默认单价；都没有或时长无效时返回 None
+    fn suggested_income(&self) -> Option<f64> {
+        let boss_rate = self.boss_rates.get(self.input_boss.trim()).copied().unwrap_or_default();
+        let rate = Some(boss_rate.rate)
+            .filter(|r| *r > 0.0)
+            .or_else(|| self.input_game_default.map(|d| d.rate).filter(|r| *r > 0.0))?;
+        let hours = parse_amount(&self.input_duration).filter(|h| *h > 0.0)?;
+        let amount = rate * hours;
+        let amount = if boss_rate.round_step > 0.0 { round_to(amount, boss_rate.round_step) } else { round2(amount) };
+        Some(amount).filter(|a| *a > 0.0)
+    }
+
+    /// 读取输入游戏的默认值（估算收入用）
+    fn load_game_default(&mut self) {
+        let game = self.input_game.trim();
+        self.input_game_default = if game.is_empty() { None } else { self.db.get_game_default(game) };
+    }
+
+    /// 选定游戏后读取默认值，时长为空时预填默认时长
+    fn apply_game_default(&mut self) {
+        self.load_game_default();
+        if let Some(d) = self.input_game_default.filter(|d| d.duration > 0.0) {
+            if self.input_duration.trim().is_empty() {
+                self.input_duration = d.duration.to_string();
             }
-        };
+        }
+    }
 
-        // 游戏名称（可为空）
-        let game: Option<&str> = if self.input_game.trim().is_empty() {
-            None
-        } else {
-            Some(self.input_game.trim())
+    /// 打开游戏设置弹窗，列出所有玩过的游戏及其默认值
+    fn open_game_settings(&mut self) {
+        let rows = self.game_list.iter()
+            .map(|game| GameDefaultRow {
+                game: game.clone(),
+                default: self.db.get_game_default(game).unwrap_or_default(),
+            })
+            .collect();
+        self.game_settings = Some(rows);
+    }
+
+    fn save_game_defaults(&mut self) {
+        let Some(rows) = &self.game_settings else {
+            return;
         };
+        let failed = rows.iter().any(|row| self.db.set_game_default(&row.game, &row.default).is_err());
+        if failed {
+            self.show_message("保存游戏设置失败", true);
+        } else {
+            self.load_game_default();
+            self.show_message("游戏设置已保存", false);
+        }
+    }
 
-        let date_str = self.input_date.format("%Y-%m-%d").to_string();
-        match self.db.add_record(&date_str, self.input_boss.trim(), income, duration, game, self.input_settled) {
-            Ok(_) => {
-                self.show_message(&format!("已添加 ¥{:.2}", income), false);
-                self.input_boss.clear();
-                self.input_income.clear();
-                self.input_duration.clear();
-                self.input_game.clear();
-                self.input_settled = false;
-                self.input_boss_error = false;
-                self.input_income_error = false;
+    /// 老板改名，记录、模板和备注一并更新
+    fn rename_boss(&mut self, old: &str, new: &str) {
+        // 与新增、导入一样规范名称，避免改出近似重复的老板
+        let new = normalize_name(new);
+        if new.is_empty() || new == old {
+            return;
+        }
+        match self.db.rename_boss(old, &new) {
+            Ok(count) => {
+                self.show_message(&format!("已将「{}」改名为「{}」（{} 条记录）", old, new, count), false);
+                self.templates = self.db.list_templates().unwrap_or_default();
                 self.refresh_data();
+                self.open_boss_detail(&new);
             }
-            Err(_) => {
-                self.show_message("添加失败", true);
-            }
+            Err(_) => self.show_message("改名失败", true),
         }
     }
 
-    fn delete_record(&mut self, id: i64) {
-        if self.db.delete_record(id).is_ok() {
-            self.show_message("已删除", false);
-            self.refresh_data();
+    /// 按标签筛选表格，再次点击同一标签取消筛选
+    fn toggle_tag_filter(&mut self, tag: &str) {
+        if self.tag_filter.as_deref() == Some(tag) {
+            self.tag_filter = None;
+            self.tag_records.clear();
+        } else {
+            self.tag_filter = Some(tag.to_string());
+            self.tag_records = self.db.records_with_tag(tag).unwrap_or_default();
+            self.day_filter = None;
         }
     }
-}
 
-fn days_in_month(year: i32, month: u32) -> u32 {
-    match month {
-        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
-        4 | 6 | 9 | 11 => 30,
-        2 => {
-            if (year % 4 == 0 && year % 100 != 0) || (year % 400 == 0) {
-                29
-            } else {
-                28
+    /// 当前的账目日期（考虑日切时间）
+    fn today(&self) -> NaiveDate {
+        effective_date(Local::now().naive_local(), self.settings.day_start_hour)
+    }
+
+    /// 读取本周的记录并汇总收入（week_start 为每周第一天）
+    fn load_week_balance(db: &Database, today: NaiveDate, week_start: Weekday) -> f64 {
+        let (first, last) = week_bounds(today, week_start);
+        let records = db
+            .get_records_between(&first.format("%Y-%m-%d").to_string(), &last.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        calc_week_balance(&records, today, week_start)
+    }
+
+    /// 程序开着跨过日切时间时，把停留在“今天”的输入日期和月份前移，并重新计算日/周/月统计
+    fn check_day_rollover(&mut self, ctx: &egui::Context) {
+        let now = Local::now().naive_local();
+        let today = effective_date(now, self.settings.day_start_hour);
+        if today != self.last_seen_date {
+            let previous = self.last_seen_date;
+            self.last_seen_date = today;
+            if self.input_date == previous {
+                self.input_date = today;
             }
+            if (self.selected_year, self.selected_month) == (previous.year(), previous.month()) {
+                self.selected_year = today.year();
+                self.selected_month = today.month();
+            }
+            self.refresh_data();
+        }
+        // 空闲时也在下一个日切时刻唤醒一次
+        if let Some(next_start) = today.succ_opt().and_then(|d| d.and_hms_opt(self.settings.day_start_hour.min(23), 0, 0)) {
+            let wait = (next_start - now).to_std().unwrap_or_default();
+            ctx.request_repaint_after(wait + Duration::from_secs(1));
         }
-        _ => 30,
     }
-}
 
-/// 格式化金额显示，大金额使用万/亿为单位
-fn format_money(amount: f64) -> String {
-    let abs_amount = amount.abs();
-    let sign = if amount < 0.0 { "-" } else { "" };
+    /// 重新统计输入日期当天的单数、时长和收入
+    fn refresh_day_data(&mut self) {
+        let date_str = self.input_date.format("%Y-%m-%d").to_string();
+        self.day_stats = self.db.day_stats(&date_str).unwrap_or_default();
+    }
 
-    if abs_amount >= 100_000_000.0 {
-        // 亿
-        format!("{}¥{:.2}亿", sign, abs_amount / 100_000_000.0)
-    } else if abs_amount >= 100_000.0 {
-        // 万
-        format!("{}¥{:.2}万", sign, abs_amount / 10_000.0)
-    } else {
-        format!("{}¥{:.2}", sign, abs_amount)
+    fn any_timer_running(&self) -> bool {
+        self.timers.iter().any(|t| t.is_running())
     }
-}
 
-/// 格式化收入显示（带+号）
-fn format_income(amount: f64) -> String {
-    let abs_amount = amount.abs();
+    /// 扣除系统睡眠的时长（各运行中的计时器都扣），暂停期间的睡眠不影响计时
+    fn skip_suspended_time(&mut self) {
+        let gap = suspend::take_gap();
+        if gap.is_zero() {
+            return;
+        }
+        let skipped = self.timers.iter_mut().map(|t| t.skip(gap)).max().unwrap_or_default();
+        if !skipped.is_zero() {
+            self.show_message(&format!("检测到系统休眠，计时已扣除 {} 分钟", skipped.as_secs().div_ceil(60)), false);
+        }
+    }
 
-    if abs_amount >= 100_000_000.0 {
-        format!("+{:.2}亿", abs_amount / 100_000_000.0)
-    } else if abs_amount >= 100_000.0 {
-        format!("+{:.2}万", abs_amount / 10_000.0)
-    } else {
-        format!("+{:.2}", abs_amount)
+    /// 将指定计时器的结果按设置取整后填入时长输入框
+    fn fill_duration_from_timer(&mut self, idx: usize) {
+        let Some(timer) = self.timers.get(idx) else {
+            return;
+        };
+        let hours = round_duration(timer.elapsed().as_secs(), self.settings.duration_rounding);
+        self.input_duration = hours.to_string();
+        self.timer_filled_duration = Some(self.input_duration.clone());
     }
-}
 
-impl eframe::App for App {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // 消息计时器
-        if self.message_timer > 0.0 {
-            self.message_timer -= ctx.input(|i| i.unstable_dt);
-            if self.message_timer <= 0.0 {
-                self.message.clear();
+    /// 处理计时器行上的操作，处理后保存计时器状态
+    fn apply_timer_action(&mut self, idx: usize, action: TimerAction) {
+        if idx >= self.timers.len() {
+            return;
+        }
+        match action {
+            TimerAction::Start => self.timers[idx].start(),
+            TimerAction::Pause => self.timers[idx].pause(),
+            TimerAction::Resume => self.timers[idx].resume(),
+            TimerAction::End => {
+                // 结束：停止计时但保留时间，写入计时记录
+                if let Some(start) = self.timers[idx].end() {
+                    let seconds = self.timers[idx].elapsed().as_secs() as i64;
+                    self.save_timer_session(&start, seconds);
+                }
+                // 时长未填写时自动填入
+                if self.input_duration.trim().is_empty() {
+                    self.fill_duration_from_timer(idx);
+                }
+            }
+            TimerAction::Reset => self.timers[idx].reset(),
+            TimerAction::Fill => self.fill_duration_from_timer(idx),
+            TimerAction::Add => {
+                let label = format!("计时{}", self.timers.len() + 1);
+                self.timers.push(TimerInstance::new(&label));
+            }
+            TimerAction::Remove => {
+                if self.timers.len() > 1 {
+                    self.timers.remove(idx);
+                }
             }
-            ctx.request_repaint();
         }
+        self.save_timers();
+    }
 
-        // 计时器运行时持续刷新
-        if self.timer_running {
-            ctx.request_repaint();
+    /// 记住查看的月份，重启后恢复
+    fn remember_selected_month(&mut self) {
+        let selected = Some((self.selected_year, self.selected_month));
+        if self.settings.selected_month != selected {
+            self.settings.selected_month = selected;
+            let _ = self.settings.save();
         }
+    }
 
-        // 加载主题和布局配置
-        let theme = Theme::default();
-        let layout = LayoutConfig::default();
+    /// 把计时器状态写入设置，重启后恢复
+    fn save_timers(&mut self) {
+        self.settings.timers = self.timers.iter().map(|t| t.to_saved()).collect();
+        let _ = self.settings.save();
+    }
 
-        // 解构主题颜色以保持代码兼容性
-        let bg_color = theme.bg_color;
-        let card_color = theme.card_color;
-        let input_bg = theme.input_bg;
-        let accent_color = theme.accent_color;
-        let green_color = theme.green_color;
-        let text_primary = theme.text_primary;
-        let text_secondary = theme.text_secondary;
-        let danger_color = theme.danger_color;
+    /// 结束计时时写入计时记录
+    fn save_timer_session(&mut self, start: &str, seconds: i64) {
+        let end = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        match self.db.add_timer_session(start, &end, seconds) {
+            Ok(id) => self.timer_session_id = Some(id),
+            Err(_) => self.show_message("保存计时记录失败", true),
+        }
+    }
 
-        // ===== 底部计时器栏（固定在底部）=====
-        egui::TopBottomPanel::bottom("timer_panel")
-            .frame(egui::Frame::default().fill(bg_color).inner_margin(egui::Margin {
-                left: layout.panel_margin as i8,
-                right: layout.panel_margin as i8,
-                top: 8,
-                bottom: 16
-            }))
-            .show(ctx, |ui| {
-                // 与内容区域等宽居中
-                let content_width = layout.content_width;
-                let available = ui.available_width();
-                let side_margin = ((available - content_width) / 2.0).max(0.0);
+    /// 添加的记录使用了计时填入的时长时，把计时关联到该记录
+    fn link_timer_session(&mut self, record_id: i64, duration_text: &str) {
+        let filled = self.timer_filled_duration.as_deref() == Some(duration_text);
+        if let Some(session) = self.timer_session_id.filter(|_| filled) {
+            let _ = self.db.link_timer_session(session, record_id);
+            self.timer_session_id = None;
+            self.timer_filled_duration = None;
+        }
+    }
 
-                ui.horizontal(|ui| {
-                    ui.add_space(side_margin);
-                    ui.vertical(|ui| {
-                        ui.set_width(content_width);
-                        // 获取实际卡片宽度，与表格对齐
-                        let timer_card_width = ui.available_width();
-                        ui.vertical(|ui| {
-                            ui.set_width(timer_card_width);
-                            egui::Frame::default()
-                                .fill(Color32::TRANSPARENT)  // 透明背景
-                                .stroke(Stroke::new(1.0, accent_color))  // 蓝色细边框
-                                .corner_radius(CornerRadius::same(layout.card_rounding as u8))  // 使用统一圆角
-                                .inner_margin(egui::Margin::symmetric(layout.card_inner_margin as i8, 16))  // 减小上下边距控制高度
-                                .show(ui, |ui| {
-                                ui.horizontal(|ui| {
-                            // 计算当前显示时间
-                            let elapsed = if self.timer_running {
-                                if let Some(start) = self.timer_start_instant {
-                                    self.timer_accumulated + start.elapsed()
-                                } else {
-                                    self.timer_accumulated
-                                }
-                            } else {
-                                self.timer_accumulated
-                            };
+    fn open_timer_sessions(&mut self) {
+        match self.db.list_timer_sessions() {
+            Ok(sessions) => self.timer_sessions = Some(sessions),
+            Err(_) => self.show_message("读取计时记录失败", true),
+        }
+    }
 
-                            let total_secs = elapsed.as_secs();
-                            let hours = total_secs / 3600;
-                            let minutes = (total_secs % 3600) / 60;
-                            let seconds = total_secs % 60;
-                            let time_str = format!("{:02}:{:02}:{:02}", hours, minutes, seconds);
-
-                            // 判断计时器状态
-                            let is_initial = !self.timer_running && self.timer_accumulated.is_zero() && !self.timer_ended;
-                            let is_running = self.timer_running;
-                            let is_paused = !self.timer_running && !self.timer_accumulated.is_zero() && !self.timer_ended;
-                            let is_ended = self.timer_ended;
-
-                            // 左侧：标签
-                            ui.label(RichText::new("计时").size(14.0).color(text_secondary));
-                            ui.add_space(16.0);
-
-                            // 时间显示（放大字体，更突出）
-                            let time_color = if is_running {
-                                accent_color
-                            } else if is_paused {
-                                theme.warning_color
-                            } else if is_ended {
-                                text_primary
-                            } else {
-                                text_secondary
-                            };
-                            ui.label(RichText::new(time_str)
-                                .font(FontId::monospace(32.0))  // 从24放大到32
-                                .color(time_color));
+    fn open_audit_log(&mut self) {
+        match self.db.recent_audit(AUDIT_LIMIT) {
+            Ok(entries) => self.audit_log = Some(entries),
+            Err(_) => self.show_message("读取操作记录失败", true),
+        }
+    }
 
-                            ui.add_space(24.0);
+    fn open_about(&mut self) {
+        self.data_dir_failed = None;
+        match self.db.stats() {
+            Ok(stats) => self.about = Some(stats),
+            Err(_) => self.show_message("读取数据概况失败", true),
+        }
+    }
 
-                            // 按钮区域
-                            let btn_height = 30.0;
-                            let btn_width = 56.0;
+    /// 在文件管理器中打开数据目录，失败时记下路径供手动前往
+    /// 打开前先把 WAL 写回主文件，这时直接复制 records.db 就是完整的备份
+    fn open_data_dir(&mut self) {
+        let _ = self.db.checkpoint();
+        let dir = Database::data_dir();
+        self.data_dir_failed = match open_in_file_manager(&dir) {
+            Ok(_) => None,
+            Err(_) => Some(dir.display().to_string()),
+        };
+    }
 
-                            // 开始按钮（仅初始状态可用）
-                            if is_initial {
-                                let start_btn = egui::Button::new(RichText::new("开始").size(13.0).color(Color32::WHITE))
-                                    .fill(green_color)
-                                    .corner_radius(CornerRadius::same(6));
-                                if ui.add_sized([btn_width, btn_height], start_btn).clicked() {
-                                    self.timer_running = true;
-                                    self.timer_start_instant = Some(Instant::now());
-                                    self.timer_ended = false;
-                                }
-                            } else {
-                                let disabled_btn = egui::Button::new(RichText::new("开始").size(13.0).color(theme.disabled_text))
-                                    .fill(theme.disabled_bg)
-                                    .corner_radius(CornerRadius::same(6));
-                                ui.add_sized([btn_width, btn_height], disabled_btn);
-                            }
+    fn purge_audit_log(&mut self) {
+        match self.db.purge_audit(self.audit_purge_days) {
+            Ok(count) => {
+                self.show_message(&format!("已清除 {} 条操作记录", count), false);
+                self.open_audit_log();
+            }
+            Err(_) => self.show_message("清除失败", true),
+        }
+    }
 
-                            ui.add_space(12.0);  // 增加按钮间距
+    fn export_timer_sessions(&mut self) {
+        let Some(sessions) = &self.timer_sessions else {
+            return;
+        };
+        let count = sessions.len();
+        let path = export::default_export_dir().join(format!("计时记录-{}.csv", Local::now().format("%Y%m%d-%H%M%S")));
+        match export::export_timer_sessions_csv(sessions, &path) {
+            Ok(_) => self.show_message(&format!("已导出 {} 条计时记录到 {}", count, path.display()), false),
+            Err(_) => self.show_message("导出失败", true),
+        }
+    }
 
-                            // 暂停/继续按钮（运行中或暂停中可用）
-                            if is_running {
-                                let pause_btn = egui::Button::new(RichText::new("暂停").size(13.0).color(Color32::WHITE))
-                                    .fill(theme.warning_color)
-                                    .corner_radius(CornerRadius::same(6));
-                                if ui.add_sized([btn_width, btn_height], pause_btn).clicked() {
-                                    if let Some(start) = self.timer_start_instant {
-                                        self.timer_accumulated += start.elapsed();
-                                    }
-                                    self.timer_running = false;
-                                    self.timer_start_instant = None;
-                                }
-                            } else if is_paused {
-                                let resume_btn = egui::Button::new(RichText::new("继续").size(13.0).color(Color32::WHITE))
-                                    .fill(accent_color)
-                                    .corner_radius(CornerRadius::same(6));
-                                if ui.add_sized([btn_width, btn_height], resume_btn).clicked() {
-                                    self.timer_running = true;
-                                    self.timer_start_instant = Some(Instant::now());
-                                }
-                            } else {
-                                let disabled_btn = egui::Button::new(RichText::new("暂停").size(13.0).color(theme.disabled_text))
-                                    .fill(theme.disabled_bg)
-                                    .corner_radius(CornerRadius::same(6));
-                                ui.add_sized([btn_width, btn_height], disabled_btn);
-                            }
+    /// 每个计时器每满设定间隔发一次系统通知，暂停、结束或关闭提醒时不触发
+    fn check_reminder(&mut self) {
+        if !self.settings.session_reminder {
+            return;
+        }
+        let interval = Duration::from_secs(u64::from(self.settings.reminder_minutes.max(1)) * 60);
+        for timer in self.timers.iter_mut().filter(|t| t.is_running()) {
+            let elapsed = timer.elapsed();
+            // 中途开启提醒时从下一个整间隔开始，不立即补发
+            let next = *timer.next_reminder.get_or_insert_with(|| next_threshold(elapsed, interval));
+            if elapsed >= next {
+                notify_session(&timer.label, elapsed);
+                timer.next_reminder = Some(next_threshold(elapsed, interval));
+            }
+        }
+    }
 
-                            ui.add_space(12.0);  // 增加按钮间距
+    /// 计时中在窗口标题显示已计时长（任务栏可见，多个计时器时显示第一个运行中的），停止后恢复原标题
+    /// 标题按秒变化，只在文字变化时发送命令，每秒最多一次
+    fn sync_title(&mut self, ctx: &egui::Context) {
+        let title = if let Some(timer) = self.timers.iter().find(|t| t.is_running()) {
+            let secs = timer.elapsed().as_secs();
+            format!("{} — {:02}:{:02}:{:02}", APP_TITLE, secs / 3600, secs % 3600 / 60, secs % 60)
+        } else {
+            APP_TITLE.to_string()
+        };
+        if title != self.window_title {
+            ctx.send_viewport_cmd(egui::ViewportCommand::Title(title.clone()));
+            self.window_title = title;
+        }
+    }
 
-                            // 结束按钮（运行中或暂停中可用，结束后禁用）
-                            if is_running || is_paused {
-                                let end_btn = egui::Button::new(RichText::new("结束").size(13.0).color(danger_color))
-                                    .fill(Color32::TRANSPARENT)
-                                    .stroke(Stroke::new(1.0, danger_color))
-                                    .corner_radius(CornerRadius::same(6));
-                                if ui.add_sized([btn_width, btn_height], end_btn).clicked() {
-                                    // 结束：停止计时但保留时间
-                                    if let Some(start) = self.timer_start_instant {
-                                        self.timer_accumulated += start.elapsed();
-                                    }
-                                    self.timer_running = false;
-                                    self.timer_start_instant = None;
-                                    self.timer_ended = true;
-                                }
-                            } else {
-                                let disabled_btn = egui::Button::new(RichText::new("结束").size(13.0).color(theme.disabled_text))
-                                    .fill(Color32::TRANSPARENT)
-                                    .stroke(Stroke::new(1.0, Color32::from_rgb(60, 65, 75)))
-                                    .corner_radius(CornerRadius::same(6));
-                                ui.add_sized([btn_width, btn_height], disabled_btn);
-                            }
+    /// 按设置创建或移除托盘图标，并把计时状态写到托盘提示里
+    #[cfg(windows)]
+    fn sync_tray(&mut self, ctx: &egui::Context) {
+        if self.settings.close_to_tray && self.tray.is_none() {
+            match tray::Tray::new(ctx) {
+                Ok(tray) => self.tray = Some(tray),
+                Err(_) => {
+                    self.settings.close_to_tray = false;
+                    self.show_message("托盘图标创建失败", true);
+                }
+            }
+        } else if !self.settings.close_to_tray {
+            self.tray = None;
+        }
 
-                            ui.add_space(12.0);  // 增加按钮间距
+        let timer = self.timers.iter().find(|t| t.is_running())
+            .or_else(|| self.timers.iter().find(|t| t.is_paused()));
+        let tooltip = if let Some(timer) = timer {
+            let secs = timer.elapsed().as_secs();
+            let state = if timer.is_running() { "计时中" } else { "已暂停" };
+            format!("{} · {} {} {:02}:{:02}:{:02}", APP_TITLE, timer.label, state, secs / 3600, secs % 3600 / 60, secs % 60)
+        } else {
+            APP_TITLE.to_string()
+        };
+        if let Some(tray) = &mut self.tray {
+            tray.set_tooltip(&tooltip);
+        }
+    }
 
-                            // 重置按钮（仅结束后可用）
-                            if is_ended {
-                                let reset_btn = egui::Button::new(RichText::new("重置").size(13.0).color(text_secondary))
-                                    .fill(input_bg)
-                                    .corner_radius(CornerRadius::same(6));
-                                if ui.add_sized([btn_width, btn_height], reset_btn).clicked() {
-                                    self.timer_accumulated = Duration::ZERO;
-                                    self.timer_ended = false;
-                                }
-                            } else {
-                                let disabled_btn = egui::Button::new(RichText::new("重置").size(13.0).color(theme.disabled_text))
-                                    .fill(theme.disabled_bg)
-                                    .corner_radius(CornerRadius::same(6));
-                                ui.add_sized([btn_width, btn_height], disabled_btn);
-                            }
+    /// 按设置注册或注销全局快捷键；快捷键按下后聚焦老板输入框
+    fn sync_hotkey(&mut self, ctx: &egui::Context) {
+        let combo = self.settings.hotkey_combo.trim();
+        if !self.settings.global_hotkey {
+            self.hotkey = None;
+        } else if self.hotkey.as_ref().is_none_or(|h| h.combo() != combo) && self.hotkey_failed.as_deref() != Some(combo) {
+            // 先注销旧的组合键再注册新的
+            self.hotkey = None;
+            match hotkey::GlobalHotkey::new(ctx, combo) {
+                Ok(hotkey) => {
+                    self.hotkey = Some(hotkey);
+                    self.hotkey_failed = None;
+                }
+                Err(_) => self.hotkey_failed = Some(combo.to_string()),
+            }
+        }
 
-                            // 计算选中日期的统计数据
-                            let selected_date_str = self.input_date.format("%Y-%m-%d").to_string();
-                            let day_records: Vec<&Record> = self.records.iter()
-                                .filter(|r| r.date == selected_date_str)
-                                .collect();
-
-                            let day_count = day_records.len();
-                            let day_hours: f64 = day_records.iter()
-                                .filter_map(|r| r.duration)
-                                .sum();
-                            let day_income: f64 = day_records.iter().map(|r| r.income).sum();
-                            let is_today = self.input_date == Local::now().date_naive();
-                            let day_label = if is_today { "今日收入" } else { &format!("{}月{}日", self.input_date.month(), self.input_date.day()) };
-
-                            // 今日统计面板 - 使用右对齐布局
-                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                                // 注意：right_to_left 布局从右到左添加元素，所以顺序要反过来
-                                ui.label(RichText::new(format!("{:.1}h", day_hours))
-                                    .size(14.0)
-                                    .color(text_primary));
+        if self.hotkey.as_ref().is_some_and(|h| h.take_triggered()) {
+            ctx.memory_mut(|m| m.request_focus(egui::Id::new("input_boss")));
+        }
+    }
 
-                                ui.label(RichText::new("·").size(14.0).color(text_primary));
+    fn show_message(&mut self, msg: &str, is_error: bool) {
+        self.message = msg.to_string();
+        self.message_is_error = is_error;
+        self.message_timer = 3.0;
+    }
 
-                                ui.label(RichText::new(format!("{}单", day_count))
-                                    .size(14.0)
-                                    .color(text_primary));
+    fn add_record(&mut self) {
+        let max_income = self.settings.max_income;
 
-                                ui.label(RichText::new("·").size(14.0).color(text_primary));
+        self.clear_input_errors();
 
-                                ui.label(RichText::new(format_money(day_income))
-                                    .size(14.0)
-                                    .color(text_primary));
+        // 规范名称（空白、全角），避免产生近似重复的老板和游戏
+        self.input_boss = normalize_name(&self.input_boss);
+        self.input_game = normalize_name(&self.input_game);
 
-                                ui.add_space(8.0);
+        // 验证各字段，所有字段都检查完再返回，一次显示全部错误
+        // 可选：禁止未来日期
+        if self.settings.forbid_future_dates && self.input_date > self.today() {
+            self.input_date_error = Some("不能是未来日期".to_string());
+        }
+        if self.input_boss.trim().is_empty() {
+            self.input_boss_error = Some("请输入老板名称".to_string());
+        }
+        let income = parse_income(&self.input_income);
+        match income {
+            None if self.input_income.trim().is_empty() => self.input_income_error = Some("请输入金额".to_string()),
+            None => self.input_income_error = Some("请输入有效金额".to_string()),
+            // 检查单笔金额上限
+            Some(v) if v > max_income => {
+                self.input_income_error = Some(format!("不能超过 {}", self.money.money(max_income)));
+            }
+            Some(_) => {}
+        }
 
-                                ui.label(RichText::new(day_label)
-                                    .size(14.0)
-                                    .color(text_primary));
-                            });
-                                });
-                            });
-                        });  // 闭合新增的 vertical (timer_card_width)
-                    });
-                });
-            });
+        // 解析时长（可为空，支持小数）
+        let duration: Option<f64> = if self.input_duration.trim().is_empty() {
+            None
+        } else {
+            match parse_amount(&self.input_duration) {
+                Some(v) if v > 0.0 => Some((v * 10.0).round() / 10.0), // 保留一位小数
+                _ => {
+                    self.input_duration_error = Some("请输入有效时长".to_string());
+                    None
+                }
+            }
+        };
 
-        // 设置全局样式
-        let mut style = (*ctx.style()).clone();
-        style.visuals.widgets.inactive.bg_fill = input_bg;
-        style.visuals.widgets.inactive.bg_stroke = Stroke::new(1.0, Color32::from_rgb(60, 65, 75));
-        style.visuals.widgets.inactive.corner_radius = CornerRadius::same(8);
-        style.visuals.widgets.hovered.bg_fill = Color32::from_rgb(55, 60, 70);
-        style.visuals.widgets.active.bg_fill = Color32::from_rgb(50, 55, 65);
-        style.visuals.selection.bg_fill = accent_color;
-        ctx.set_style(style);
+        if self.input_date_error.is_some()
+            || self.input_boss_error.is_some()
+            || self.input_income_error.is_some()
+            || self.input_duration_error.is_some()
+        {
+            return;
+        }
+        let income = income.unwrap_or_default();
 
-        egui::CentralPanel::default()
-            .frame(egui::Frame::default().fill(bg_color).inner_margin(layout.panel_margin as i8))
-            .show(ctx, |ui| {
-                // 固定内容宽度，居中显示
-                let content_width = layout.content_width;
-                let available = ui.available_width();
-                let side_margin = ((available - content_width) / 2.0).max(0.0);
+        // 游戏名称（可为空）
+        let game = if self.input_game.trim().is_empty() {
+            None
+        } else {
+            Some(self.input_game.trim().to_string())
+        };
 
-                ui.horizontal(|ui| {
-                    ui.add_space(side_margin);
-                    ui.vertical(|ui| {
-                        ui.set_width(content_width);
+        let date_str = self.input_date.format("%Y-%m-%d").to_string();
 
-                // ===== 顶部标题区 =====
-                let mut month_changed = false;
-                let mut new_sel_year = self.selected_year;
-                let mut new_sel_month = self.selected_month;
-                let combo_text_color = Color32::from_rgb(30, 30, 35); // 下拉框文字用深色
+        // 疑似重复：同日期、同老板、同金额已存在时，需在几秒内再次点击确认
+        let dup_key = format!("{}|{}|{}", date_str, self.input_boss.trim(), income);
+        let confirmed = matches!(&self.duplicate_pending, Some((key, _)) if *key == dup_key);
+        self.duplicate_pending = None;
+        if !confirmed && self.db.record_exists(&date_str, self.input_boss.trim(), income) {
+            self.duplicate_pending = Some((dup_key, Instant::now()));
+            self.show_message("疑似重复记录，再次点击添加以确认", true);
+            return;
+        }
 
-                // 标题行：左边标题，右边统计信息
-                ui.horizontal(|ui| {
-                    // 左边：标题
-                    ui.label(RichText::new("陪玩日记")
-                        .font(FontId::new(32.0, egui::FontFamily::Name("cute".into())))
-                        .color(text_primary));
+        let record = Record {
+            date: date_str,
+            boss: self.input_boss.trim().to_string(),
+            income,
+            duration,
+            game,
+            settled: self.input_settled,
+            currency: self.input_currency.clone(),
+            ..Record::default()
+        };
+        match self.db.add_record(&record) {
+            Ok(id) => {
+                let duration_text = self.input_duration.trim().to_string();
+                self.link_timer_session(id, &duration_text);
+                self.show_message(&format!("已添加 {}", self.money.money_in(income, &record.currency)), false);
+                self.finish_onboarding();
+                self.remember_input();
+                self.clear_input();
+                self.restore_pinned_input();
+                self.refresh_data();
+            }
+            Err(_) => {
+                self.show_message("添加失败", true);
+            }
+        }
+    }
 
-                    // 右边：统计信息（右对齐）
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        // 从右到左排列：总结余 -> 月结余 -> 日结余
+    /// 用户添加或导入了真实数据，以后不再显示新手引导
+    fn finish_onboarding(&mut self) {
+        if !self.settings.onboarded {
+            self.settings.onboarded = true;
+            let _ = self.settings.save();
+        }
+    }
 
-                        // 总结余
-                        ui.label(RichText::new(format_money(self.total_balance))
-                            .font(FontId::proportional(22.0))
-                            .color(green_color));
-                        ui.label(RichText::new("年收入")
-                            .font(FontId::proportional(13.0))
-                            .color(text_secondary));
+    /// 插入两条示例记录，方便新用户了解界面（可随时删除）
+    fn add_demo_records(&mut self) {
+        let today = self.today();
+        let demos = [
+            (today, "示例老板", "示例游戏", 2.0, 100.0, true),
+            (today.pred_opt().unwrap_or(today), "示例老板", "示例游戏", 1.5, 75.0, false),
+        ];
+        for (date, boss, game, duration, income, settled) in demos {
+            let record = Record {
+                date: date.format("%Y-%m-%d").to_string(),
+                boss: boss.to_string(),
+                income,
+                duration: Some(duration),
+                game: Some(game.to_string()),
+                settled,
+                ..Record::default()
+            };
+            if self.db.add_record(&record).is_err() {
+                self.show_message("添加示例失败", true);
+                break;
+            }
+        }
+        self.selected_year = today.year();
+        self.selected_month = today.month();
+        self.refresh_data();
+    }
 
-                        ui.add_space(20.0);
+    /// 记住本次添加的老板、游戏、时长（开启“记住上次输入”时）
+    fn remember_input(&mut self) {
+        if !self.settings.remember_input {
+            return;
+        }
+        self.settings.last_boss = self.input_boss.trim().to_string();
+        self.settings.last_game = self.input_game.trim().to_string();
+        self.settings.last_duration = self.input_duration.trim().to_string();
+        let _ = self.settings.save();
+    }
 
-                        // 月结余
-                        ui.label(RichText::new(format_money(self.month_balance))
-                            .font(FontId::proportional(18.0))
-                            .color(accent_color));
+    /// 用上次输入预填固定的字段，收入始终留空
+    fn restore_pinned_input(&mut self) {
+        if !self.settings.remember_input {
+            return;
+        }
+        if self.settings.pin_boss {
+            self.input_boss = self.settings.last_boss.clone();
+        }
+        if self.settings.pin_game {
+            self.input_game = self.settings.last_game.clone();
+        }
+        if self.settings.pin_duration {
+            self.input_duration = self.settings.last_duration.clone();
+        }
+        self.load_game_default();
+    }
 
-                        // 月份选择
-                        let month_combo = egui::ComboBox::from_id_salt("header_month_select")
-                            .width(45.0)
-                            .selected_text(RichText::new(format!("{:02}", new_sel_month)).size(13.0).color(combo_text_color));
-                        month_combo.show_ui(ui, |ui| {
-                            for m in 1..=12u32 {
-                                if ui.selectable_value(&mut new_sel_month, m, format!("{:02}月", m)).changed() {
-                                    month_changed = true;
-                                }
-                            }
-                        });
+    fn clear_input_errors(&mut self) {
+        self.input_boss_error = None;
+        self.input_income_error = None;
+        self.input_duration_error = None;
+        self.input_date_error = None;
+    }
 
-                        ui.label(RichText::new("-").size(13.0).color(text_secondary));
-
-                        // 年份选择
-                        let current_year = Local::now().year();
-                        let year_combo = egui::ComboBox::from_id_salt("header_year_select")
-                            .width(65.0)
-                            .selected_text(RichText::new(format!("{}", new_sel_year)).size(13.0).color(combo_text_color));
-                        year_combo.show_ui(ui, |ui| {
-                            for y in ((current_year - 10)..=(current_year)).rev() {
-                                if ui.selectable_value(&mut new_sel_year, y, format!("{}年", y)).changed() {
-                                    month_changed = true;
-                                }
-                            }
-                        });
+    /// 清空输入行并关闭联想弹窗
+    fn clear_input(&mut self) {
+        self.input_boss.clear();
+        self.input_income.clear();
+        self.input_duration.clear();
+        self.input_game.clear();
+        self.input_game_default = None;
+        self.input_settled = false;
+        self.clear_input_errors();
+        self.active_suggestion = ActiveSuggestion::None;
+        self.selected_suggestion_index = None;
+    }
 
-                        ui.label(RichText::new("月收入")
-                            .font(FontId::proportional(13.0))
-                            .color(text_secondary));
+    fn delete_record(&mut self, id: i64) {
+        if self.db.delete_record(id).is_ok() {
+            self.show_message("已删除", false);
+            self.refresh_data();
+        }
+    }
 
-                    });
-                });
+    /// 请求删除：开启删除确认时先弹窗确认，否则直接删除
+    fn request_delete(&mut self, id: i64) {
+        if self.settings.confirm_delete {
+            self.pending_delete = Some(id);
+        } else {
+            self.delete_record(id);
+        }
+    }
 
-                // 处理年月选择变化
-                if month_changed || new_sel_year != self.selected_year || new_sel_month != self.selected_month {
-                    self.selected_year = new_sel_year;
-                    self.selected_month = new_sel_month;
-                    let year_month = format!("{}-{:02}", self.selected_year, self.selected_month);
-                    self.month_balance = Self::calc_month_balance(&self.records, &year_month);
-                    self.total_balance = Self::calc_year_balance(&self.records, self.selected_year);
-                }
+    /// 执行已确认的批量结清 / 取消结清
+    fn apply_settle(&mut self, pending: PendingSettle) {
+        let boss = pending.boss.as_deref();
+        let period = pending.period.as_deref();
+        let result = if pending.settled {
+            self.db.settle_all(boss, period)
+        } else {
+            self.db.unsettle_all(boss, period)
+        };
+        match result {
+            Ok(count) => {
+                let action = if pending.settled { "结清" } else { "取消结清" };
+                self.show_message(&format!("已{} {} 条记录", action, count), false);
+                self.refresh_data();
+            }
+            Err(_) => self.show_message("批量更新失败", true),
+        }
+    }
 
-                ui.add_space(30.0);
+    /// 重新统计收入分布（全部记录时单独查询，不影响选中月份的数据）
+    fn refresh_histogram(&mut self) {
+        self.histogram = if self.histogram_all_time {
+            income_histogram(&self.db.get_all_records().unwrap_or_default(), self.histogram_bin)
+        } else {
+            income_histogram(&self.records, self.histogram_bin)
+        };
+    }
 
-                // 定义统一的卡片宽度
-                let cards_width = ui.available_width();
+    fn open_unsettled_report(&mut self) {
+        match self.db.unsettled_by_boss() {
+            Ok(rows) => self.unsettled_report = Some(rows),
+            Err(_) => self.show_message("读取待结算失败", true),
+        }
+    }
 
-                // ===== 输入卡片 =====
-                let card_inner_w = cards_width - (layout.card_inner_margin * 2.0);
-                ui.vertical(|ui| {
-                    ui.set_width(cards_width);
-                    egui::Frame::default()
-                        .fill(card_color)
-                        .corner_radius(CornerRadius::same(layout.card_rounding as u8))
-                        .inner_margin(layout.card_inner_margin as i8)
-                        .show(ui, |ui| {
-                            ui.set_width(card_inner_w);
-                        let input_height = 40.0;
-                        let label_size = 13.0;
-                        let input_font_size = 15.0;
-                        let col_spacing = layout.col_spacing;
+    /// 导出文件路径：默认导出目录下按时间戳命名
+    fn export_path(ext: &str) -> PathBuf {
+        let file_name = format!("jz-{}.{}", Local::now().format("%Y%m%d-%H%M%S"), ext);
+        export::default_export_dir().join(file_name)
+    }
 
-                        // 固定宽度元素
-                        let date_width = 175.0;  // 日期选择框
-                        let today_btn_width = 50.0;
-                        let btn_width = 65.0;
-                        let checkbox_width = 55.0;  // 结清勾选框加宽
+    /// 导出 CSV，勾选“仅导出当前视图”时只导出表格中显示的记录
+    fn export_csv(&mut self, view: &[Record], view_desc: &str) {
+        let all_records;
+        let (records, desc) = if self.export_current_view {
+            (view, view_desc)
+        } else {
+            all_records = self.db.get_all_records().unwrap_or_default();
+            (all_records.as_slice(), "全部记录")
+        };
+        let count = records.len();
+        let path = Self::export_path("csv");
+        match export::export_csv(records, &path, desc, self.export_summary) {
+            Ok(_) => self.show_message(&format!("已导出 {} 条记录到 {}", count, path.display()), false),
+            Err(_) => self.show_message("导出失败", true),
+        }
+    }
 
-                        // 动态分配剩余宽度给输入框
-                        let fixed_total = date_width + today_btn_width + btn_width + checkbox_width;
-                        let spacing_total = col_spacing * 7.0;
-                        let flex_total = (card_inner_w - fixed_total - spacing_total).max(200.0);
-                        // 比例分配，并设置最小宽度保护（防止从全屏游戏切换时窗口异常缩小）
-                        let boss_width = (flex_total * 0.28).max(80.0);
+    /// 选择文件夹，按月份各导出一个 CSV（始终包含全部记录）
+    fn export_csv_by_month(&mut self) {
+        let Some(dir) = rfd::FileDialog::new()
+            .set_directory(export::default_export_dir())
+            .pick_folder()
+        else {
+            return;
+        };
+        let records = self.db.get_all_records().unwrap_or_default();
+        match export::export_csv_by_month(&records, &dir, self.export_summary) {
+            Ok(0) => self.show_message("没有可导出的记录", true),
+            Ok(count) => self.show_message(&format!("已导出 {} 个文件到 {}", count, dir.display()), false),
+            Err(_) => self.show_message("导出失败", true),
+        }
+    }
+
+    /// 导出 JSON 完整备份（始终包含全部记录）
+    fn export_json(&mut self) {
+        let path = Self::export_path("json");
+        match self.db.export_json(&path) {
+            Ok(count) => self.show_message(&format!("已备份 {} 条记录到 {}", count, path.display()), false),
+            Err(_) => self.show_message("备份失败", true),
+        }
+    }
+
+    /// 请求截图导出图表，截图在之后的帧中送达
+    fn export_chart(&mut self, ctx: &egui::Context, name: String, rect: egui::Rect) {
+        self.pending_chart_export = Some((name, rect));
+        ctx.send_viewport_cmd(egui::ViewportCommand::Screenshot(egui::UserData::default()));
+    }
+
+    /// 收到截图后裁剪出图表区域，保存为 PNG
+    fn save_chart_screenshot(&mut self, ctx: &egui::Context) {
+        if self.pending_chart_export.is_none() {
+            return;
+        }
+        let screenshot = ctx.input(|i| i.events.iter().find_map(|e| match e {
+            egui::Event::Screenshot { image, .. } => Some(image.clone()),
+            _ => None,
+        }));
+        let (Some(screenshot), Some((name, rect))) = (screenshot, self.pending_chart_export.take()) else {
+            return;
+        };
+        let chart = screenshot.region(&rect, Some(ctx.pixels_per_point()));
+        let path = export::default_export_dir()
+            .join(format!("jz-{}-{}.png", name, Local::now().format("%Y%m%d-%H%M%S")));
+        let [width, height] = chart.size;
+        match export::export_png(chart.as_raw(), width as u32, height as u32, &path) {
+            Ok(_) => self.show_message(&format!("已导出图表到 {}", path.display()), false),
+            Err(_) => self.show_message("导出图表失败", true),
+        }
+    }
+
+    /// 选择 JSON 备份文件并导入，重复记录自动跳过
+    fn import_json(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON 备份", &["json"])
+            .set_directory(export::default_export_dir())
+            .pick_file()
+        else {
+            return;
+        };
+        match self.db.import_json(&path) {
+            Ok(count) => {
+                self.show_message(&format!("已导入 {} 条记录", count), false);
+                if count > 0 {
+                    self.finish_onboarding();
+                }
+                self.refresh_data();
+            }
+            Err(_) => self.show_message("导入失败，请检查文件格式", true),
+        }
+    }
+
+    /// 选择任意 CSV 文件，打开列映射向导
+    fn open_csv_import(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("CSV 文件", &["csv"])
+            .set_directory(export::default_export_dir())
+            .pick_file()
+        else {
+            return;
+        };
+        let Ok(text) = std::fs::read_to_string(&path) else {
+            self.show_message("读取失败，请确认文件为 UTF-8 编码", true);
+            return;
+        };
+        let mut rows = import::parse_csv(&text);
+        import::strip_summary_rows(&mut rows);
+        if rows.is_empty() {
+            self.show_message("文件中没有数据", true);
+            return;
+        }
+        let mapping = ColumnMapping::guess(&rows[0]);
+        self.csv_import = Some(CsvImport {
+            file_name: path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default(),
+            has_header: mapping != ColumnMapping::default(),
+            rows,
+            mapping,
+            date_format: DateFormat::Ymd,
+        });
+    }
+
+    /// 按映射导入 CSV 中能解析的行，重复记录自动跳过，提示中分别列出无效和重复的条数
+    fn commit_csv_import(&mut self) {
+        let Some(wizard) = self.csv_import.take() else {
+            return;
+        };
+        let (records, invalid): (Vec<_>, Vec<_>) = wizard.parse_rows(self.settings.max_income, &self.money)
+            .into_iter()
+            .partition(Result::is_ok);
+        let records: Vec<Record> = records.into_iter().flatten().collect();
+        let valid = records.len();
+        match self.db.import_records(records) {
+            Ok(count) => {
+                self.show_message(
+                    &format!("已导入 {} 条，跳过 {} 条无效、{} 条重复", count, invalid.len(), valid - count),
+                    false,
+                );
+                if count > 0 {
+                    self.finish_onboarding();
+                }
+                self.refresh_data();
+            }
+            Err(_) => self.show_message("导入失败", true),
+        }
+    }
+
+    /// 自动锁定是否生效：未加密的数据库需先设置 PIN
+    fn auto_lock_enabled(&self) -> bool {
+        self.settings.auto_lock && (self.db_encrypted || self.settings.has_lock_pin())
+    }
+
+    /// 记录键鼠操作时间，超过设定时间无操作则锁定
+    fn check_auto_lock(&mut self, ctx: &egui::Context) {
+        let (now, active) = ctx.input(|i| {
+            let active = i.events.iter().any(|e| matches!(e,
+                egui::Event::Key { .. }
+                    | egui::Event::Text(_)
+                    | egui::Event::PointerMoved(_)
+                    | egui::Event::PointerButton { .. }
+                    | egui::Event::MouseWheel { .. }));
+            (i.time, active)
+        });
+        if active || !self.auto_lock_enabled() {
+            self.last_input_time = now;
+            return;
+        }
+        let timeout = f64::from(self.settings.lock_minutes.max(1)) * 60.0;
+        let idle = now - self.last_input_time;
+        if idle >= timeout {
+            self.locked = true;
+            self.unlock_input.clear();
+            self.unlock_error = false;
+            self.pending_delete = None;
+        } else {
+            ctx.request_repaint_after(Duration::from_secs_f64(timeout - idle));
+        }
+    }
+
+    /// 校验密码（加密数据库）或 PIN 后解锁
+    fn try_unlock(&mut self, ctx: &egui::Context) {
+        let ok = if self.db_encrypted {
+            Database::check_password(&self.unlock_input)
+        } else {
+            self.settings.check_lock_pin(&self.unlock_input)
+        };
+        self.unlock_input.clear();
+        self.unlock_error = !ok;
+        if ok {
+            self.locked = false;
+            self.last_input_time = ctx.input(|i| i.time);
+        }
+    }
+
+    /// 为数据库设置或修改密码，两次输入一致才执行
+    #[cfg(feature = "encryption")]
+    fn set_password(&mut self) {
+        let Some(form) = &self.password_form else {
+            return;
+        };
+        if form.password.is_empty() {
+            self.show_message("密码不能为空", true);
+            return;
+        }
+        if form.password != form.confirm {
+            self.show_message("两次输入的密码不一致", true);
+            return;
+        }
+        let password = form.password.clone();
+        match self.db.set_password(&password) {
+            Ok(_) => {
+                self.password_form = None;
+                self.db_encrypted = true;
+                self.show_message("密码已设置，下次启动需输入密码", false);
+            }
+            Err(e) => self.show_message(&format!("设置密码失败：{}", e), true),
+        }
+    }
+
+    /// 导出 Excel，同样遵循“仅导出当前视图”选项
+    fn export_xlsx(&mut self, view: &[Record]) {
+        let all_records;
+        let records = if self.export_current_view {
+            view
+        } else {
+            all_records = self.db.get_all_records().unwrap_or_default();
+            all_records.as_slice()
+        };
+        let count = records.len();
+        let path = Self::export_path("xlsx");
+        match export::export_xlsx(records, &path, &self.money.symbol, self.money.decimals) {
+            Ok(_) => self.show_message(&format!("已导出 {} 条记录到 {}", count, path.display()), false),
+            Err(_) => self.show_message("导出失败", true),
+        }
+    }
+}
+
+/// 占用恰好 height 高度的一行：show_rows 按固定行高换算滚动位置，
+/// 内容偏高时裁掉多出的部分而不是撑高这一行，否则行数多了会越滚越偏
+fn fixed_height_row<R>(ui: &mut egui::Ui, height: f32, add_contents: impl FnOnce(&mut egui::Ui) -> R) -> R {
+    let (rect, _) = ui.allocate_exact_size(Vec2::new(ui.available_width(), height), egui::Sense::hover());
+    let mut row_ui = ui.new_child(egui::UiBuilder::new().max_rect(rect).layout(*ui.layout()));
+    row_ui.set_clip_rect(rect.intersect(ui.clip_rect()));
+    add_contents(&mut row_ui)
+}
+
+/// 年月前后移动若干个月，跨年时自动进位
+fn shift_month(year: i32, month: u32, delta: i32) -> (i32, u32) {
+    let index = year * 12 + month as i32 - 1 + delta;
+    (index.div_euclid(12), index.rem_euclid(12) as u32 + 1)
+}
+
+/// 本月与上月收入的对比
+enum MonthChange {
+    New,           // 上月为零，无法计算百分比
+    Percent(f64),  // 相对上月的变化百分比
+}
+
+/// 两个月都没有收入时不显示
+fn month_change(current: f64, previous: f64) -> Option<MonthChange> {
+    if previous.abs() < 0.005 {
+        return (current.abs() >= 0.005).then_some(MonthChange::New);
+    }
+    Some(MonthChange::Percent((current - previous) / previous.abs() * 100.0))
+}
+
+/// 将计时秒数按取整方式转换为小时
+fn round_duration(secs: u64, mode: DurationRounding) -> f64 {
+    let hours = secs as f64 / 3600.0;
+    match mode {
+        DurationRounding::Nearest0_1 => (hours * 10.0).round() / 10.0,
+        DurationRounding::Up0_5 => (hours * 2.0).ceil() / 2.0,
+        DurationRounding::Nearest0_5 => (hours * 2.0).round() / 2.0,
+    }
+}
+
+/// 大于 `elapsed` 的最小整数倍间隔
+fn next_threshold(elapsed: Duration, interval: Duration) -> Duration {
+    let n = elapsed.as_secs() / interval.as_secs() + 1;
+    interval * n as u32
+}
+
+/// 发送计时提醒通知（在后台线程中发送，避免阻塞界面）
+fn notify_session(label: &str, elapsed: Duration) {
+    let secs = elapsed.as_secs();
+    let body = format!("{} 已经连续计时 {} 小时 {} 分钟，注意休息", label, secs / 3600, secs % 3600 / 60);
+    std::thread::spawn(move || {
+        let _ = notify_rust::Notification::new()
+            .summary(APP_TITLE)
+            .body(&body)
+            .show();
+    });
+}
+
+/// 某天所在周的第一天和最后一天，week_start 为每周第一天（周一即 ISO 周）
+fn week_bounds(date: NaiveDate, week_start: Weekday) -> (NaiveDate, NaiveDate) {
+    let first = date - chrono::Days::new(u64::from(date.weekday().days_since(week_start)));
+    (first, first + chrono::Days::new(6))
+}
+
+/// 月历表头的星期名，从 week_start 开始
+fn weekday_names(week_start: Weekday) -> [&'static str; 7] {
+    let names = ["一", "二", "三", "四", "五", "六", "日"];
+    let offset = week_start.num_days_from_monday() as usize;
+    std::array::from_fn(|i| names[(i + offset) % 7])
+}
+
+/// 计算截至 today 的连续记账天数（days 为去重后的日期，顺序不限）
+/// 今天还没记录时从昨天往前数，连昨天也没有则为 0；中断后重新从 1 开始
+fn current_streak(days: &[String], today: NaiveDate) -> u32 {
+    let active: HashSet<NaiveDate> = days.iter()
+        .filter_map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .collect();
+    let mut day = if active.contains(&today) {
+        today
+    } else {
+        match today.pred_opt() {
+            Some(d) => d,
+            None => return 0,
+        }
+    };
+    let mut streak = 0;
+    while active.contains(&day) {
+        streak += 1;
+        match day.pred_opt() {
+            Some(d) => day = d,
+            None => break,
+        }
+    }
+    streak
+}
+
+/// 汇总 `today` 所在周的主币种收入，周可以跨月、跨年
+fn calc_week_balance(records: &[Record], today: NaiveDate, week_start: Weekday) -> f64 {
+    let (first, last) = week_bounds(today, week_start);
+    let total = records.iter()
+        .filter(|r| r.currency == DEFAULT_CURRENCY)
+        .filter(|r| {
+            NaiveDate::parse_from_str(&r.date, "%Y-%m-%d")
+                .is_ok_and(|d| d >= first && d <= last)
+        })
+        .map(|r| r.income)
+        .sum();
+    round2(total)
+}
+
+/// 按时间顺序累计每位老板（同币种）的未结金额，records 为从早到晚的未结清记录
+/// 返回记录 id 到截至该记录的累计金额
+fn unsettled_running_totals(records: &[Record]) -> HashMap<i64, f64> {
+    let mut owed: HashMap<(&str, &str), f64> = HashMap::new();
+    records.iter()
+        .map(|r| {
+            let total = owed.entry((r.boss.as_str(), r.currency.as_str())).or_insert(0.0);
+            *total += r.income;
+            (r.id, round2(*total))
+        })
+        .collect()
+}
+
+/// 记录条数最多的前 n 位老板（条数相同时按名字排序）
+fn top_bosses(records: &[Record], n: usize) -> Vec<(String, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for r in records {
+        *counts.entry(r.boss.as_str()).or_insert(0) += 1;
+    }
+    let mut top: Vec<(String, usize)> = counts.into_iter().map(|(b, c)| (b.to_string(), c)).collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    top.truncate(n);
+    top
+}
+
+/// 按收入区间统计单数：返回（区间起点, 终点, 单数），从 0 到最高收入所在区间，中间的空区间也保留
+/// 最多 MAX_HISTOGRAM_BINS 个区间，超出时最后一个区间终点为无穷大，包含其后的全部金额
+/// 只统计主币种，bin_size 不为正数时返回空
+fn income_histogram(records: &[Record], bin_size: f64) -> Vec<(f64, f64, usize)> {
+    if bin_size <= 0.0 {
+        return Vec::new();
+    }
+    let incomes: Vec<f64> = records.iter()
+        .filter(|r| r.currency == DEFAULT_CURRENCY && r.income.is_finite() && r.income >= 0.0)
+        .map(|r| r.income)
+        .collect();
+    let Some(max) = incomes.iter().copied().reduce(f64::max) else {
+        return Vec::new();
+    };
+    // 一笔误输入的大金额（如 1e9）不能撑出上千万个区间，超出上限的都计入最后一个区间
+    let bins = ((max / bin_size) as usize + 1).min(MAX_HISTOGRAM_BINS);
+    let mut counts = vec![0usize; bins];
+    for income in incomes {
+        counts[((income / bin_size) as usize).min(bins - 1)] += 1;
+    }
+    let overflow = max >= bins as f64 * bin_size;
+    counts.into_iter()
+        .enumerate()
+        .map(|(i, c)| {
+            let start = i as f64 * bin_size;
+            let end = if overflow && i == bins - 1 { f64::INFINITY } else { start + bin_size };
+            (start, end, c)
+        })
+        .collect()
+}
+
+/// 绘制收入分布柱状图，悬停显示区间和单数
+fn histogram_chart(ui: &mut egui::Ui, bins: &[(f64, f64, usize)], money: &MoneyFormat, theme: &Theme) {
+    let height = 120.0;
+    let label_height = 16.0;
+    let (rect, response) = ui.allocate_exact_size(Vec2::new(ui.available_width(), height + label_height), egui::Sense::hover());
+    let max_count = bins.iter().map(|(_, _, c)| *c).max().unwrap_or(0).max(1);
+    let slot = rect.width() / bins.len().max(1) as f32;
+    let bar_width = (slot * 0.8).max(1.0);
+    // 区间太多时隔几个标一次刻度，避免文字重叠
+    let label_every = ((40.0 / slot).ceil() as usize).max(1);
+    let painter = ui.painter_at(rect);
+    let hover = response.hover_pos();
+    for (i, (start, end, count)) in bins.iter().enumerate() {
+        let x = rect.left() + slot * i as f32 + (slot - bar_width) / 2.0;
+        let bar_height = height * *count as f32 / max_count as f32;
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(x, rect.top() + height - bar_height),
+            egui::pos2(x + bar_width, rect.top() + height),
+        );
+        let column = egui::Rect::from_min_max(egui::pos2(x, rect.top()), egui::pos2(x + bar_width, rect.top() + height));
+        let hovered = hover.is_some_and(|p| column.contains(p));
+        let fill = if hovered { theme.accent_color } else { theme.green_color };
+        painter.rect_filled(bar, CornerRadius::same(2), fill);
+        if i % label_every == 0 {
+            painter.text(
+                egui::pos2(x, rect.bottom()),
+                egui::Align2::LEFT_BOTTOM,
+                money.amount(*start),
+                FontId::proportional(10.0),
+                theme.text_secondary,
+            );
+        }
+        if hovered {
+            let range = if end.is_finite() {
+                format!("{}–{}", money.amount(*start), money.amount(*end))
+            } else {
+                format!("{} 以上", money.amount(*start))
+            };
+            response.clone().on_hover_text(format!("{}：{} 单", range, count));
+        }
+    }
+}
+
+/// 按结清状态拆分主币种收入，返回（已收, 未收）
+fn settled_split(records: &[Record]) -> (f64, f64) {
+    let (settled, unsettled) = records.iter()
+        .filter(|r| r.currency == DEFAULT_CURRENCY)
+        .fold((0.0, 0.0), |(settled, unsettled), r| {
+            if r.settled {
+                (settled + r.income, unsettled)
+            } else {
+                (settled, unsettled + r.income)
+            }
+        });
+    (round2(settled), round2(unsettled))
+}
+
+/// 按元显示的最大金额（含）的最低值，超过后以“万”为单位
+/// 实际取它与单笔上限中较大的一个：任何合法的单笔记录都按元显示，收入列不会混用单位
+const WAN_THRESHOLD: f64 = 100_000.0;
+/// 以“亿”为单位显示的起点（含），按万显示时舍入后满一亿的金额也算
+const YI_THRESHOLD: f64 = 100_000_000.0;
+
+/// 金额显示格式（币种符号、小数位、是否使用万/亿单位），由设置生成
+#[derive(Clone)]
+struct MoneyFormat {
+    symbol: String,       // 主币种符号，其他币种使用固定符号
+    decimals: usize,      // 小数位数，万/亿单位同样适用
+    chinese_units: bool,  // 大金额以万/亿为单位
+    wan_threshold: f64,   // 超过该值时以万为单位，不低于单笔上限
+}
+
+impl MoneyFormat {
+    fn from_settings(settings: &Settings) -> Self {
+        Self {
+            symbol: settings.currency_symbol.clone(),
+            decimals: usize::from(settings.decimals.min(4)),
+            chinese_units: settings.chinese_units,
+            wan_threshold: settings.max_income.max(WAN_THRESHOLD),
+        }
+    }
+
+    /// 格式化金额的绝对值（不含符号），万/亿同样按设置的小数位数显示
+    /// 两个边界都用舍入后的显示值判断、都包含起点：万从 wan_threshold 之后的第一个显示值开始，
+    /// 亿从 1 亿开始，99,999,999 显示为 1.00亿 而不是 10000.00万
+    fn amount(&self, abs_amount: f64) -> String {
+        let wan_start = self.display_steps(self.wan_threshold, 1.0) + 1.0;
+        let yi_start = self.display_steps(YI_THRESHOLD, 10_000.0);
+        if self.chinese_units && self.display_steps(abs_amount, 10_000.0) >= yi_start {
+            format!("{:.*}亿", self.decimals, abs_amount / YI_THRESHOLD)
+        } else if self.chinese_units && self.display_steps(abs_amount, 1.0) >= wan_start {
+            format!("{:.*}万", self.decimals, abs_amount / 10_000.0)
+        } else {
+            self.group_thousands(abs_amount)
+        }
+    }
+
+    /// 以 unit（元、万）为单位并按小数位数舍入后，金额包含多少个最小显示单位（整数）
+    /// 按整数比较单位边界，不受浮点误差影响
+    fn display_steps(&self, abs_amount: f64, unit: f64) -> f64 {
+        (abs_amount / unit * 10f64.powi(self.decimals as i32)).round()
+    }
+
+    /// 按设置的小数位数并按千分位加逗号：8500.0 -> "8,500.00"
+    fn group_thousands(&self, abs_amount: f64) -> String {
+        let formatted = format!("{:.*}", self.decimals, abs_amount);
+        let (int_part, frac_part) = match formatted.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+            None => (formatted.as_str(), None),
+        };
+        let mut grouped = String::with_capacity(formatted.len() + int_part.len() / 3);
+        for (i, ch) in int_part.chars().enumerate() {
+            if i > 0 && (int_part.len() - i) % 3 == 0 {
+                grouped.push(',');
+            }
+            grouped.push(ch);
+        }
+        match frac_part {
+            Some(frac) => format!("{}.{}", grouped, frac),
+            None => grouped,
+        }
+    }
+
+    /// 主币种金额，带符号
+    /// 累加误差产生的 -0.0000001 按 0 处理，不显示负号
+    fn money(&self, amount: f64) -> String {
+        let sign = if round2(amount) < 0.0 { "-" } else { "" };
+        format!("{}{}{}", sign, self.symbol, self.amount(amount.abs()))
+    }
+
+    /// 主币种收入（带+号，不带币种符号）
+    fn income(&self, amount: f64) -> String {
+        format!("+{}", self.amount(amount.abs()))
+    }
+
+    /// 按指定币种格式化金额，主币种与 money 一致
+    fn money_in(&self, amount: f64, currency: &str) -> String {
+        if currency == DEFAULT_CURRENCY {
+            return self.money(amount);
+        }
+        let sign = if round2(amount) < 0.0 { "-" } else { "" };
+        format!("{}{}{}", sign, currency_symbol(currency), self.amount(amount.abs()))
+    }
+
+    /// 按指定币种格式化收入：主币种不带符号（与 income 一致），其他币种带符号
+    fn income_in(&self, amount: f64, currency: &str) -> String {
+        if currency == DEFAULT_CURRENCY {
+            self.income(amount)
+        } else {
+            format!("+{}{}", currency_symbol(currency), self.amount(amount.abs()))
+        }
+    }
+}
+
+/// 按显示格式重新渲染存储的 YYYY-MM-DD 日期，解析失败时原样返回
+fn format_display_date(iso: &str, fmt: DateDisplay) -> String {
+    let Ok(date) = NaiveDate::parse_from_str(iso, "%Y-%m-%d") else {
+        return iso.to_string();
+    };
+    match fmt {
+        DateDisplay::Iso => iso.to_string(),
+        DateDisplay::Slash => date.format("%m/%d").to_string(),
+        DateDisplay::Chinese => format!("{}月{}日", date.month(), date.day()),
+    }
+}
+
+/// 把金额四舍五入到 step 的整数倍（如 step 为 50 时 275 → 300），step 不为正数时原样返回
+fn round_to(amount: f64, step: f64) -> f64 {
+    if step > 0.0 {
+        (amount / step).round() * step
+    } else {
+        amount
+    }
+}
+
+/// 所选记录的合计：主币种在前，其他币种分别相加后用“+”连接，没有选中时为 0
+fn selected_total_text(records: &[&Record], money: &MoneyFormat) -> String {
+    let mut totals: Vec<(&str, f64)> = Vec::new();
+    for r in records {
+        match totals.iter_mut().find(|(c, _)| *c == r.currency) {
+            Some((_, sum)) => *sum += r.income,
+            None => totals.push((&r.currency, r.income)),
+        }
+    }
+    if totals.is_empty() {
+        return money.money(0.0);
+    }
+    totals.sort_by_key(|(c, _)| *c != DEFAULT_CURRENCY);
+    totals.iter()
+        .map(|(c, sum)| money.money_in(round2(*sum), c))
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// 在输入框下方画一行小字（错误提示或预览），不占布局高度，避免输入行跳动
+fn field_hint(ui: &egui::Ui, field: egui::Rect, text: &str, color: Color32) {
+    ui.painter().text(
+        field.left_bottom() + Vec2::new(2.0, 2.0),
+        egui::Align2::LEFT_TOP,
+        text,
+        FontId::proportional(11.0),
+        color,
+    );
+}
+
+/// 文件大小显示为 B / KB / MB
+fn format_file_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// 在系统文件管理器中打开目录
+fn open_in_file_manager(dir: &std::path::Path) -> std::io::Result<()> {
+    let program = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    std::process::Command::new(program).arg(dir).spawn().map(|_| ())
+}
+
+/// 列分隔线的横向位置：每两列之间间距的中点，相对行内容左侧
+fn column_divider_offsets(widths: &[f32], spacing: f32) -> Vec<f32> {
+    let mut x = 0.0;
+    let mut offsets = Vec::with_capacity(widths.len().saturating_sub(1));
+    for w in &widths[..widths.len().saturating_sub(1)] {
+        x += w;
+        offsets.push(x + spacing / 2.0);
+        x += spacing;
+    }
+    offsets
+}
+
+/// 解析录入时间：YYYY-MM-DD HH:MM:SS，秒可省略
+fn parse_datetime(text: &str) -> Option<chrono::NaiveDateTime> {
+    let text = text.trim();
+    chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M"))
+        .ok()
+}
+
+/// 常用金额显示为空格分隔的文本
+fn format_presets(presets: &[f64]) -> String {
+    presets.iter().map(f64::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// 解析收入金额，必须为正的有限数
+fn parse_income(text: &str) -> Option<f64> {
+    parse_amount(text).filter(|v| *v > 0.0)
+}
+
+/// 单条记录的时薪，时长缺失或为零时为 None
+fn hourly_rate(record: &Record) -> Option<f64> {
+    record.duration
+        .filter(|d| *d > 0.0)
+        .map(|d| record.income / d)
+        .filter(|rate| rate.is_finite())
+}
+
+/// 格式化时长：四舍五入到一位小数，整数小时不带小数（2h），否则保留一位（1.5h）
+/// 没有时长、非正数或非有限数（旧数据可能有 NaN）显示为 "-"，不足 0.05h 的显示为 "<0.1h"
+fn format_duration(hours: Option<f64>) -> String {
+    let Some(hours) = hours.filter(|h| h.is_finite() && *h > 0.0) else {
+        return "-".to_string();
+    };
+    let rounded = (hours * 10.0).round() / 10.0;
+    if rounded == 0.0 {
+        "<0.1h".to_string()
+    } else if rounded.fract() == 0.0 {
+        format!("{}h", rounded)
+    } else {
+        format!("{:.1}h", rounded)
+    }
+}
+
+/// 把老板名等用户输入转换为合法的文件名，替换 Windows 文件名中不允许的字符
+fn sanitize_file_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if matches!(c, '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control() { '_' } else { c })
+        .collect()
+}
+
+/// 将记录格式化为便于发给老板的一行文字：2024-06-01 王者荣耀 2h ¥200.00
+fn record_to_line(r: &Record, money: &MoneyFormat) -> String {
+    let mut parts = vec![r.date.clone()];
+    if let Some(game) = r.game.as_deref().filter(|g| !g.is_empty()) {
+        parts.push(game.to_string());
+    }
+    if r.duration.is_some_and(|d| d.is_finite() && d > 0.0) {
+        parts.push(format_duration(r.duration));
+    }
+    parts.push(money.money_in(r.income, &r.currency));
+    parts.join(" ")
+}
+
+/// 可选币种：代码与符号，第一项为主币种
+const CURRENCIES: [(&str, &str); 4] = [("CNY", "¥"), ("USD", "$"), ("EUR", "€"), ("HKD", "HK$")];
+
+/// 币种代码对应的符号，未知币种直接显示代码
+fn currency_symbol(code: &str) -> &str {
+    CURRENCIES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map(|(_, sym)| *sym)
+        .unwrap_or(code)
+}
+
+/// 按币种分组的金额，每行一种币种，主币种在前；没有金额时为 "-"
+fn currency_breakdown(totals: &HashMap<String, f64>, money: &MoneyFormat) -> String {
+    if totals.is_empty() {
+        return "-".to_string();
+    }
+    let mut lines: Vec<(&String, &f64)> = totals.iter().collect();
+    lines.sort_by_key(|(c, _)| (c.as_str() != DEFAULT_CURRENCY, c.as_str()));
+    lines.iter()
+        .map(|(c, v)| format!("{}  {}", c, money.money_in(**v, c)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 绘制月历热力图：每天按收入在卡片色与绿色之间插值着色，无记录的日子保持卡片色
+/// 悬停显示当天单数、时长和收入，返回被点击的日期（几号）
+fn calendar_heatmap(
+    ui: &mut egui::Ui,
+    (year, month): (i32, u32),
+    totals: &[DayStat],
+    selected_day: Option<u32>,
+    week_start: Weekday,
+    money: &MoneyFormat,
+    theme: &Theme,
+) -> Option<u32> {
+    let cell = Vec2::new(40.0, 28.0);
+    let max_income = totals.iter().map(|s| s.income).fold(0.0, f64::max);
+    let first_weekday = NaiveDate::from_ymd_opt(year, month, 1)
+        .map(|d| d.weekday().days_since(week_start))
+        .unwrap_or(0);
+    let mut clicked = None;
+
+    ui.spacing_mut().item_spacing = Vec2::new(4.0, 4.0);
+    ui.horizontal(|ui| {
+        for name in weekday_names(week_start) {
+            ui.add_sized(cell, egui::Label::new(RichText::new(name).size(12.0).color(theme.text_secondary)));
+        }
+    });
+
+    // 当月天数：下月 1 日的前一天
+    let (next_year, next_month) = shift_month(year, month, 1);
+    let days = NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|d| d.pred_opt())
+        .map_or(0, |d| d.day());
+    let mut day = 1;
+    while day <= days {
+        ui.horizontal(|ui| {
+            for col in 0..7u32 {
+                if (day == 1 && col < first_weekday) || day > days {
+                    ui.add_space(cell.x);
+                    continue;
+                }
+                let stat = totals.iter().find(|s| s.day == day);
+                let income = stat.map(|s| s.income).unwrap_or(0.0);
+                let fill = if income > 0.0 && max_income > 0.0 {
+                    // 最低保留 0.2 的强度，避免小额收入与无记录难以区分
+                    let t = 0.2 + 0.8 * (income / max_income) as f32;
+                    theme.card_color.lerp_to_gamma(theme.green_color, t)
+                } else {
+                    theme.card_color
+                };
+                let stroke = if selected_day == Some(day) {
+                    Stroke::new(1.5, theme.accent_color)
+                } else {
+                    Stroke::new(1.0, theme.border_color)
+                };
+                let btn = egui::Button::new(RichText::new(day.to_string()).size(12.0).color(theme.text_primary))
+                    .fill(fill)
+                    .stroke(stroke)
+                    .corner_radius(CornerRadius::same(4));
+                let response = ui.add_sized(cell, btn).on_hover_ui(|ui| {
+                    let text = match stat {
+                        Some(s) => format!("{} 单 · {} · {}", s.count, format_duration(Some(s.hours)), money.money(s.income)),
+                        None => "无记录".to_string(),
+                    };
+                    ui.label(RichText::new(text).size(13.0));
+                });
+                if response.clicked() {
+                    clicked = Some(day);
+                }
+                day += 1;
+            }
+        });
+    }
+    clicked
+}
+
+/// 日期选择框：显示当前日期，点击弹出月历，点某一天后关闭；返回日期是否改变
+/// 月历只列出当月真实存在的日期，不会选出 2 月 30 日这样的日子
+fn date_picker(ui: &mut egui::Ui, id: &str, date: &mut NaiveDate, size: Vec2, border: Color32, week_start: Weekday, theme: &Theme) -> bool {
+    let weekday = ["一", "二", "三", "四", "五", "六", "日"][date.weekday().num_days_from_monday() as usize];
+    let text = RichText::new(format!("{}  周{}", date.format("%Y-%m-%d"), weekday)).size(14.0);
+    calendar_popup(ui, id, date, calendar_button(text, border, theme), size, week_start, theme)
+}
+
+/// 月份选择框：显示年月，弹出与日期选择框相同的月历，点其中任意一天即选中那个月
+fn month_picker(ui: &mut egui::Ui, id: &str, year: &mut i32, month: &mut u32, size: Vec2, week_start: Weekday, theme: &Theme) -> bool {
+    let Some(mut date) = NaiveDate::from_ymd_opt(*year, *month, 1) else {
+        return false;
+    };
+    let text = RichText::new(format!("{}-{:02}", year, month)).size(13.0);
+    if !calendar_popup(ui, id, &mut date, calendar_button(text, theme.border_color, theme), size, week_start, theme) {
+        return false;
+    }
+    let changed = (date.year(), date.month()) != (*year, *month);
+    (*year, *month) = (date.year(), date.month());
+    changed
+}
+
+/// 日期和月份选择框的按钮：与输入框同底色，文字用主文字色保证深色主题下可读
+fn calendar_button(text: RichText, border: Color32, theme: &Theme) -> egui::Button<'static> {
+    egui::Button::new(text.color(theme.text_primary))
+        .fill(theme.input_bg)
+        .stroke(Stroke::new(1.0, border))
+        .corner_radius(CornerRadius::same(8))
+}
+
+/// 日期和月份选择框共用的月历弹窗
+fn calendar_popup(ui: &mut egui::Ui, id: &str, date: &mut NaiveDate, button: egui::Button, size: Vec2, week_start: Weekday, theme: &Theme) -> bool {
+    let popup_id = ui.make_persistent_id(id);
+    let view_id = popup_id.with("view");  // 月历当前显示的年月
+    let response = ui.add_sized(size, button);
+    if response.clicked() {
+        ui.data_mut(|d| d.insert_temp(view_id, (date.year(), date.month())));
+        ui.memory_mut(|m| m.toggle_popup(popup_id));
+    }
+
+    let mut picked = None;
+    egui::popup_below_widget(ui, popup_id, &response, egui::PopupCloseBehavior::CloseOnClickOutside, |ui| {
+        let (mut year, mut month) = ui.data(|d| d.get_temp(view_id)).unwrap_or((date.year(), date.month()));
+        let cell = Vec2::new(32.0, 24.0);
+        ui.spacing_mut().item_spacing = Vec2::new(2.0, 2.0);
+        ui.horizontal(|ui| {
+            if ui.small_button("◀").clicked() {
+                (year, month) = shift_month(year, month, -1);
+            }
+            ui.add_sized([cell.x * 7.0 - 60.0, cell.y], egui::Label::new(
+                RichText::new(format!("{}年{}月", year, month)).size(13.0).color(theme.text_primary)
+            ));
+            if ui.small_button("▶").clicked() {
+                (year, month) = shift_month(year, month, 1);
+            }
+        });
+        ui.horizontal(|ui| {
+            for name in weekday_names(week_start) {
+                ui.add_sized(cell, egui::Label::new(RichText::new(name).size(12.0).color(theme.text_secondary)));
+            }
+        });
+        let first_weekday = NaiveDate::from_ymd_opt(year, month, 1)
+            .map(|d| d.weekday().days_since(week_start))
+            .unwrap_or(0);
+        let (next_year, next_month) = shift_month(year, month, 1);
+        let days = NaiveDate::from_ymd_opt(next_year, next_month, 1)
+            .and_then(|d| d.pred_opt())
+            .map_or(0, |d| d.day());
+        let mut day = 1;
+        while day <= days {
+            ui.horizontal(|ui| {
+                for col in 0..7u32 {
+                    if (day == 1 && col < first_weekday) || day > days {
+                        ui.add_space(cell.x + 2.0);
+                        continue;
+                    }
+                    let Some(this) = NaiveDate::from_ymd_opt(year, month, day) else {
+                        day += 1;
+                        continue;
+                    };
+                    let selected = this == *date;
+                    let (fill, text) = if selected {
+                        (theme.accent_color, Color32::WHITE)
+                    } else {
+                        (Color32::TRANSPARENT, theme.text_primary)
+                    };
+                    let btn = egui::Button::new(RichText::new(day.to_string()).size(12.0).color(text))
+                        .fill(fill)
+                        .stroke(Stroke::NONE)
+                        .corner_radius(CornerRadius::same(4));
+                    if ui.add_sized(cell, btn).clicked() {
+                        picked = Some(this);
+                    }
+                    day += 1;
+                }
+            });
+        }
+        ui.data_mut(|d| d.insert_temp(view_id, (year, month)));
+    });
+
+    match picked {
+        Some(new_date) => {
+            ui.memory_mut(|m| m.close_popup());
+            let changed = new_date != *date;
+            *date = new_date;
+            changed
+        }
+        None => false,
+    }
+}
+
+/// 绘制标签胶囊，返回点击响应
+fn tag_chip(ui: &mut egui::Ui, tag: &str, selected: bool, theme: &Theme) -> egui::Response {
+    let (fill, text) = if selected {
+        (theme.accent_color, Color32::WHITE)
+    } else {
+        (theme.active_bg, theme.accent_color)
+    };
+    ui.add(
+        egui::Button::new(RichText::new(tag).size(11.0).color(text))
+            .fill(fill)
+            .stroke(Stroke::NONE)
+            .corner_radius(CornerRadius::same(8))
+            .min_size(Vec2::new(0.0, 18.0)),
+    )
+}
+
+/// 输入框标题旁的“固定”开关，点击切换，返回是否改变
+fn pin_toggle(ui: &mut egui::Ui, pinned: &mut bool, theme: &Theme) -> bool {
+    let clicked = tag_chip(ui, "固定", *pinned, theme)
+        .on_hover_text("添加后保留该字段")
+        .clicked();
+    if clicked {
+        *pinned = !*pinned;
+    }
+    clicked
+}
+
+/// 一条联想结果
+struct Suggestion {
+    text: String,
+    matched: Vec<usize>,  // 与输入匹配的字符下标（按字符计），弹窗中高亮显示
+}
+
+/// 按输入模糊过滤联想列表（不区分大小写，字符按顺序出现即可匹配，最多 limit 项）
+/// 以输入开头的排在最前，其余按匹配得分从高到低；输入为空时按列表原顺序取前 limit 项
+fn filter_suggestions(list: &[String], input: &str, limit: usize) -> Vec<Suggestion> {
+    let input = normalize_name(input);
+    let input = input.as_str();
+    if input.is_empty() {
+        return list.iter().take(limit).map(|item| Suggestion { text: item.clone(), matched: Vec::new() }).collect();
+    }
+    let matcher = SkimMatcherV2::default().ignore_case();
+    let input_lower = input.to_lowercase();
+    let mut scored: Vec<(bool, i64, Suggestion)> = list.iter()
+        .filter_map(|item| {
+            let (score, matched) = matcher.fuzzy_indices(item, input)?;
+            Some((item.to_lowercase().starts_with(&input_lower), score, Suggestion { text: item.clone(), matched }))
+        })
+        .collect();
+    // 稳定排序，同分时保持列表原顺序
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)));
+    scored.into_iter().take(limit).map(|(_, _, suggestion)| suggestion).collect()
+}
+
+/// 处理联想列表的键盘操作：上下键移动高亮（首尾循环），回车确认
+/// 返回回车确认的下标；按键在输入框处理前被消费，避免回车触发添加
+fn handle_suggestion_keys(ctx: &egui::Context, len: usize, index: &mut Option<usize>) -> Option<usize> {
+    if len == 0 {
+        return None;
+    }
+    let (down, up) = ctx.input_mut(|i| (
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+        i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+    ));
+    if down {
+        *index = Some(index.map_or(0, |i| (i + 1) % len));
+    } else if up {
+        *index = Some(index.map_or(len - 1, |i| (i + len - 1) % len));
+    }
+    match *index {
+        Some(i) if i < len && ctx.input_mut(|inp| inp.consume_key(egui::Modifiers::NONE, egui::Key::Enter)) => Some(i),
+        _ => None,
+    }
+}
+
+/// 在输入框下方绘制联想弹窗（下方放不下时翻到上方），高亮项使用强调色背景，返回被点击的建议
+fn suggestion_popup(
+    ui: &egui::Ui,
+    id: &str,
+    anchor: egui::Rect,
+    suggestions: &[Suggestion],
+    noted: &HashSet<String>,
+    highlighted: Option<usize>,
+    theme: &Theme,
+) -> Option<String> {
+    let width = anchor.width();
+    let mut clicked = None;
+    // 预估弹窗高度：每项 28px 加项间距，再加内边距和边框（项数不超过联想数量设置）
+    let rows = suggestions.len() as f32;
+    let height = rows * 28.0 + (rows - 1.0).max(0.0) * ui.spacing().item_spacing.y + 10.0;
+    let screen = ui.ctx().screen_rect();
+    let room_below = screen.bottom() - anchor.bottom() - 4.0;
+    let room_above = anchor.top() - screen.top() - 4.0;
+    let (pos, pivot) = if height > room_below && room_above > room_below {
+        (anchor.left_top() - Vec2::new(0.0, 4.0), egui::Align2::LEFT_BOTTOM)
+    } else {
+        (anchor.left_bottom() + Vec2::new(0.0, 4.0), egui::Align2::LEFT_TOP)
+    };
+    egui::Area::new(egui::Id::new(id))
+        .order(egui::Order::Foreground)
+        .pivot(pivot)
+        .fixed_pos(pos)
+        .show(ui.ctx(), |ui| {
+            egui::Frame::default()
+                .fill(theme.popup_bg)
+                .corner_radius(CornerRadius::same(6))
+                .stroke(Stroke::new(1.0, theme.popup_border))
+                .shadow(egui::epaint::Shadow { offset: [0, 2], blur: 8, spread: 0, color: Color32::from_black_alpha(60) })
+                .inner_margin(4)
+                .show(ui, |ui| {
+                    ui.set_width(width - 8.0);
+                    for (idx, suggestion) in suggestions.iter().enumerate() {
+                        let item = &suggestion.text;
+                        let is_highlighted = highlighted == Some(idx);
+                        let (fill, text_color) = if is_highlighted {
+                            (theme.accent_color, Color32::WHITE)
+                        } else {
+                            (Color32::TRANSPARENT, theme.text_primary)
+                        };
+                        // 匹配的字符用强调色（高亮行上加下划线），连续同类字符合并为一段
+                        let mut job = egui::text::LayoutJob::default();
+                        let chars: Vec<char> = item.chars().collect();
+                        let mut start = 0;
+                        while start < chars.len() {
+                            let is_match = suggestion.matched.contains(&start);
+                            let mut end = start + 1;
+                            while end < chars.len() && suggestion.matched.contains(&end) == is_match {
+                                end += 1;
+                            }
+                            let segment: String = chars[start..end].iter().collect();
+                            let mut text = RichText::new(segment).size(14.0).color(text_color);
+                            if is_match {
+                                text = if is_highlighted { text.underline() } else { text.color(theme.accent_color) };
+                            }
+                            text.append_to(&mut job, ui.style(), egui::FontSelection::Default, egui::Align::Center);
+                            start = end;
+                        }
+                        // 有备注的项在后面加一个小标记
+                        if noted.contains(item) {
+                            let mark_color = if is_highlighted { Color32::WHITE } else { theme.accent_color };
+                            RichText::new("  备注").size(11.0).color(mark_color)
+                                .append_to(&mut job, ui.style(), egui::FontSelection::Default, egui::Align::Center);
+                        }
+                        let btn = egui::Button::new(job)
+                            .fill(fill).stroke(Stroke::NONE).corner_radius(CornerRadius::same(4));
+                        if ui.add_sized([width - 16.0, 28.0], btn).clicked() {
+                            clicked = Some(item.clone());
+                        }
+                    }
+                });
+        });
+    clicked
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // 消息计时器
+        if self.message_timer > 0.0 {
+            self.message_timer -= ctx.input(|i| i.unstable_dt);
+            if self.message_timer <= 0.0 {
+                self.message.clear();
+            }
+            ctx.request_repaint();
+        }
+
+        // 记录窗口尺寸和位置（最小化时不记录），关闭时写入设置
+        ctx.input(|i| {
+            let viewport = i.viewport();
+            if viewport.minimized != Some(true) {
+                if let Some(rect) = viewport.inner_rect {
+                    self.settings.window_size = Some([rect.width(), rect.height()]);
+                }
+                if let Some(rect) = viewport.outer_rect {
+                    self.settings.window_pos = Some([rect.min.x, rect.min.y]);
+                }
+            }
+        });
+        #[cfg(windows)]
+        self.sync_tray(ctx);
+        self.sync_hotkey(ctx);
+        if ctx.input(|i| i.viewport().close_requested()) {
+            // 开启了关闭到托盘：取消关闭，只隐藏窗口，计时器继续运行
+            #[cfg(windows)]
+            if self.tray.as_ref().is_some_and(|t| !t.quit_requested()) {
+                ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                ctx.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+            }
+            self.save_timers();
+        }
+
+        self.save_chart_screenshot(ctx);
+
+        // 重复确认超时后自动失效
+        if let Some((_, since)) = &self.duplicate_pending {
+            if since.elapsed() >= DUPLICATE_CONFIRM_WINDOW {
+                self.duplicate_pending = None;
+            } else {
+                ctx.request_repaint_after(DUPLICATE_CONFIRM_WINDOW - since.elapsed());
+            }
+        }
+
+        self.check_day_rollover(ctx);
+
+        // 计时器运行时持续刷新
+        self.skip_suspended_time();
+        if self.any_timer_running() {
+            self.check_reminder();
+            ctx.request_repaint();
+        }
+        self.sync_title(ctx);
+
+        // 加载主题和布局配置
+        let theme = Theme::from_settings(&self.settings, ctx);
+        let money = self.money.clone();
+
+        // 锁定时只显示解锁界面，隐藏账本内容
+        if !self.locked {
+            self.check_auto_lock(ctx);
+        }
+        if self.locked {
+            let mut submit = false;
+            egui::CentralPanel::default()
+                .frame(egui::Frame::default().fill(theme.bg_color))
+                .show(ctx, |ui| {
+                    ui.add_space(ui.available_height() * 0.35);
+                    ui.vertical_centered(|ui| {
+                        let hint = if self.db_encrypted { "已锁定，请输入数据库密码" } else { "已锁定，请输入 PIN" };
+                        ui.label(RichText::new(hint).size(16.0).color(theme.text_primary));
+                        ui.add_space(12.0);
+                        let input = ui.add(egui::TextEdit::singleline(&mut self.unlock_input)
+                            .password(true)
+                            .desired_width(220.0));
+                        input.request_focus();
+                        submit = input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if self.unlock_error {
+                            ui.add_space(8.0);
+                            ui.label(RichText::new("密码错误").size(14.0).color(theme.danger_color));
+                        }
+                        ui.add_space(16.0);
+                        let unlock = egui::Button::new(RichText::new("解锁").size(14.0).color(Color32::WHITE))
+                            .fill(theme.accent_color)
+                            .corner_radius(CornerRadius::same(6))
+                            .min_size(Vec2::new(80.0, 32.0));
+                        submit |= ui.add(unlock).clicked();
+                    });
+                });
+            if submit {
+                self.try_unlock(ctx);
+            }
+            return;
+        }
+        let mut layout = LayoutConfig::for_density(self.settings.density);
+        layout.content_width = if self.settings.auto_width { *CONTENT_WIDTH_RANGE.end() } else { self.settings.content_width };
+
+        // 解构主题颜色以保持代码兼容性
+        let bg_color = theme.bg_color;
+        let card_color = theme.card_color;
+        let input_bg = theme.input_bg;
+        let accent_color = theme.accent_color;
+        let green_color = theme.green_color;
+        let text_primary = theme.text_primary;
+        let text_secondary = theme.text_secondary;
+        let danger_color = theme.danger_color;
+
+        // ===== 底部计时器栏（固定在底部）=====
+        egui::TopBottomPanel::bottom("timer_panel")
+            .frame(egui::Frame::default().fill(bg_color).inner_margin(egui::Margin {
+                left: layout.panel_margin as i8,
+                right: layout.panel_margin as i8,
+                top: 8,
+                bottom: 16
+            }))
+            .show(ctx, |ui| {
+                // 与内容区域等宽居中（两个面板边距相同，按同样规则计算宽度保证对齐）
+                let content_width = layout.content_width.min(ui.available_width());
+                let available = ui.available_width();
+                let side_margin = ((available - content_width) / 2.0).max(0.0);
+
+                ui.horizontal(|ui| {
+                    ui.add_space(side_margin);
+                    ui.vertical(|ui| {
+                        ui.set_width(content_width);
+                        // 获取实际卡片宽度，与表格对齐
+                        let timer_card_width = ui.available_width();
+                        ui.vertical(|ui| {
+                            ui.set_width(timer_card_width);
+                            egui::Frame::default()
+                                .fill(Color32::TRANSPARENT)  // 透明背景
+                                .stroke(Stroke::new(1.0, accent_color))  // 蓝色细边框
+                                .corner_radius(CornerRadius::same(layout.card_rounding as u8))  // 使用统一圆角
+                                .inner_margin(egui::Margin::symmetric(layout.card_inner_margin as i8, 16))  // 减小上下边距控制高度
+                                .show(ui, |ui| {
+                                    let mut timer_action: Option<(usize, TimerAction)> = None;
+                                    let mut label_changed = false;
+                                    let timer_count = self.timers.len();
+                                    for idx in 0..timer_count {
+                                        if idx > 0 {
+                                            ui.add_space(8.0);
+                                        }
+                                        ui.horizontal(|ui| {
+                                            // 计算当前显示时间
+                                            let timer = &self.timers[idx];
+                                            let elapsed = timer.elapsed();
+
+                                            let total_secs = elapsed.as_secs();
+                                            let hours = total_secs / 3600;
+                                            let minutes = (total_secs % 3600) / 60;
+                                            let seconds = total_secs % 60;
+                                            let time_str = format!("{:02}:{:02}:{:02}", hours, minutes, seconds);
+
+                                            // 判断计时器状态
+                                            let is_initial = timer.is_initial();
+                                            let is_running = timer.is_running();
+                                            let is_paused = timer.is_paused();
+                                            let is_ended = timer.is_ended();
+
+                                            // 左侧：计时器名称（可直接修改）
+                                            let label_response = ui.add(egui::TextEdit::singleline(&mut self.timers[idx].label)
+                                                .desired_width(64.0)
+                                                .frame(false)
+                                                .text_color(text_secondary)
+                                                .font(FontId::proportional(14.0)));
+                                            label_changed |= label_response.lost_focus();
+                                            ui.add_space(8.0);
+
+                                            // 时间显示（放大字体，更突出）
+                                            let time_color = if is_running {
+                                                accent_color
+                                            } else if is_paused {
+                                                theme.warning_color
+                                            } else if is_ended {
+                                                text_primary
+                                            } else {
+                                                text_secondary
+                                            };
+                                            ui.label(RichText::new(time_str)
+                                                .font(FontId::monospace(32.0))  // 从24放大到32
+                                                .color(time_color));
+
+                                            ui.add_space(24.0);
+
+                                            // 按钮区域
+                                            let btn_height = 30.0;
+                                            let btn_width = 56.0;
+
+                                            // 开始按钮（仅初始状态可用）
+                                            if is_initial {
+                                                let start_btn = egui::Button::new(RichText::new("开始").size(13.0).color(Color32::WHITE))
+                                                    .fill(green_color)
+                                                    .corner_radius(CornerRadius::same(6));
+                                                if ui.add_sized([btn_width, btn_height], start_btn).clicked() {
+                                                    timer_action = Some((idx, TimerAction::Start));
+                                                }
+                                            } else {
+                                                let disabled_btn = egui::Button::new(RichText::new("开始").size(13.0).color(theme.disabled_text))
+                                                    .fill(theme.disabled_bg)
+                                                    .corner_radius(CornerRadius::same(6));
+                                                ui.add_sized([btn_width, btn_height], disabled_btn);
+                                            }
+
+                                            ui.add_space(12.0);  // 增加按钮间距
+
+                                            // 暂停/继续按钮（运行中或暂停中可用）
+                                            if is_running {
+                                                let pause_btn = egui::Button::new(RichText::new("暂停").size(13.0).color(Color32::WHITE))
+                                                    .fill(theme.warning_color)
+                                                    .corner_radius(CornerRadius::same(6));
+                                                if ui.add_sized([btn_width, btn_height], pause_btn).clicked() {
+                                                    timer_action = Some((idx, TimerAction::Pause));
+                                                }
+                                            } else if is_paused {
+                                                let resume_btn = egui::Button::new(RichText::new("继续").size(13.0).color(Color32::WHITE))
+                                                    .fill(accent_color)
+                                                    .corner_radius(CornerRadius::same(6));
+                                                if ui.add_sized([btn_width, btn_height], resume_btn).clicked() {
+                                                    timer_action = Some((idx, TimerAction::Resume));
+                                                }
+                                            } else {
+                                                let disabled_btn = egui::Button::new(RichText::new("暂停").size(13.0).color(theme.disabled_text))
+                                                    .fill(theme.disabled_bg)
+                                                    .corner_radius(CornerRadius::same(6));
+                                                ui.add_sized([btn_width, btn_height], disabled_btn);
+                                            }
+
+                                            ui.add_space(12.0);  // 增加按钮间距
+
+                                            // 结束按钮（运行中或暂停中可用，结束后禁用）
+                                            if is_running || is_paused {
+                                                let end_btn = egui::Button::new(RichText::new("结束").size(13.0).color(danger_color))
+                                                    .fill(Color32::TRANSPARENT)
+                                                    .stroke(Stroke::new(1.0, danger_color))
+                                                    .corner_radius(CornerRadius::same(6));
+                                                if ui.add_sized([btn_width, btn_height], end_btn).clicked() {
+                                                    timer_action = Some((idx, TimerAction::End));
+                                                }
+                                            } else {
+                                                let disabled_btn = egui::Button::new(RichText::new("结束").size(13.0).color(theme.disabled_text))
+                                                    .fill(Color32::TRANSPARENT)
+                                                    .stroke(Stroke::new(1.0, theme.border_color))
+                                                    .corner_radius(CornerRadius::same(6));
+                                                ui.add_sized([btn_width, btn_height], disabled_btn);
+                                            }
+
+                                            ui.add_space(12.0);  // 增加按钮间距
+
+                                            // 重置按钮（仅结束后可用）
+                                            if is_ended {
+                                                let reset_btn = egui::Button::new(RichText::new("重置").size(13.0).color(text_secondary))
+                                                    .fill(input_bg)
+                                                    .corner_radius(CornerRadius::same(6));
+                                                if ui.add_sized([btn_width, btn_height], reset_btn).clicked() {
+                                                    timer_action = Some((idx, TimerAction::Reset));
+                                                }
+                                            } else {
+                                                let disabled_btn = egui::Button::new(RichText::new("重置").size(13.0).color(theme.disabled_text))
+                                                    .fill(theme.disabled_bg)
+                                                    .corner_radius(CornerRadius::same(6));
+                                                ui.add_sized([btn_width, btn_height], disabled_btn);
+                                            }
+
+                                            ui.add_space(12.0);
+
+                                            // 填入时长（有计时结果时可用，填入这个计时器的时长）
+                                            let fill_btn = egui::Button::new(RichText::new("填入时长").size(13.0).color(accent_color))
+                                                .fill(Color32::TRANSPARENT)
+                                                .stroke(Stroke::new(1.0, accent_color))
+                                                .corner_radius(CornerRadius::same(6));
+                                            if ui.add_enabled(!is_initial, fill_btn).clicked() {
+                                                timer_action = Some((idx, TimerAction::Fill));
+                                            }
+
+                                            // 第一行可以添加计时器，其余行可以移除（运行中不可移除）
+                                            ui.add_space(8.0);
+                                            if idx == 0 {
+                                                if ui.add_enabled(timer_count < MAX_TIMERS, egui::Button::new("＋").small())
+                                                    .on_hover_text("再开一个计时器")
+                                                    .clicked()
+                                                {
+                                                    timer_action = Some((idx, TimerAction::Add));
+                                                }
+                                            } else if ui.add_enabled(!is_running, egui::Button::new("✕").small())
+                                                .on_hover_text("移除计时器")
+                                                .clicked()
+                                            {
+                                                timer_action = Some((idx, TimerAction::Remove));
+                                            }
+
+                                            // 统计信息只显示在第一行
+                                            if idx > 0 {
+                                                return;
+                                            }
+
+                                            // 选中日期的统计数据（数据变化时已刷新）
+                                            let (day_count, day_hours, day_income) = self.day_stats;
+                                            let is_today = self.input_date == self.today();
+                                            let day_label = if is_today { "今日收入" } else { &format!("{}月{}日", self.input_date.month(), self.input_date.day()) };
+
+                                            // 今日统计面板 - 使用右对齐布局
+                                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                                // 注意：right_to_left 布局从右到左添加元素，所以顺序要反过来
+                                                ui.label(RichText::new(format!("{:.1}h", day_hours))
+                                                    .size(14.0)
+                                                    .color(text_primary));
+
+                                                ui.label(RichText::new("·").size(14.0).color(text_primary));
+
+                                                ui.label(RichText::new(format!("{}单", day_count))
+                                                    .size(14.0)
+                                                    .color(text_primary));
+
+                                                ui.label(RichText::new("·").size(14.0).color(text_primary));
+
+                                                ui.label(RichText::new(money.money(day_income))
+                                                    .size(14.0)
+                                                    .color(text_primary));
+
+                                                ui.add_space(8.0);
+
+                                                ui.label(RichText::new(day_label)
+                                                    .size(14.0)
+                                                    .color(text_primary));
+
+                                                if self.streak > 0 {
+                                                    ui.add_space(16.0);
+                                                    ui.label(RichText::new(format!("连续 {} 天", self.streak))
+                                                        .size(14.0)
+                                                        .color(theme.warning_color));
+                                                }
+
+                                                ui.add_space(16.0);
+
+                                                ui.label(RichText::new(money.money(self.week_balance))
+                                                    .size(14.0)
+                                                    .color(text_primary));
+
+                                                ui.add_space(8.0);
+
+                                                ui.label(RichText::new("本周")
+                                                    .size(14.0)
+                                                    .color(text_secondary));
+
+                                                // 一键结清日期为今天的全部记录，确认后执行，不影响之前的日期
+                                                ui.add_space(16.0);
+                                                if ui.small_button("今日结清").on_hover_text("将今天的全部记录标记为已结清").clicked() {
+                                                    let today = self.today().format("%Y-%m-%d").to_string();
+                                                    self.pending_settle = Some(PendingSettle { boss: None, period: Some(today), settled: true });
+                                                }
+                                            });
+                                        });
+                                    }
+                                    if let Some((idx, action)) = timer_action {
+                                        self.apply_timer_action(idx, action);
+                                    } else if label_changed {
+                                        self.save_timers();
+                                    }
+                                });
+                        });  // 闭合新增的 vertical (timer_card_width)
+                    });
+                });
+            });
+
+        // 提示消息
+        if !self.message.is_empty() {
+            let toast_color = if self.message_is_error { danger_color } else { green_color };
+            egui::Area::new(egui::Id::new("toast"))
+                .order(egui::Order::Tooltip)
+                .anchor(egui::Align2::CENTER_TOP, Vec2::new(0.0, 12.0))
+                .interactable(false)
+                .show(ctx, |ui| {
+                    egui::Frame::default()
+                        .fill(card_color)
+                        .stroke(Stroke::new(1.0, toast_color))
+                        .corner_radius(CornerRadius::same(8))
+                        .inner_margin(egui::Margin::symmetric(16, 8))
+                        .show(ui, |ui| {
+                            ui.label(RichText::new(&self.message).size(14.0).color(toast_color));
+                        });
+                });
+        }
+
+        // 设置全局样式
+        let mut style = (*ctx.style()).clone();
+        style.visuals = if theme.is_dark { egui::Visuals::dark() } else { egui::Visuals::light() };
+        style.visuals.widgets.inactive.bg_fill = input_bg;
+        style.visuals.widgets.inactive.bg_stroke = Stroke::new(1.0, theme.border_color);
+        style.visuals.widgets.inactive.corner_radius = CornerRadius::same(8);
+        style.visuals.widgets.hovered.bg_fill = theme.hover_bg;
+        style.visuals.widgets.active.bg_fill = theme.active_bg;
+        style.visuals.selection.bg_fill = accent_color;
+        ctx.set_style(style);
+
+        egui::CentralPanel::default()
+            .frame(egui::Frame::default().fill(bg_color).inner_margin(layout.panel_margin as i8))
+            .show(ctx, |ui| {
+                // 内容宽度不超过窗口可用宽度，居中显示
+                let content_width = layout.content_width.min(ui.available_width());
+                let available = ui.available_width();
+                let side_margin = ((available - content_width) / 2.0).max(0.0);
+
+                ui.horizontal(|ui| {
+                    ui.add_space(side_margin);
+                    ui.vertical(|ui| {
+                        ui.set_width(content_width);
+
+                // ===== 顶部标题区 =====
+                let mut month_changed = false;
+                let mut new_sel_year = self.selected_year;
+                let mut new_sel_month = self.selected_month;
+
+                // 标题行：左边标题，右边统计信息
+                ui.horizontal(|ui| {
+                    // 左边：标题
+                    ui.label(RichText::new("陪玩日记")
+                        .font(FontId::new(32.0, egui::FontFamily::Name("cute".into())))
+                        .color(text_primary));
+
+                    // 右边：统计信息（右对齐）
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        // 从右到左排列：总结余 -> 月结余 -> 日结余
+
+                        // 总结余
+                        let year_response = ui.label(RichText::new(money.money(self.total_balance))
+                            .font(FontId::proportional(22.0))
+                            .color(green_color));
+                        // 有其他币种收入时，悬停分别列出本年和累计的各币种合计，不同币种不相加
+                        if self.all_time_by_currency.keys().any(|c| c != DEFAULT_CURRENCY) {
+                            let text = format!(
+                                "{}年\n{}\n\n累计\n{}",
+                                self.selected_year,
+                                currency_breakdown(&self.year_by_currency, &money),
+                                currency_breakdown(&self.all_time_by_currency, &money),
+                            );
+                            year_response.on_hover_text(text);
+                        }
+                        ui.label(RichText::new("年收入")
+                            .font(FontId::proportional(13.0))
+                            .color(text_secondary));
+
+                        ui.add_space(20.0);
+
+                        // 环比上月
+                        match month_change(self.month_balance, self.prev_month_balance) {
+                            Some(MonthChange::New) => {
+                                ui.label(RichText::new("新").size(12.0).color(accent_color))
+                                    .on_hover_text("上月没有收入");
+                            }
+                            Some(MonthChange::Percent(pct)) => {
+                                let (text, color) = if pct >= 0.0 {
+                                    (format!("▲+{:.0}%", pct), green_color)
+                                } else {
+                                    (format!("▼{:.0}%", pct), danger_color)
+                                };
+                                ui.label(RichText::new(text).size(12.0).color(color))
+                                    .on_hover_text(format!("上月 {}", money.money(self.prev_month_balance)));
+                            }
+                            None => {}
+                        }
+
+                        // 月结余
+                        ui.label(RichText::new(money.money(self.month_balance))
+                            .font(FontId::proportional(18.0))
+                            .color(accent_color));
+
+                        // 下一个月
+                        if ui.small_button("▶").on_hover_text("下个月").clicked() {
+                            (new_sel_year, new_sel_month) = shift_month(new_sel_year, new_sel_month, 1);
+                        }
+
+                        // 月份选择：与输入行的日期选择框共用月历
+                        if month_picker(ui, "header_month_picker", &mut new_sel_year, &mut new_sel_month, Vec2::new(80.0, 22.0), self.settings.week_start.weekday(), &theme) {
+                            month_changed = true;
+                        }
+
+                        // 上一个月
+                        if ui.small_button("◀").on_hover_text("上个月").clicked() {
+                            (new_sel_year, new_sel_month) = shift_month(new_sel_year, new_sel_month, -1);
+                        }
+                        // 回到本月（已在本月时不可点）
+                        let today = self.today();
+                        let is_current = new_sel_year == today.year() && new_sel_month == today.month();
+                        if ui.add_enabled(!is_current, egui::Button::new("回到本月").small()).clicked() {
+                            new_sel_year = today.year();
+                            new_sel_month = today.month();
+                        }
+
+                        ui.label(RichText::new("月收入")
+                            .font(FontId::proportional(13.0))
+                            .color(text_secondary));
+
+                    });
+                });
+
+                // 处理年月选择变化
+                if month_changed || new_sel_year != self.selected_year || new_sel_month != self.selected_month {
+                    self.selected_year = new_sel_year;
+                    self.selected_month = new_sel_month;
+                    self.day_filter = None;
+                    self.boss_filter = None;
+                    self.refresh_data();
+                }
+
+                // 月收入目标进度（目标为 0 时不显示）
+                if self.settings.monthly_goal > 0.0 {
+                    let goal = self.settings.monthly_goal;
+                    let reached = self.month_balance >= goal;
+                    let fill = if reached { green_color } else { accent_color };
+                    let status = if reached {
+                        format!("超额 {}", money.money(self.month_balance - goal))
+                    } else {
+                        format!("还差 {}", money.money(goal - self.month_balance))
+                    };
+                    ui.add_space(10.0);
+                    ui.add(egui::ProgressBar::new((self.month_balance / goal).clamp(0.0, 1.0) as f32)
+                        .desired_height(8.0)
+                        .fill(fill)
+                        .corner_radius(CornerRadius::same(4)));
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(format!("月目标 {}", money.money(goal))).size(12.0).color(text_secondary));
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.label(RichText::new(status).size(12.0).color(fill));
+                        });
+                    });
+                    ui.add_space(20.0);
+                } else {
+                    ui.add_space(30.0);
+                }
+
+                // 定义统一的卡片宽度
+                let cards_width = ui.available_width();
+
+                // ===== 输入卡片 =====
+                let card_inner_w = cards_width - (layout.card_inner_margin * 2.0);
+                ui.vertical(|ui| {
+                    ui.set_width(cards_width);
+                    egui::Frame::default()
+                        .fill(card_color)
+                        .corner_radius(CornerRadius::same(layout.card_rounding as u8))
+                        .inner_margin(layout.card_inner_margin as i8)
+                        .show(ui, |ui| {
+                            ui.set_width(card_inner_w);
+                        let input_height = layout.input_height;
+                        let input_margin_y = layout.input_margin_y;
+                        let label_size = 13.0;
+                        let input_font_size = 15.0;
+                        let col_spacing = layout.col_spacing;
+
+                        // 固定宽度元素
+                        let date_width = 175.0;  // 日期选择框
+                        let today_btn_width = 50.0;
+                        let btn_width = 65.0;
+                        let checkbox_width = 55.0;  // 结清勾选框加宽
+                        let currency_width = 70.0;
+
+                        // 动态分配剩余宽度给输入框
+                        let fixed_total = date_width + today_btn_width + btn_width + checkbox_width + currency_width;
+                        let spacing_total = col_spacing * 8.0;
+                        let flex_total = (card_inner_w - fixed_total - spacing_total).max(200.0);
+                        // 比例分配，并设置最小宽度保护（防止从全屏游戏切换时窗口异常缩小）
+                        let boss_width = (flex_total * 0.28).max(80.0);
                         let game_width = (flex_total * 0.28).max(80.0);
                         let duration_width = (flex_total * 0.18).max(50.0);
                         let income_width = (flex_total * 0.26).max(70.0);
 
-                        let mut new_year = self.input_date.year();
-                        let mut new_month = self.input_date.month();
-                        let mut new_day = self.input_date.day();
-                        let mut set_today = false;
+                        let prev_date = self.input_date;
+                        let mut set_today = false;
+
+                        let dark_text = theme.combo_text;
+                        let mut form_focused = false;  // 任一输入框持有（或刚失去）焦点
+                        let mut picked_template: Option<Template> = None;
+                        let mut template_to_delete: Option<i64> = None;
+                        let mut pin_changed = false;
+
+                        ui.horizontal(|ui| {
+                            ui.spacing_mut().item_spacing.x = col_spacing;
+
+                            // 日期列
+                            ui.vertical(|ui| {
+                                ui.set_width(date_width);
+                                ui.label(RichText::new("日期").color(text_secondary).size(label_size));
+                                ui.add_space(4.0);
+                                let date_border = if self.input_date_error.is_some() { danger_color } else { theme.border_color };
+                                date_picker(ui, "input_date", &mut self.input_date, Vec2::new(date_width, input_height), date_border, self.settings.week_start.weekday(), &theme);
+                                if let Some(error) = &self.input_date_error {
+                                    field_hint(ui, ui.min_rect(), error, danger_color);
+                                }
+                            });
+
+                            // 今天按钮
+                            ui.vertical(|ui| {
+                                ui.set_width(today_btn_width);
+                                ui.add_space(17.0 + 4.0);
+                                let today_btn = egui::Button::new(RichText::new("今天").size(13.0).color(accent_color))
+                                    .fill(Color32::TRANSPARENT)
+                                    .stroke(Stroke::new(1.0, accent_color))
+                                    .corner_radius(CornerRadius::same(6));
+                                if ui.add_sized([today_btn_width, input_height], today_btn).clicked() {
+                                    set_today = true;
+                                }
+                            });
+
+                            // 老板列（必填）
+                            ui.vertical(|ui| {
+                                ui.set_width(boss_width);
+                                ui.horizontal(|ui| {
+                                    ui.spacing_mut().item_spacing = Vec2::ZERO;
+                                    ui.label(RichText::new("老板").color(text_secondary).size(label_size));
+                                    ui.label(RichText::new("*").color(danger_color).size(label_size));
+                                    if self.settings.remember_input {
+                                        ui.add_space(6.0);
+                                        pin_changed |= pin_toggle(ui, &mut self.settings.pin_boss, &theme);
+                                    }
+                                });
+                                ui.add_space(4.0);
+                                // 联想列表（上下键选择、回车确认）
+                                let boss_id = egui::Id::new("input_boss");
+                                let boss_suggestions = if self.active_suggestion == ActiveSuggestion::Boss {
+                                    // 未输入时列出最近用过的老板
+                                    let source = if self.input_boss.trim().is_empty() { &self.recent_bosses } else { &self.boss_list };
+                                    filter_suggestions(source, &self.input_boss, self.settings.suggestion_limit())
+                                } else {
+                                    Vec::new()
+                                };
+                                let mut boss_picked: Option<String> = None;
+                                if !boss_suggestions.is_empty() && ui.memory(|m| m.has_focus(boss_id)) {
+                                    if let Some(i) = handle_suggestion_keys(ui.ctx(), boss_suggestions.len(), &mut self.selected_suggestion_index) {
+                                        boss_picked = Some(boss_suggestions[i].text.clone());
+                                    }
+                                }
+                                // 使用 scope 限制样式修改范围
+                                let boss_response = ui.scope(|ui| {
+                                    if self.input_boss_error.is_some() {
+                                        ui.visuals_mut().widgets.inactive.bg_stroke = Stroke::new(1.0, danger_color);
+                                        ui.visuals_mut().widgets.hovered.bg_stroke = Stroke::new(1.0, danger_color);
+                                    }
+                                    ui.add_sized(
+                                        [boss_width, input_height],
+                                        egui::TextEdit::singleline(&mut self.input_boss)
+                                            .id(boss_id)
+                                            .font(FontId::proportional(input_font_size))
+                                            .margin(egui::Margin::symmetric(8, input_margin_y))
+                                            .vertical_align(egui::Align::Center)
+                                    )
+                                }).inner;
+                                form_focused |= boss_response.has_focus() || boss_response.lost_focus();
+                                if boss_response.gained_focus() {
+                                    self.active_suggestion = ActiveSuggestion::Boss;
+                                    self.selected_suggestion_index = None;
+                                    self.input_boss_error = None; // 获得焦点时清除错误状态
+                                }
+                                if let Some(error) = &self.input_boss_error {
+                                    field_hint(ui, boss_response.rect, error, danger_color);
+                                }
+                                if boss_response.changed() {
+                                    self.selected_suggestion_index = None;
+                                }
+                                // 老板建议列表
+                                if !boss_suggestions.is_empty() {
+                                    let clicked = suggestion_popup(ui, "boss_suggestions", boss_response.rect,
+                                        &boss_suggestions, &self.noted_bosses, self.selected_suggestion_index, &theme);
+                                    boss_picked = boss_picked.or(clicked);
+                                }
+                                if let Some(boss) = boss_picked {
+                                    self.input_boss = boss;
+                                    self.active_suggestion = ActiveSuggestion::None;
+                                    self.selected_suggestion_index = None;
+                                } else if self.active_suggestion == ActiveSuggestion::Boss && !boss_response.has_focus() && ui.ctx().input(|i| i.pointer.any_click()) {
+                                    self.active_suggestion = ActiveSuggestion::None;
+                                }
+                            });
+
+                            // 游戏列
+                            ui.vertical(|ui| {
+                                ui.set_width(game_width);
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new("游戏").color(text_secondary).size(label_size));
+                                    if self.settings.remember_input {
+                                        pin_changed |= pin_toggle(ui, &mut self.settings.pin_game, &theme);
+                                    }
+                                });
+                                ui.add_space(4.0);
+                                let game_id = egui::Id::new("input_game");
+                                let game_suggestions = if self.active_suggestion == ActiveSuggestion::Game {
+                                    let source = if self.input_game.trim().is_empty() { &self.recent_games } else { &self.game_list };
+                                    filter_suggestions(source, &self.input_game, self.settings.suggestion_limit())
+                                } else {
+                                    Vec::new()
+                                };
+                                let mut game_picked: Option<String> = None;
+                                if !game_suggestions.is_empty() && ui.memory(|m| m.has_focus(game_id)) {
+                                    if let Some(i) = handle_suggestion_keys(ui.ctx(), game_suggestions.len(), &mut self.selected_suggestion_index) {
+                                        game_picked = Some(game_suggestions[i].text.clone());
+                                    }
+                                }
+                                let game_response = ui.add_sized(
+                                    [game_width, input_height],
+                                    egui::TextEdit::singleline(&mut self.input_game)
+                                        .id(game_id)
+                                        .font(FontId::proportional(input_font_size))
+                                        .margin(egui::Margin::symmetric(8, input_margin_y))
+                                        .vertical_align(egui::Align::Center)
+                                );
+                                form_focused |= game_response.has_focus() || game_response.lost_focus();
+                                if game_response.gained_focus() {
+                                    self.active_suggestion = ActiveSuggestion::Game;
+                                    self.selected_suggestion_index = None;
+                                }
+                                if game_response.changed() {
+                                    self.selected_suggestion_index = None;
+                                }
+                                if !game_suggestions.is_empty() {
+                                    let clicked = suggestion_popup(ui, "game_suggestions", game_response.rect,
+                                        &game_suggestions, &HashSet::new(), self.selected_suggestion_index, &theme);
+                                    game_picked = game_picked.or(clicked);
+                                }
+                                if game_response.lost_focus() {
+                                    self.apply_game_default();
+                                }
+                                if let Some(game) = game_picked {
+                                    self.input_game = game;
+                                    self.active_suggestion = ActiveSuggestion::None;
+                                    self.selected_suggestion_index = None;
+                                    self.apply_game_default();
+                                } else if self.active_suggestion == ActiveSuggestion::Game && !game_response.has_focus() && ui.ctx().input(|i| i.pointer.any_click()) {
+                                    self.active_suggestion = ActiveSuggestion::None;
+                                }
+                            });
+
+                            // 时长列
+                            ui.vertical(|ui| {
+                                ui.set_width(duration_width);
+                                ui.horizontal(|ui| {
+                                    ui.label(RichText::new("时长/h").color(text_secondary).size(label_size));
+                                    if self.settings.remember_input {
+                                        pin_changed |= pin_toggle(ui, &mut self.settings.pin_duration, &theme);
+                                    }
+                                });
+                                ui.add_space(4.0);
+                                let duration_response = ui.scope(|ui| {
+                                    if self.input_duration_error.is_some() {
+                                        ui.visuals_mut().widgets.inactive.bg_stroke = Stroke::new(1.0, danger_color);
+                                        ui.visuals_mut().widgets.hovered.bg_stroke = Stroke::new(1.0, danger_color);
+                                    }
+                                    ui.add_sized([duration_width, input_height],
+                                        egui::TextEdit::singleline(&mut self.input_duration)
+                                            .font(FontId::proportional(input_font_size))
+                                            .margin(egui::Margin::symmetric(6, input_margin_y))
+                                            .vertical_align(egui::Align::Center)
+                                            .char_limit(5)
+                                    )
+                                }).inner;
+                                form_focused |= duration_response.has_focus() || duration_response.lost_focus();
+                                // Tab 切到其他输入框时关闭联想
+                                if duration_response.gained_focus() {
+                                    self.active_suggestion = ActiveSuggestion::None;
+                                    self.input_duration_error = None;
+                                }
+                                if let Some(error) = &self.input_duration_error {
+                                    field_hint(ui, duration_response.rect, error, danger_color);
+                                }
+                            });
+
+                            // 收入列（必填）
+                            ui.vertical(|ui| {
+                                ui.set_width(income_width);
+                                ui.horizontal(|ui| {
+                                    ui.spacing_mut().item_spacing = Vec2::ZERO;
+                                    ui.label(RichText::new("收入").color(text_secondary).size(label_size));
+                                    ui.label(RichText::new("*").color(danger_color).size(label_size));
+                                    // 按老板时薪估算的收入，点击填入（只在未手动输入时显示）
+                                    if let Some(amount) = self.suggested_income().filter(|_| self.input_income.trim().is_empty()) {
+                                        ui.add_space(6.0);
+                                        if tag_chip(ui, &format!("≈{}", amount), false, &theme)
+                                            .on_hover_text("按老板时薪或游戏单价估算，点击填入")
+                                            .clicked()
+                                        {
+                                            self.input_income = amount.to_string();
+                                        }
+                                    }
+                                    // 常用金额，点击直接填入
+                                    let mut preset_picked = None;
+                                    for amount in self.settings.income_presets.iter().take(MAX_INCOME_PRESETS) {
+                                        if tag_chip(ui, &amount.to_string(), false, &theme).clicked() {
+                                            preset_picked = Some(*amount);
+                                        }
+                                    }
+                                    if let Some(amount) = preset_picked {
+                                        self.input_income = amount.to_string();
+                                        self.input_income_error = None;
+                                    }
+                                });
+                                ui.add_space(4.0);
+                                // 使用 scope 限制样式修改范围
+                                let income_response = ui.scope(|ui| {
+                                    if self.input_income_error.is_some() {
+                                        ui.visuals_mut().widgets.inactive.bg_stroke = Stroke::new(1.0, danger_color);
+                                        ui.visuals_mut().widgets.hovered.bg_stroke = Stroke::new(1.0, danger_color);
+                                    }
+                                    ui.add_sized(
+                                        [income_width, input_height],
+                                        egui::TextEdit::singleline(&mut self.input_income)
+                                            .font(FontId::proportional(input_font_size))
+                                            .margin(egui::Margin::symmetric(6, input_margin_y))
+                                            .vertical_align(egui::Align::Center)
+                                            .char_limit(10)
+                                    )
+                                }).inner;
+                                form_focused |= income_response.has_focus() || income_response.lost_focus();
+                                // 获得焦点时清除错误状态
+                                if income_response.gained_focus() {
+                                    self.input_income_error = None;
+                                    self.active_suggestion = ActiveSuggestion::None;
+                                }
+                                // 有错误时显示错误，否则输入时预览格式化后的金额（全角数字、千分位逗号按解析后的值显示）
+                                if let Some(error) = &self.input_income_error {
+                                    field_hint(ui, income_response.rect, error, danger_color);
+                                } else if !self.input_income.trim().is_empty() {
+                                    match parse_income(&self.input_income) {
+                                        Some(value) => field_hint(ui, income_response.rect, &format!("= {}", money.income_in(value, &self.input_currency)), text_secondary),
+                                        None => field_hint(ui, income_response.rect, "不是有效金额", danger_color),
+                                    }
+                                }
+                            });
+
+                            // 币种列
+                            ui.vertical(|ui| {
+                                ui.set_width(currency_width);
+                                ui.label(RichText::new("币种").color(text_secondary).size(label_size));
+                                ui.add_space(4.0);
+                                egui::Frame::default()
+                                    .fill(input_bg)
+                                    .corner_radius(CornerRadius::same(8))
+                                    .stroke(Stroke::new(1.0, theme.border_color))
+                                    .inner_margin(egui::Margin::symmetric(6, 0))
+                                    .show(ui, |ui| {
+                                        ui.set_height(input_height);
+                                        ui.horizontal_centered(|ui| {
+                                            egui::ComboBox::from_id_salt("currency_select")
+                                                .width(currency_width - 20.0)
+                                                .selected_text(RichText::new(&self.input_currency).size(13.0).color(dark_text))
+                                                .show_ui(ui, |ui| {
+                                                    for (code, symbol) in CURRENCIES {
+                                                        ui.selectable_value(&mut self.input_currency, code.to_string(), format!("{} {}", symbol, code));
+                                                    }
+                                                });
+                                        });
+                                    });
+                            });
+
+                            // 结清列
+                            ui.vertical(|ui| {
+                                ui.set_width(checkbox_width);
+                                ui.label(RichText::new("结清").color(text_secondary).size(label_size));
+                                ui.add_space(4.0);
+                                ui.add_space(10.0);
+                                ui.scope(|ui| {
+                                    if self.input_settled {
+                                        ui.visuals_mut().widgets.inactive.bg_fill = danger_color;
+                                        ui.visuals_mut().widgets.inactive.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+                                        ui.visuals_mut().widgets.hovered.bg_fill = danger_color;
+                                        ui.visuals_mut().widgets.hovered.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+                                    }
+                                    ui.add_sized([checkbox_width, 20.0], egui::Checkbox::new(&mut self.input_settled, ""));
+                                });
+                            });
+
+                            // 添加按钮（上方为模板下拉）
+                            ui.vertical(|ui| {
+                                ui.set_width(btn_width);
+                                if self.templates.is_empty() {
+                                    ui.add_space(17.0 + 4.0);
+                                } else {
+                                    ui.allocate_ui(Vec2::new(btn_width, 17.0), |ui| {
+                                        ui.menu_button(RichText::new("模板 ▾").color(accent_color).size(label_size), |ui| {
+                                            for t in &self.templates {
+                                                ui.horizontal(|ui| {
+                                                    if ui.button(&t.name).on_hover_text(format!(
+                                                        "{} · {} · {}",
+                                                        t.boss,
+                                                        t.game.as_deref().unwrap_or("-"),
+                                                        money.money(t.income),
+                                                    )).clicked() {
+                                                        picked_template = Some(t.clone());
+                                                        ui.close_menu();
+                                                    }
+                                                    if ui.small_button("✕").on_hover_text("删除模板").clicked() {
+                                                        template_to_delete = Some(t.id);
+                                                    }
+                                                });
+                                            }
+                                        });
+                                    });
+                                    ui.add_space(4.0);
+                                }
+                                let btn = egui::Button::new(RichText::new("添加").font(FontId::proportional(14.0)).color(Color32::WHITE))
+                                    .fill(accent_color)
+                                    .corner_radius(CornerRadius::same(6));
+                                if ui.add_sized([btn_width, input_height], btn).clicked() {
+                                    self.add_record();
+                                }
+                            });
+                            }); // 结束 vertical, horizontal
+
+                        // 模板只填充输入行，仍需点击添加
+                        if let Some(t) = picked_template {
+                            self.apply_template(&t);
+                            set_today = true;
+                        }
+                        if let Some(id) = template_to_delete {
+                            self.delete_template(id);
+                        }
+                        if pin_changed {
+                            let _ = self.settings.save();
+                        }
+
+                        // 处理日期变化
+                        if set_today {
+                            self.input_date = self.today();
+                        }
+                        if self.input_date != prev_date {
+                            self.input_date_error = None;
+                            self.refresh_day_data();
+                        }
+
+                        // 键盘快捷键：回车添加，Esc 先关闭联想弹窗，没有弹窗时清空输入
+                        if form_focused {
+                            let (enter, escape) = ui.input(|i| (i.key_pressed(egui::Key::Enter), i.key_pressed(egui::Key::Escape)));
+                            if enter {
+                                self.active_suggestion = ActiveSuggestion::None;
+                                self.add_record();
+                            } else if escape && self.active_suggestion != ActiveSuggestion::None {
+                                self.active_suggestion = ActiveSuggestion::None;
+                                self.selected_suggestion_index = None;
+                            } else if escape {
+                                self.clear_input();
+                            }
+                        }
+                    });
+                });
+
+                ui.add_space(24.0);
+
+                // ===== 表格区域 =====
+                ui.vertical(|ui| {
+                    ui.set_width(cards_width);
+                    egui::Frame::default()
+                        .fill(card_color)
+                        .corner_radius(CornerRadius::same(layout.card_rounding as u8))
+                        .inner_margin(layout.card_inner_margin as i8)
+                        .show(ui, |ui| {
+                            let table_inner_w = cards_width - (layout.card_inner_margin * 2.0);
+                            ui.set_width(table_inner_w);
+                        let table_w = table_inner_w;
+                        // 让表格占据剩余所有高度
+                        let remaining_height = ui.available_height();
+                        ui.set_min_height(remaining_height.max(390.0));
 
-                        let dark_text = Color32::from_rgb(30, 30, 35);
+                        // 数据列表（选中月份的记录，日历选中某天时只显示该天；按标签筛选时显示全部月份）
+                        let view_records: Vec<Record> = if self.tag_filter.is_some() {
+                            self.tag_records.clone()
+                        } else {
+                            self.records.iter()
+                                .filter(|r| self.day_filter.as_ref().is_none_or(|d| r.date == *d))
+                                .cloned()
+                                .collect()
+                        };
+                        // 常客胶囊取自老板筛选之前的列表，筛选后其他胶囊仍可切换
+                        let top_bosses = top_bosses(&view_records, 5);
+                        let filtered_records: Vec<Record> = match &self.boss_filter {
+                            Some(boss) => view_records.into_iter().filter(|r| r.boss == *boss).collect(),
+                            None => view_records,
+                        };
+                        let mut view_desc = match (&self.tag_filter, &self.day_filter) {
+                            (Some(tag), _) => format!("标签「{}」", tag),
+                            (None, Some(date)) => date.clone(),
+                            (None, None) => format!("{}年{:02}月", self.selected_year, self.selected_month),
+                        };
+                        if let Some(boss) = &self.boss_filter {
+                            view_desc = format!("{} · {}", view_desc, boss);
+                        }
 
+                        // 工具栏
                         ui.horizontal(|ui| {
+                            ui.label(RichText::new(format!("{} · {}条", view_desc, filtered_records.len()))
+                                .color(text_secondary)
+                                .size(13.0));
+                            if self.day_filter.is_some() && ui.small_button("清除日期筛选").clicked() {
+                                self.day_filter = None;
+                            }
+                            if self.tag_filter.is_some() && ui.small_button("清除标签筛选").clicked() {
+                                self.tag_filter = None;
+                                self.tag_records.clear();
+                            }
+                            if self.boss_filter.is_some() && ui.small_button("清除老板筛选").clicked() {
+                                self.boss_filter = None;
+                            }
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                if ui.button(RichText::new("设置").size(13.0).color(accent_color)).clicked() {
+                                    self.show_settings = true;
+                                }
+                                let batch_label = if self.batch_mode { "退出批量" } else { "批量" };
+                                if ui.button(RichText::new(batch_label).size(13.0).color(accent_color)).clicked() {
+                                    self.batch_mode = !self.batch_mode;
+                                    self.batch_selected.clear();
+                                }
+                                ui.menu_button(RichText::new("导入").size(13.0).color(accent_color), |ui| {
+                                    if ui.button("导入 JSON 备份").clicked() {
+                                        ui.close_menu();
+                                        self.import_json();
+                                    }
+                                    if ui.button("导入 CSV（列映射）").clicked() {
+                                        ui.close_menu();
+                                        self.open_csv_import();
+                                    }
+                                });
+                                ui.menu_button(RichText::new("结清").size(13.0).color(accent_color), |ui| {
+                                    // 按当前月份批量操作，有老板筛选时只处理该老板
+                                    let month = Some(format!("{}-{:02}", self.selected_year, self.selected_month));
+                                    if ui.button("本月全部结清").clicked() {
+                                        self.pending_settle = Some(PendingSettle { boss: self.boss_filter.clone(), period: month.clone(), settled: true });
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("本月全部取消结清").clicked() {
+                                        self.pending_settle = Some(PendingSettle { boss: self.boss_filter.clone(), period: month, settled: false });
+                                        ui.close_menu();
+                                    }
+                                    ui.separator();
+                                    if ui.button("待结算老板").clicked() {
+                                        ui.close_menu();
+                                        self.open_unsettled_report();
+                                    }
+                                });
+                                ui.menu_button(RichText::new("导出").size(13.0).color(accent_color), |ui| {
+                                    ui.checkbox(&mut self.export_current_view, "仅导出当前视图");
+                                    ui.checkbox(&mut self.export_summary, "CSV 附加合计行");
+                                    ui.separator();
+                                    if ui.button("导出 CSV").clicked() {
+                                        self.export_csv(&filtered_records, &view_desc);
+                                        ui.close_menu();
+                                    }
+                                    if ui.button("按月导出 CSV").clicked() {
+                                        ui.close_menu();
+                                        self.export_csv_by_month();
+                                    }
+                                    if ui.button("导出 Excel").clicked() {
+                                        self.export_xlsx(&filtered_records);
+                                        ui.close_menu();
+                                    }
+                                    ui.separator();
+                                    if ui.button("导出 JSON 备份").clicked() {
+                                        self.export_json();
+                                        ui.close_menu();
+                                    }
+                                });
+                            });
+                        });
+
+                        // 收入日历（热力图），点击某天筛选表格
+                        egui::CollapsingHeader::new(RichText::new("收入日历").size(13.0).color(text_secondary))
+                            .id_salt("income_calendar")
+                            .default_open(false)
+                            .show(ui, |ui| {
+                                let selected_day = self.day_filter.as_ref()
+                                    .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                                    .map(|d| d.day());
+                                let calendar = ui.scope(|ui| calendar_heatmap(ui, (self.selected_year, self.selected_month), &self.daily_totals, selected_day, self.settings.week_start.weekday(), &money, &theme));
+                                if ui.small_button("导出图表").clicked() {
+                                    let name = format!("收入日历-{}-{:02}", self.selected_year, self.selected_month);
+                                    self.export_chart(ctx, name, calendar.response.rect);
+                                }
+                                if let Some(day) = calendar.inner {
+                                    if selected_day == Some(day) {
+                                        self.day_filter = None;
+                                    } else {
+                                        self.day_filter = Some(format!("{}-{:02}-{:02}", self.selected_year, self.selected_month, day));
+                                    }
+                                    self.tag_filter = None;
+                                }
+                            });
+
+                        // 收入分布：按单笔收入区间统计单数
+                        egui::CollapsingHeader::new(RichText::new("收入分布").size(13.0).color(text_secondary))
+                            .id_salt("income_histogram")
+                            .default_open(false)
+                            .show(ui, |ui| {
+                                let mut changed = false;
+                                ui.horizontal(|ui| {
+                                    changed |= ui.radio_value(&mut self.histogram_all_time, false, "本月").changed();
+                                    changed |= ui.radio_value(&mut self.histogram_all_time, true, "全部").changed();
+                                    ui.add_space(12.0);
+                                    ui.label(RichText::new("区间").size(13.0).color(text_secondary));
+                                    egui::ComboBox::from_id_salt("histogram_bin")
+                                        .width(60.0)
+                                        .selected_text(money.amount(self.histogram_bin))
+                                        .show_ui(ui, |ui| {
+                                            for bin in [50.0, 100.0, 200.0, 500.0] {
+                                                changed |= ui.selectable_value(&mut self.histogram_bin, bin, money.amount(bin)).changed();
+                                            }
+                                        });
+                                });
+                                if changed {
+                                    self.refresh_histogram();
+                                }
+                                if self.histogram.is_empty() {
+                                    ui.label(RichText::new("暂无记录").size(13.0).color(text_secondary));
+                                } else {
+                                    let chart = ui.scope(|ui| histogram_chart(ui, &self.histogram, &money, &theme));
+                                    if ui.small_button("导出图表").clicked() {
+                                        let range = if self.histogram_all_time {
+                                            "全部".to_string()
+                                        } else {
+                                            format!("{}-{:02}", self.selected_year, self.selected_month)
+                                        };
+                                        self.export_chart(ctx, format!("收入分布-{}", range), chart.response.rect);
+                                    }
+                                }
+                            });
+
+                        // 常客：当前视图中记录最多的老板，点击筛选，再次点击取消
+                        if !top_bosses.is_empty() {
+                            ui.horizontal(|ui| {
+                                ui.spacing_mut().item_spacing.x = 6.0;
+                                ui.label(RichText::new("常客").color(text_secondary).size(13.0));
+                                for (boss, count) in &top_bosses {
+                                    let selected = self.boss_filter.as_ref() == Some(boss);
+                                    if tag_chip(ui, &format!("{} {}", boss, count), selected, &theme).clicked() {
+                                        self.boss_filter = if selected { None } else { Some(boss.clone()) };
+                                    }
+                                }
+                            });
+                        }
+
+                        ui.add_space(8.0);
+
+                        // 固定列宽
+                        let col_spacing = layout.col_spacing;
+                        let delete_btn_width = 60.0;
+                        let settled_width = 45.0;
+                        // 时薪列可选，开启时从数据列中分出宽度
+                        let show_rate = self.settings.show_hourly_rate;
+                        let spacing_total = col_spacing * if show_rate { 8.0 } else { 7.0 };  // 8列有7个间距，加时薪列为9列
+                        let data_width = table_w - delete_btn_width - settled_width - spacing_total;
+                        let rate_width = if show_rate { data_width * 0.12 } else { 0.0 };
+                        let data_width = data_width - rate_width;
+                        let col_widths = [
+                            data_width * 0.15,  // 日期
+                            data_width * 0.18,  // 老板
+                            data_width * 0.20,  // 游戏
+                            data_width * 0.10,  // 时长
+                            data_width * 0.17,  // 收入
+                            data_width * 0.20,  // 结余
+                            settled_width,      // 结清
+                            delete_btn_width,   // 操作
+                        ];
+                        // 列分隔线相对行内容左侧的位置（开启列分隔线时使用）
+                        let dividers = if self.settings.column_dividers {
+                            let mut widths = col_widths[..5].to_vec();
+                            if show_rate {
+                                widths.push(rate_width);
+                            }
+                            widths.extend_from_slice(&col_widths[5..]);
+                            column_divider_offsets(&widths, col_spacing)
+                        } else {
+                            Vec::new()
+                        };
+                        let divider_stroke = Stroke::new(1.0, theme.text_muted.gamma_multiply(0.5));
+
+                        // 表头
+                        let header_rect = ui.horizontal(|ui| {
                             ui.spacing_mut().item_spacing.x = col_spacing;
+                            ui.add_sized([col_widths[0], 22.0], egui::Label::new(
+                                RichText::new("日期").color(text_secondary).size(14.0)
+                            ));
+                            ui.add_sized([col_widths[1], 22.0], egui::Label::new(
+                                RichText::new("老板").color(text_secondary).size(14.0)
+                            ));
+                            ui.add_sized([col_widths[2], 22.0], egui::Label::new(
+                                RichText::new("游戏").color(text_secondary).size(14.0)
+                            ));
+                            ui.add_sized([col_widths[3], 22.0], egui::Label::new(
+                                RichText::new("时长").color(text_secondary).size(14.0)
+                            ));
+                            ui.add_sized([col_widths[4], 22.0], egui::Label::new(
+                                RichText::new("收入").color(text_secondary).size(14.0)
+                            ));
+                            if show_rate {
+                                ui.add_sized([rate_width, 22.0], egui::Label::new(
+                                    RichText::new("时薪").color(text_secondary).size(14.0)
+                                ));
+                            }
+                            ui.add_sized([col_widths[5], 22.0], egui::Label::new(
+                                RichText::new("结余").color(text_secondary).size(14.0)
+                            ));
+                            // 点击表头切换按结清状态排序（未结清在前）
+                            let settled_header = if self.unsettled_first { "结清 ▲" } else { "结清" };
+                            let settled_color = if self.unsettled_first { accent_color } else { text_secondary };
+                            if ui.add_sized([col_widths[6], 22.0], egui::Label::new(
+                                RichText::new(settled_header).color(settled_color).size(14.0)
+                            ).sense(egui::Sense::click()))
+                                .on_hover_text("点击切换：未结清的排在前面")
+                                .on_hover_cursor(egui::CursorIcon::PointingHand)
+                                .clicked()
+                            {
+                                self.unsettled_first = !self.unsettled_first;
+                            }
+                            ui.add_sized([col_widths[7], 22.0], egui::Label::new(
+                                RichText::new(if self.batch_mode { "选择" } else { "操作" }).color(text_secondary).size(14.0)
+                            ));
+                        }).response.rect;
+                        for x in &dividers {
+                            ui.painter().vline(header_rect.left() + x, header_rect.y_range(), divider_stroke);
+                        }
 
-                            // 日期列
-                            ui.vertical(|ui| {
-                                ui.set_width(date_width);
-                                ui.label(RichText::new("日期").color(text_secondary).size(label_size));
-                                ui.add_space(4.0);
-                                egui::Frame::default()
-                                    .fill(input_bg)
-                                    .corner_radius(CornerRadius::same(8))
-                                    .stroke(Stroke::new(1.0, Color32::from_rgb(60, 65, 75)))
-                                    .inner_margin(egui::Margin::symmetric(6, 0))
-                                    .show(ui, |ui| {
-                                        ui.set_height(input_height);
-                                        ui.horizontal_centered(|ui| {
-                                            ui.spacing_mut().item_spacing.x = 2.0;
-                                            let current_year = Local::now().year();
-                                            egui::ComboBox::from_id_salt("year_select")
-                                                .width(56.0)
-                                                .selected_text(RichText::new(format!("{}", new_year)).size(13.0).color(dark_text))
-                                                .show_ui(ui, |ui| {
-                                                    for y in (current_year - 5)..=(current_year + 1) {
-                                                        ui.selectable_value(&mut new_year, y, format!("{}", y));
+                        ui.add_space(10.0);
+                        ui.separator();
+                        ui.add_space(6.0);
+
+                        // 计算当月累计结余（按时间正序累计，最新记录显示总累计）
+                        // 不同币种分别累计，每行显示该记录所属币种的结余
+                        let mut remaining: HashMap<&str, f64> = HashMap::new();
+                        for r in &filtered_records {
+                            *remaining.entry(r.currency.as_str()).or_insert(0.0) += r.income;
+                        }
+                        let mut running_balances: Vec<f64> = Vec::new();
+                        for r in &filtered_records {
+                            let balance = remaining.entry(r.currency.as_str()).or_insert(0.0);
+                            running_balances.push(round2(*balance));
+                            *balance -= r.income;
+                        }
+                        // 按结清状态排序时结余跟随各自的记录移动（稳定排序，同状态内保持时间顺序）
+                        let (filtered_records, running_balances): (Vec<Record>, Vec<f64>) = if self.unsettled_first {
+                            let mut rows: Vec<(Record, f64)> = filtered_records.into_iter().zip(running_balances).collect();
+                            rows.sort_by_key(|(r, _)| r.settled);
+                            rows.into_iter().unzip()
+                        } else {
+                            (filtered_records, running_balances)
+                        };
+
+                        if self.record_count == 0 && !self.settings.onboarded {
+                            // 新手引导：数据库为空且从未添加过真实数据
+                            let mut import_json = false;
+                            let mut import_csv = false;
+                            let mut add_demo = false;
+                            egui::ScrollArea::vertical()
+                                .auto_shrink([false, false])
+                                .show(ui, |ui| {
+                                    ui.add_space(40.0);
+                                    egui::Frame::default()
+                                        .fill(theme.card_color)
+                                        .stroke(Stroke::new(1.0, theme.border_color))
+                                        .corner_radius(CornerRadius::same(layout.card_rounding as u8))
+                                        .inner_margin(egui::Margin::same(24))
+                                        .show(ui, |ui| {
+                                            ui.vertical_centered(|ui| {
+                                                ui.label(RichText::new("欢迎使用陪玩日记")
+                                                    .color(text_primary)
+                                                    .size(20.0));
+                                                ui.add_space(12.0);
+                                                for line in [
+                                                    "1. 在上方填写日期、老板、游戏、时长和收入，点击“添加”记一笔",
+                                                    "2. 陪玩时可用底部计时器计时，结束后一键填入时长",
+                                                    "3. 老板付款后勾选“结清”，顶部随时查看本月和全年收入",
+                                                ] {
+                                                    ui.label(RichText::new(line).color(text_secondary).size(14.0));
+                                                }
+                                                ui.add_space(16.0);
+                                                ui.horizontal(|ui| {
+                                                    // 两个按钮居中
+                                                    let buttons_width = 220.0;
+                                                    ui.add_space(((ui.available_width() - buttons_width) / 2.0).max(0.0));
+                                                    ui.menu_button(RichText::new("导入数据").size(14.0).color(accent_color), |ui| {
+                                                        if ui.button("导入 JSON 备份").clicked() {
+                                                            ui.close_menu();
+                                                            import_json = true;
+                                                        }
+                                                        if ui.button("导入 CSV（列映射）").clicked() {
+                                                            ui.close_menu();
+                                                            import_csv = true;
+                                                        }
+                                                    });
+                                                    ui.add_space(16.0);
+                                                    if ui.button(RichText::new("添加示例").size(14.0)).clicked() {
+                                                        add_demo = true;
                                                     }
                                                 });
-                                            ui.label(RichText::new("-").size(13.0).color(text_secondary));
-                                            egui::ComboBox::from_id_salt("month_select")
-                                                .width(36.0)
-                                                .selected_text(RichText::new(format!("{:02}", new_month)).size(13.0).color(dark_text))
-                                                .show_ui(ui, |ui| {
-                                                    for m in 1..=12u32 {
-                                                        ui.selectable_value(&mut new_month, m, format!("{:02}", m));
+                                                ui.add_space(8.0);
+                                                ui.label(RichText::new("示例记录可以随时删除")
+                                                    .color(theme.text_muted)
+                                                    .size(12.0));
+                                            });
+                                        });
+                                });
+                            if import_json {
+                                self.import_json();
+                            }
+                            if import_csv {
+                                self.open_csv_import();
+                            }
+                            if add_demo {
+                                self.add_demo_records();
+                            }
+                        } else if filtered_records.is_empty() {
+                            egui::ScrollArea::vertical()
+                                .auto_shrink([false, false])
+                                .show(ui, |ui| {
+                                    ui.add_space(80.0);
+                                    ui.vertical_centered(|ui| {
+                                        ui.label(RichText::new("当月暂无记录")
+                                            .color(text_secondary)
+                                            .size(17.0));
+                                        ui.add_space(8.0);
+                                        ui.label(RichText::new("选择其他月份或添加新记录")
+                                            .color(theme.text_muted)
+                                            .size(13.0));
+                                    });
+                                });
+                        } else {
+                            let mut to_delete: Option<i64> = None;
+                            let mut to_toggle_settled: Option<(i64, bool)> = None;
+                            let mut clicked_tag: Option<String> = None;
+                            let mut to_add_tag: Option<(i64, String)> = None;
+                            let mut to_remove_tag: Option<(i64, String)> = None;
+                            let mut to_save_template: Option<(Record, String)> = None;
+                            let mut to_copy_today: Option<Record> = None;
+                            let mut to_edit_created_at: Option<Record> = None;
+                            let mut to_update_income: Option<(i64, String)> = None;
+                            let mut clicked_boss: Option<String> = None;
+                            let mut to_reorder: Option<(i64, i64, bool, String)> = None;  // (拖动的记录, 目标记录, 放在上方, 日期)
+                            let mut hovered_row: Option<i64> = None;
+                            let row_height = layout.row_height;
+
+                            // 键盘操作表格：上下方向键移动选中行，Delete 删除选中（或悬停）的行；输入框有焦点时不响应
+                            let keyboard_free = !ctx.wants_keyboard_input()
+                                && self.pending_delete.is_none()
+                                && self.created_at_edit.is_none();
+                            let (key_up, key_down, key_delete) = if keyboard_free {
+                                ctx.input_mut(|i| (
+                                    i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowUp),
+                                    i.consume_key(egui::Modifiers::NONE, egui::Key::ArrowDown),
+                                    i.consume_key(egui::Modifiers::NONE, egui::Key::Delete),
+                                ))
+                            } else {
+                                (false, false, false)
+                            };
+                            let mut scroll_offset = None;
+                            if key_up || key_down {
+                                let last = filtered_records.len() - 1;
+                                let current = self.focused_row
+                                    .and_then(|id| filtered_records.iter().position(|r| r.id == id));
+                                let idx = match current {
+                                    Some(i) if key_up => i.saturating_sub(1),
+                                    Some(i) => (i + 1).min(last),
+                                    None if key_up => last,
+                                    None => 0,
+                                };
+                                self.focused_row = Some(filtered_records[idx].id);
+                                // 选中行移出可见区域时滚动到它
+                                let stride = row_height + ui.spacing().item_spacing.y;
+                                let (offset, height) = self.table_view;
+                                let top = idx as f32 * stride;
+                                if top < offset {
+                                    scroll_offset = Some(top);
+                                } else if top + row_height > offset + height {
+                                    scroll_offset = Some(top + row_height - height);
+                                }
+                            }
+
+                            // 只布局可见区域内的行，记录再多每帧开销也只与可见行数相关
+                            // 底部留出合计行的高度
+                            let footer_height = 34.0;
+                            let mut scroll_area = egui::ScrollArea::vertical()
+                                .auto_shrink([false, false])
+                                .max_height((ui.available_height() - footer_height).max(row_height));
+                            if let Some(offset) = scroll_offset {
+                                scroll_area = scroll_area.vertical_scroll_offset(offset);
+                            }
+                            let table_output = scroll_area
+                                .show_rows(ui, row_height, filtered_records.len(), |ui, row_range| {
+                                    for idx in row_range {
+                                        let record = &filtered_records[idx];
+                                        let row_bg = if self.settings.zebra_stripes && idx % 2 == 1 {
+                                            theme.stripe_color
+                                        } else {
+                                            Color32::TRANSPARENT
+                                        };
+
+                                        let row_response = fixed_height_row(ui, row_height, |ui| egui::Frame::default()
+                                            .fill(row_bg)
+                                            .corner_radius(CornerRadius::same(6))
+                                            .inner_margin(egui::Margin::symmetric(4, 6))
+                                            .show(ui, |ui| {
+                                                ui.horizontal(|ui| {
+                                                    ui.spacing_mut().item_spacing.x = col_spacing;
+                                                    let text_height = row_height - 12.0;
+
+                                                    // 日期（悬停显示录入时间，开启后在日期后显示“时:分”，完整日期省去年份）
+                                                    let created_time = record.created_at.as_deref().and_then(|t| t.get(11..16));
+                                                    let date_fmt = self.settings.date_display;
+                                                    let date_text = match created_time {
+                                                        Some(time) if self.settings.show_time && date_fmt == DateDisplay::Iso => {
+                                                            format!("{} {}", record.date.get(5..).unwrap_or(&record.date), time)
+                                                        }
+                                                        Some(time) if self.settings.show_time => {
+                                                            format!("{} {}", format_display_date(&record.date, date_fmt), time)
+                                                        }
+                                                        _ => format_display_date(&record.date, date_fmt),
+                                                    };
+                                                    let date_response = ui.add_sized([col_widths[0], text_height], egui::Label::new(
+                                                        RichText::new(date_text)
+                                                            .color(text_primary)
+                                                            .size(14.0)
+                                                    ));
+                                                    if let Some(created_at) = &record.created_at {
+                                                        date_response.on_hover_text(format!("录入于 {}", created_at));
+                                                    }
+                                                    // 老板（点击打开详情）
+                                                    let boss_response = ui.add_sized([col_widths[1], text_height], egui::Label::new(
+                                                        RichText::new(&record.boss)
+                                                            .color(text_primary)
+                                                            .size(14.0)
+                                                    ).sense(egui::Sense::click()))
+                                                        .on_hover_cursor(egui::CursorIcon::PointingHand);
+                                                    if boss_response.clicked() {
+                                                        clicked_boss = Some(record.boss.clone());
+                                                    }
+                                                    // 游戏（有标签时在后面显示标签胶囊）
+                                                    let game_text = record.game.as_deref().unwrap_or("-");
+                                                    if record.tags.is_empty() {
+                                                        ui.add_sized([col_widths[2], text_height], egui::Label::new(
+                                                            RichText::new(game_text)
+                                                                .color(text_primary)
+                                                                .size(14.0)
+                                                        ));
+                                                    } else {
+                                                        ui.allocate_ui_with_layout(
+                                                            Vec2::new(col_widths[2], text_height),
+                                                            egui::Layout::left_to_right(egui::Align::Center),
+                                                            |ui| {
+                                                                ui.set_width(col_widths[2]);
+                                                                ui.spacing_mut().item_spacing.x = 4.0;
+                                                                ui.label(RichText::new(game_text).color(text_primary).size(14.0));
+                                                                for tag in &record.tags {
+                                                                    let selected = self.tag_filter.as_ref() == Some(tag);
+                                                                    if tag_chip(ui, tag, selected, &theme).clicked() {
+                                                                        clicked_tag = Some(tag.clone());
+                                                                    }
+                                                                }
+                                                            },
+                                                        );
+                                                    }
+                                                    // 时长
+                                                    let duration_text = format_duration(record.duration);
+                                                    ui.add_sized([col_widths[3], text_height], egui::Label::new(
+                                                        RichText::new(duration_text)
+                                                            .color(text_secondary)
+                                                            .size(14.0)
+                                                    ));
+                                                    // 收入（点击后就地修改，回车或失焦保存，Esc 取消）
+                                                    if self.editing_income == Some(record.id) {
+                                                        let edit_id = egui::Id::new(("edit_income", record.id));
+                                                        let response = ui.add_sized(
+                                                            [col_widths[4], text_height],
+                                                            egui::TextEdit::singleline(&mut self.editing_income_text)
+                                                                .id(edit_id)
+                                                                .font(FontId::proportional(14.0))
+                                                                .char_limit(10)
+                                                        );
+                                                        if !response.has_focus() && !response.lost_focus() {
+                                                            response.request_focus();
+                                                        }
+                                                        if response.lost_focus() {
+                                                            // Esc 取消；金额没变时直接退出编辑，不保存也不提示
+                                                            let unchanged = parse_income(&self.editing_income_text) == Some(record.income);
+                                                            if !unchanged && !ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                                                                to_update_income = Some((record.id, self.editing_income_text.clone()));
+                                                            }
+                                                            self.editing_income = None;
+                                                        }
+                                                    } else {
+                                                        let response = ui.add_sized([col_widths[4], text_height], egui::Label::new(
+                                                            RichText::new(money.income_in(record.income, &record.currency))
+                                                                .color(green_color)
+                                                                .size(14.0)
+                                                        ).sense(egui::Sense::click()))
+                                                            .on_hover_cursor(egui::CursorIcon::Text);
+                                                        if response.clicked() {
+                                                            self.editing_income = Some(record.id);
+                                                            self.editing_income_text = record.income.to_string();
+                                                        }
+                                                    }
+                                                    // 时薪（没有时长时留空）
+                                                    if show_rate {
+                                                        let rate_text = hourly_rate(record)
+                                                            .map(|rate| format!("{}/h", money.money_in(rate, &record.currency)))
+                                                            .unwrap_or_default();
+                                                        ui.add_sized([rate_width, text_height], egui::Label::new(
+                                                            RichText::new(rate_text)
+                                                                .color(text_secondary)
+                                                                .size(14.0)
+                                                        ));
+                                                    }
+                                                    // 结余
+                                                    let running_balance = running_balances.get(idx).unwrap_or(&0.0);
+                                                    ui.add_sized([col_widths[5], text_height], egui::Label::new(
+                                                        RichText::new(money.money_in(*running_balance, &record.currency))
+                                                            .color(text_primary)
+                                                            .size(14.0)
+                                                    ));
+
+                                                    // 结清勾选框（可点击修改）
+                                                    let mut settled = record.settled;
+                                                    let checkbox_response = ui.scope(|ui| {
+                                                        if settled {
+                                                            ui.visuals_mut().widgets.inactive.bg_fill = danger_color;
+                                                            ui.visuals_mut().widgets.inactive.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+                                                            ui.visuals_mut().widgets.hovered.bg_fill = danger_color;
+                                                            ui.visuals_mut().widgets.hovered.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+                                                        }
+                                                        ui.add_sized([col_widths[6], text_height], egui::Checkbox::new(&mut settled, ""))
+                                                    }).inner;
+                                                    if checkbox_response.changed() {
+                                                        to_toggle_settled = Some((record.id, settled));
+                                                    }
+
+                                                    if self.batch_mode {
+                                                        // 批量模式：勾选后计入底部的所选合计
+                                                        let mut selected = self.batch_selected.contains(&record.id);
+                                                        if ui.add_sized([col_widths[7], text_height], egui::Checkbox::new(&mut selected, "")).changed() {
+                                                            if selected {
+                                                                self.batch_selected.insert(record.id);
+                                                            } else {
+                                                                self.batch_selected.remove(&record.id);
+                                                            }
+                                                        }
+                                                    } else {
+                                                        // 删除按钮
+                                                        let btn = egui::Button::new(
+                                                            RichText::new("删除")
+                                                                .size(12.0)
+                                                                .color(danger_color)
+                                                        )
+                                                        .fill(Color32::TRANSPARENT)
+                                                        .stroke(Stroke::new(1.0, danger_color))
+                                                        .corner_radius(CornerRadius::same(5))
+                                                        .min_size(Vec2::new(48.0, 26.0));
+
+                                                        if ui.add(btn).clicked() {
+                                                            to_delete = Some(record.id);
+                                                        }
+                                                    }
+                                                });
+                                            })
+                                            .response)
+                                            .interact(egui::Sense::click_and_drag());
+                                        // 未结清的行悬停显示该老板截至此单的累计未结金额
+                                        let row_response = match self.unsettled_running.get(&record.id) {
+                                            Some(owed) if !record.settled => row_response.on_hover_text(
+                                                format!("{} 截至此单累计未结 {}", record.boss, money.money_in(*owed, &record.currency))
+                                            ),
+                                            _ => row_response,
+                                        };
+                                        if row_response.clicked() {
+                                            self.focused_row = Some(record.id);
+                                        }
+                                        // 行内容左侧有 4 的内边距
+                                        for x in &dividers {
+                                            ui.painter().vline(row_response.rect.left() + 4.0 + x, row_response.rect.y_range(), divider_stroke);
+                                        }
+                                        if row_response.contains_pointer() {
+                                            hovered_row = Some(record.id);
+                                        }
+                                        if self.focused_row == Some(record.id) {
+                                            ui.painter().rect_stroke(
+                                                row_response.rect,
+                                                CornerRadius::same(6),
+                                                Stroke::new(1.5, accent_color),
+                                                egui::StrokeKind::Inside,
+                                            );
+                                        }
+
+                                        // 拖动整行调整同一天内的顺序，拖到其他日期的行上无效；按结清排序时不能拖动
+                                        if !self.unsettled_first {
+                                            row_response.dnd_set_drag_payload(DraggedRow { id: record.id, date: record.date.clone() });
+                                        }
+                                        if row_response.dragged() {
+                                            ui.ctx().set_cursor_icon(egui::CursorIcon::Grabbing);
+                                        }
+                                        let pointer_above = ui.ctx().pointer_interact_pos()
+                                            .is_some_and(|pos| pos.y < row_response.rect.center().y);
+                                        if let Some(dragged) = row_response.dnd_hover_payload::<DraggedRow>() {
+                                            if dragged.date != record.date {
+                                                ui.ctx().set_cursor_icon(egui::CursorIcon::NoDrop);
+                                            } else if dragged.id != record.id {
+                                                let y = if pointer_above { row_response.rect.top() } else { row_response.rect.bottom() };
+                                                ui.painter().hline(row_response.rect.x_range(), y, Stroke::new(2.0, accent_color));
+                                            }
+                                        }
+                                        if let Some(dragged) = row_response.dnd_release_payload::<DraggedRow>() {
+                                            if dragged.date == record.date && dragged.id != record.id {
+                                                to_reorder = Some((dragged.id, record.id, pointer_above, record.date.clone()));
+                                            }
+                                        }
+
+                                        row_response.context_menu(|ui| {
+                                                // 右键菜单：复制、移除已有标签、输入新标签（带已有标签联想）
+                                                ui.set_min_width(160.0);
+                                                if ui.button("复制").clicked() {
+                                                    ui.ctx().copy_text(record_to_line(record, &money));
+                                                    ui.close_menu();
+                                                }
+                                                if ui.button("复制为CSV行").clicked() {
+                                                    ui.ctx().copy_text(export::record_to_csv_line(record));
+                                                    ui.close_menu();
+                                                }
+                                                if ui.button("复制到今天").clicked() {
+                                                    to_copy_today = Some(record.clone());
+                                                    ui.close_menu();
+                                                }
+                                                if ui.button("修改录入时间").clicked() {
+                                                    to_edit_created_at = Some(record.clone());
+                                                    ui.close_menu();
+                                                }
+                                                ui.separator();
+                                                if !record.tags.is_empty() {
+                                                    ui.horizontal_wrapped(|ui| {
+                                                        for tag in &record.tags {
+                                                            if ui.small_button(format!("{} ✕", tag)).clicked() {
+                                                                to_remove_tag = Some((record.id, tag.clone()));
+                                                                ui.close_menu();
+                                                            }
+                                                        }
+                                                    });
+                                                    ui.separator();
+                                                }
+                                                let input = ui.add(
+                                                    egui::TextEdit::singleline(&mut self.tag_input)
+                                                        .hint_text("添加标签，回车确认")
+                                                        .char_limit(20)
+                                                );
+                                                if input.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                                                    to_add_tag = Some((record.id, self.tag_input.clone()));
+                                                    ui.close_menu();
+                                                }
+                                                let suggestions: Vec<String> = filter_suggestions(&self.tag_list, self.tag_input.trim(), self.settings.suggestion_limit())
+                                                    .into_iter()
+                                                    .map(|s| s.text)
+                                                    .filter(|t| !record.tags.contains(t))
+                                                    .collect();
+                                                for tag in suggestions {
+                                                    if ui.button(&tag).clicked() {
+                                                        to_add_tag = Some((record.id, tag));
+                                                        ui.close_menu();
                                                     }
-                                                });
-                                            ui.label(RichText::new("-").size(13.0).color(text_secondary));
-                                            let max_days = days_in_month(new_year, new_month);
-                                            egui::ComboBox::from_id_salt("day_select")
-                                                .width(36.0)
-                                                .selected_text(RichText::new(format!("{:02}", new_day)).size(13.0).color(dark_text))
-                                                .show_ui(ui, |ui| {
-                                                    for d in 1..=max_days {
-                                                        ui.selectable_value(&mut new_day, d, format!("{:02}", d));
+                                                }
+                                                ui.separator();
+                                                // 存为模板，名称留空时使用老板名
+                                                ui.horizontal(|ui| {
+                                                    ui.add(
+                                                        egui::TextEdit::singleline(&mut self.template_name_input)
+                                                            .hint_text(&record.boss)
+                                                            .desired_width(100.0)
+                                                            .char_limit(20)
+                                                    );
+                                                    if ui.button("存为模板").clicked() {
+                                                        to_save_template = Some((record.clone(), self.template_name_input.clone()));
+                                                        ui.close_menu();
                                                     }
                                                 });
+                                            });
+                                    }
+                                });
+                            self.table_view = (table_output.state.offset.y, table_output.inner_rect.height());
+                            if key_delete && to_delete.is_none() {
+                                to_delete = self.focused_row
+                                    .filter(|id| filtered_records.iter().any(|r| r.id == *id))
+                                    .or(hovered_row);
+                            }
+
+                            // 合计行：与表格列对齐，统计当前视图（含筛选）的单数、时长、收入，以及已收 / 未收
+                            let (settled_sum, unsettled_sum) = settled_split(&filtered_records);
+                            let (total_hours, total_income) = export::record_totals(&filtered_records);
+                            ui.separator();
+                            egui::Frame::default()
+                                .inner_margin(egui::Margin::symmetric(4, 0))
+                                .show(ui, |ui| {
+                                    ui.horizontal(|ui| {
+                                        ui.spacing_mut().item_spacing.x = col_spacing;
+                                        let text_height = 20.0;
+                                        ui.add_sized([col_widths[0], text_height], egui::Label::new(
+                                            RichText::new("合计").color(text_secondary).size(14.0)
+                                        ));
+                                        ui.add_sized([col_widths[1], text_height], egui::Label::new(
+                                            RichText::new(format!("{}单", filtered_records.len())).color(text_primary).size(14.0)
+                                        ));
+                                        ui.add_sized([col_widths[2], text_height], egui::Label::new(""));
+                                        ui.add_sized([col_widths[3], text_height], egui::Label::new(
+                                            RichText::new(format!("{:.1}h", total_hours)).color(text_primary).size(14.0)
+                                        ));
+                                        ui.add_sized([col_widths[4], text_height], egui::Label::new(
+                                            RichText::new(money.money(total_income)).color(green_color).size(14.0)
+                                        ));
+                                        // 结余、结清、操作三列合并显示已收 / 未收
+                                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                            ui.label(RichText::new(format!("未收 {}", money.money(unsettled_sum)))
+                                                .color(theme.warning_color)
+                                                .size(13.0));
+                                            ui.label(RichText::new("/").color(text_secondary).size(13.0));
+                                            ui.label(RichText::new(format!("已收 {}", money.money(settled_sum)))
+                                                .color(green_color)
+                                                .size(13.0));
                                         });
                                     });
-                            });
+                                    // 批量模式：所选记录的单数和合计（按币种分别相加）
+                                    if self.batch_mode {
+                                        let selected: Vec<&Record> = filtered_records.iter()
+                                            .filter(|r| self.batch_selected.contains(&r.id))
+                                            .collect();
+                                        let mut select_all = false;
+                                        let mut clear = false;
+                                        ui.horizontal(|ui| {
+                                            ui.label(RichText::new(format!("已选 {} 单", selected.len())).color(text_secondary).size(13.0));
+                                            ui.label(RichText::new(format!("合计 {}", selected_total_text(&selected, &money)))
+                                                .color(accent_color)
+                                                .size(15.0));
+                                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                                clear = ui.small_button("清空").clicked();
+                                                select_all = ui.small_button("全选").clicked();
+                                            });
+                                        });
+                                        if select_all {
+                                            self.batch_selected.extend(filtered_records.iter().map(|r| r.id));
+                                        }
+                                        if clear {
+                                            self.batch_selected.clear();
+                                        }
+                                    }
+                                });
 
-                            // 今天按钮
-                            ui.vertical(|ui| {
-                                ui.set_width(today_btn_width);
-                                ui.add_space(17.0 + 4.0);
-                                let today_btn = egui::Button::new(RichText::new("今天").size(13.0).color(accent_color))
-                                    .fill(Color32::TRANSPARENT)
-                                    .stroke(Stroke::new(1.0, accent_color))
-                                    .corner_radius(CornerRadius::same(6));
-                                if ui.add_sized([today_btn_width, input_height], today_btn).clicked() {
-                                    set_today = true;
+                            // 处理标签操作
+                            if let Some(tag) = clicked_tag {
+                                self.toggle_tag_filter(&tag);
+                            }
+                            if let Some((id, tag)) = to_add_tag {
+                                self.tag_input.clear();
+                                if self.db.add_tag(id, &tag).is_ok() {
+                                    self.refresh_data();
                                 }
-                            });
-
-                            // 老板列（必填）
-                            ui.vertical(|ui| {
-                                ui.set_width(boss_width);
-                                ui.horizontal(|ui| {
-                                    ui.spacing_mut().item_spacing = Vec2::ZERO;
-                                    ui.label(RichText::new("老板").color(text_secondary).size(label_size));
-                                    ui.label(RichText::new("*").color(danger_color).size(label_size));
+                            }
+                            if let Some(boss) = clicked_boss {
+                                self.open_boss_detail(&boss);
+                            }
+                            if let Some((id, text)) = to_update_income {
+                                self.update_income(id, &text);
+                            }
+                            if let Some(record) = to_copy_today {
+                                self.copy_to_today(&record);
+                            }
+                            if let Some(record) = to_edit_created_at {
+                                self.created_at_edit = Some(CreatedAtEdit {
+                                    id: record.id,
+                                    summary: record_to_line(&record, &money),
+                                    text: record.created_at.clone().unwrap_or_default(),
+                                    error: false,
                                 });
-                                ui.add_space(4.0);
-                                // 使用 scope 限制样式修改范围
-                                let boss_response = ui.scope(|ui| {
-                                    if self.input_boss_error {
-                                        ui.visuals_mut().widgets.inactive.bg_stroke = Stroke::new(1.0, danger_color);
-                                        ui.visuals_mut().widgets.hovered.bg_stroke = Stroke::new(1.0, danger_color);
-                                    }
-                                    ui.add_sized(
-                                        [boss_width, input_height],
-                                        egui::TextEdit::singleline(&mut self.input_boss)
-                                            .font(FontId::proportional(input_font_size))
-                                            .margin(egui::Margin::symmetric(8, 8))
-                                    )
-                                }).inner;
-                                if boss_response.gained_focus() {
-                                    self.show_boss_suggestions = true;
-                                    self.input_boss_error = false; // 获得焦点时清除错误状态
+                            }
+                            if let Some((dragged_id, target_id, above, date)) = to_reorder {
+                                self.reorder_record(dragged_id, target_id, above, &date);
+                            }
+                            if let Some((record, name)) = to_save_template {
+                                self.template_name_input.clear();
+                                self.save_template(&record, &name);
+                            }
+                            if let Some((id, tag)) = to_remove_tag {
+                                if self.db.remove_tag(id, &tag).is_ok() {
+                                    self.refresh_data();
                                 }
-                                // 老板建议列表
-                                let mut boss_suggestion_clicked = false;
-                                if self.show_boss_suggestions && !self.boss_list.is_empty() {
-                                    let input_lower = self.input_boss.to_lowercase();
-                                    let suggestions: Vec<_> = self.boss_list.iter()
-                                        .filter(|b| input_lower.is_empty() || b.to_lowercase().contains(&input_lower))
-                                        .take(6).cloned().collect();
-                                    if !suggestions.is_empty() {
-                                        egui::Area::new(egui::Id::new("boss_suggestions"))
-                                            .order(egui::Order::Foreground)
-                                            .fixed_pos(boss_response.rect.left_bottom() + Vec2::new(0.0, 4.0))
-                                            .show(ui.ctx(), |ui| {
-                                                egui::Frame::default()
-                                                    .fill(Color32::from_rgb(50, 55, 65))
-                                                    .corner_radius(CornerRadius::same(6))
-                                                    .stroke(Stroke::new(1.0, Color32::from_rgb(70, 75, 85)))
-                                                    .shadow(egui::epaint::Shadow { offset: [0, 2], blur: 8, spread: 0, color: Color32::from_black_alpha(60) })
-                                                    .inner_margin(4)
-                                                    .show(ui, |ui| {
-                                                        ui.set_width(boss_width - 8.0);
-                                                        for boss in &suggestions {
-                                                            let btn = egui::Button::new(RichText::new(boss).size(14.0).color(text_primary))
-                                                                .fill(Color32::TRANSPARENT).stroke(Stroke::NONE).corner_radius(CornerRadius::same(4));
-                                                            if ui.add_sized([boss_width - 16.0, 28.0], btn).clicked() {
-                                                                self.input_boss = boss.clone();
-                                                                boss_suggestion_clicked = true;
-                                                            }
-                                                        }
-                                                    });
-                                            });
-                                    }
+                            }
+
+                            // 处理结清状态更新
+                            if let Some((id, new_settled)) = to_toggle_settled {
+                                if self.db.update_settled(id, new_settled).is_ok() {
+                                    self.refresh_data();
                                 }
-                                if boss_suggestion_clicked {
-                                    self.show_boss_suggestions = false;
-                                } else if self.show_boss_suggestions && !boss_response.has_focus() && ui.ctx().input(|i| i.pointer.any_click()) {
-                                    self.show_boss_suggestions = false;
+                            }
+
+                            if let Some(id) = to_delete {
+                                self.request_delete(id);
+                            }
+                        }
+                    });
+                }); // vertical for table card
+                    }); // vertical
+                }); // horizontal for centering
+            });
+
+        // ===== 设置窗口 =====
+        let mut settings_changed = false;
+        let mut suggestion_limit_changed = false;
+        let mut week_start_changed = false;
+        let mut open_integrity = false;
+        let mut open_game_settings = false;
+        let mut open_timer_sessions = false;
+        let mut open_audit_log = false;
+        let mut open_about = false;
+        #[cfg(feature = "encryption")]
+        let mut open_password = false;
+        egui::Window::new("设置")
+            .open(&mut self.show_settings)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.spacing_mut().item_spacing.y = 10.0;
+                ui.horizontal(|ui| {
+                    ui.label("主题");
+                    settings_changed |= ui.radio_value(&mut self.settings.theme, ThemeMode::Auto, "跟随系统").changed();
+                    settings_changed |= ui.radio_value(&mut self.settings.theme, ThemeMode::Dark, "深色").changed();
+                    settings_changed |= ui.radio_value(&mut self.settings.theme, ThemeMode::Light, "浅色").changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("强调色");
+                    settings_changed |= egui::color_picker::color_edit_button_srgb(ui, &mut self.settings.accent_color).changed();
+                    let default_accent = Settings::default().accent_color;
+                    if ui.add_enabled(self.settings.accent_color != default_accent, egui::Button::new("恢复默认")).clicked() {
+                        self.settings.accent_color = default_accent;
+                        settings_changed = true;
+                    }
+                });
+                settings_changed |= ui.checkbox(&mut self.settings.confirm_delete, "删除前确认").changed();
+                settings_changed |= ui.checkbox(&mut self.settings.forbid_future_dates, "禁止未来日期").changed();
+                settings_changed |= ui.checkbox(&mut self.settings.show_time, "表格显示录入时间").changed();
+                settings_changed |= ui.checkbox(&mut self.settings.show_hourly_rate, "表格显示时薪列")
+                    .on_hover_text("收入 ÷ 时长，没有填时长的记录留空")
+                    .changed();
+                settings_changed |= ui.checkbox(&mut self.settings.zebra_stripes, "表格隔行背景").changed();
+                settings_changed |= ui.checkbox(&mut self.settings.column_dividers, "表格列分隔线").changed();
+                ui.horizontal(|ui| {
+                    ui.label("日期显示");
+                    settings_changed |= ui.radio_value(&mut self.settings.date_display, DateDisplay::Iso, "2024-06-01").changed();
+                    settings_changed |= ui.radio_value(&mut self.settings.date_display, DateDisplay::Slash, "06/01").changed();
+                    settings_changed |= ui.radio_value(&mut self.settings.date_display, DateDisplay::Chinese, "6月1日").changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("联想数量");
+                    suggestion_limit_changed = ui.add(egui::DragValue::new(&mut self.settings.suggestion_limit).range(3..=20).suffix(" 项")).changed();
+                    settings_changed |= suggestion_limit_changed;
+                });
+                settings_changed |= ui.checkbox(&mut self.settings.remember_input, "记住上次输入")
+                    .on_hover_text("添加后保留标记为“固定”的老板、游戏、时长")
+                    .changed();
+                #[cfg(windows)]
+                {
+                    settings_changed |= ui.checkbox(&mut self.settings.close_to_tray, "关闭时最小化到托盘").changed();
+                }
+                ui.horizontal(|ui| {
+                    settings_changed |= ui.checkbox(&mut self.settings.global_hotkey, "全局快捷键").changed();
+                    settings_changed |= ui.add_enabled(
+                        self.settings.global_hotkey,
+                        egui::TextEdit::singleline(&mut self.settings.hotkey_combo).desired_width(120.0),
+                    ).on_hover_text("在任意程序中按下后切换到记账本并聚焦老板输入框，格式如 Ctrl+Alt+J").changed();
+                    if self.settings.global_hotkey && self.hotkey_failed.as_deref() == Some(self.settings.hotkey_combo.trim()) {
+                        ui.label(RichText::new("无法注册").size(12.0).color(danger_color));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("计时取整");
+                    settings_changed |= ui.radio_value(&mut self.settings.duration_rounding, DurationRounding::Nearest0_1, "0.1h 四舍五入").changed();
+                    settings_changed |= ui.radio_value(&mut self.settings.duration_rounding, DurationRounding::Up0_5, "0.5h 向上").changed();
+                    settings_changed |= ui.radio_value(&mut self.settings.duration_rounding, DurationRounding::Nearest0_5, "0.5h 四舍五入").changed();
+                });
+                ui.horizontal(|ui| {
+                    let toggled = ui.checkbox(&mut self.settings.session_reminder, "计时提醒，每").changed();
+                    let interval_changed = ui.add_enabled(
+                        self.settings.session_reminder,
+                        egui::DragValue::new(&mut self.settings.reminder_minutes).range(5..=600).suffix(" 分钟"),
+                    ).changed();
+                    // 重新从下一个整间隔开始计算
+                    if toggled || interval_changed {
+                        for timer in &mut self.timers {
+                            timer.next_reminder = None;
+                        }
+                    }
+                    settings_changed |= toggled || interval_changed;
+                });
+                ui.horizontal(|ui| {
+                    ui.label("每周开始");
+                    let before = self.settings.week_start;
+                    ui.radio_value(&mut self.settings.week_start, WeekStart::Monday, "周一");
+                    ui.radio_value(&mut self.settings.week_start, WeekStart::Sunday, "周日");
+                    week_start_changed = self.settings.week_start != before;
+                    settings_changed |= week_start_changed;
+                });
+                ui.horizontal(|ui| {
+                    ui.label("日切时间");
+                    settings_changed |= ui.add(egui::DragValue::new(&mut self.settings.day_start_hour).range(0..=12).suffix(" 点"))
+                        .on_hover_text("早于该时间的记录算作前一天，如设为 4 点，凌晨 1 点属于前一天（月初凌晨计入上个月）")
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    settings_changed |= ui.checkbox(&mut self.settings.auto_lock, "无操作").changed();
+                    settings_changed |= ui.add_enabled(
+                        self.settings.auto_lock,
+                        egui::DragValue::new(&mut self.settings.lock_minutes).range(1..=240).suffix(" 分钟"),
+                    ).changed();
+                    ui.label("后锁定");
+                });
+                if self.settings.auto_lock && !self.db_encrypted {
+                    // 只保存 PIN 的哈希，输入框不回显已设置的 PIN
+                    ui.horizontal(|ui| {
+                        ui.label("解锁 PIN");
+                        ui.add(egui::TextEdit::singleline(&mut self.pin_input)
+                            .password(true)
+                            .hint_text(if self.settings.has_lock_pin() { "已设置" } else { "" })
+                            .desired_width(120.0));
+                        if ui.add_enabled(!self.pin_input.is_empty(), egui::Button::new("设置")).clicked() {
+                            self.settings.set_lock_pin(&self.pin_input);
+                            self.pin_input.clear();
+                            settings_changed = true;
+                        }
+                        if self.settings.has_lock_pin() {
+                            if ui.button("清除").clicked() {
+                                self.settings.set_lock_pin("");
+                                settings_changed = true;
+                            }
+                        } else {
+                            ui.label(RichText::new("设置 PIN 后生效").size(12.0).color(theme.text_muted));
+                        }
+                    });
+                }
+                ui.horizontal(|ui| {
+                    ui.label("月收入目标");
+                    settings_changed |= ui.add(egui::DragValue::new(&mut self.settings.monthly_goal)
+                        .range(0.0..=100_000_000.0)
+                        .speed(100.0)
+                        .prefix(&money.symbol))
+                        .on_hover_text("设为 0 不显示进度条")
+                        .changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("常用金额");
+                    let response = ui.add(egui::TextEdit::singleline(&mut self.income_presets_text).desired_width(160.0))
+                        .on_hover_text(format!("空格分隔，最多 {} 个，显示在收入框旁", MAX_INCOME_PRESETS));
+                    if response.lost_focus() {
+                        let presets: Vec<f64> = self.income_presets_text
+                            .split([' ', ',', '，', '、'])
+                            .filter_map(parse_income)
+                            .take(MAX_INCOME_PRESETS)
+                            .collect();
+                        self.income_presets_text = format_presets(&presets);
+                        if presets != self.settings.income_presets {
+                            self.settings.income_presets = presets;
+                            settings_changed = true;
+                        }
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("单笔上限");
+                    settings_changed |= ui.add(egui::DragValue::new(&mut self.settings.max_income)
+                        .range(1.0..=100_000_000.0)
+                        .speed(100.0)
+                        .prefix(&money.symbol)).changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("金额显示");
+                    settings_changed |= ui.add(egui::TextEdit::singleline(&mut self.settings.currency_symbol)
+                        .desired_width(36.0)
+                        .char_limit(4))
+                        .on_hover_text("主币种符号，可留空")
+                        .changed();
+                    settings_changed |= ui.add(egui::DragValue::new(&mut self.settings.decimals).range(0..=4).suffix(" 位小数")).changed();
+                    settings_changed |= ui.checkbox(&mut self.settings.chinese_units, "万/亿单位").changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("界面密度");
+                    settings_changed |= ui.radio_value(&mut self.settings.density, Density::Compact, "紧凑").changed();
+                    settings_changed |= ui.radio_value(&mut self.settings.density, Density::Standard, "标准").changed();
+                    settings_changed |= ui.radio_value(&mut self.settings.density, Density::Comfortable, "宽松").changed();
+                });
+                ui.horizontal(|ui| {
+                    ui.label("内容宽度");
+                    settings_changed |= ui.checkbox(&mut self.settings.auto_width, "自适应窗口").changed();
+                    settings_changed |= ui.add_enabled(!self.settings.auto_width, egui::Slider::new(&mut self.settings.content_width, CONTENT_WIDTH_RANGE)
+                        .step_by(10.0)
+                        .suffix("px")).changed();
+                });
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("数据检查").clicked() {
+                        open_integrity = true;
+                    }
+                    if ui.button("游戏设置").clicked() {
+                        open_game_settings = true;
+                    }
+                    if ui.button("计时记录").clicked() {
+                        open_timer_sessions = true;
+                    }
+                    if ui.button("操作记录").clicked() {
+                        open_audit_log = true;
+                    }
+                    if ui.button("关于").clicked() {
+                        open_about = true;
+                    }
+                    #[cfg(feature = "encryption")]
+                    {
+                        let label = if self.db_encrypted { "修改密码" } else { "设置密码" };
+                        if ui.button(label).clicked() {
+                            open_password = true;
+                        }
+                    }
+                });
+            });
+        if settings_changed {
+            let _ = self.settings.save();
+            self.money = MoneyFormat::from_settings(&self.settings);
+        }
+        if week_start_changed {
+            self.week_balance = Self::load_week_balance(&self.db, self.today(), self.settings.week_start.weekday());
+        }
+        if suggestion_limit_changed {
+            self.recent_bosses = self.db.recent_bosses(self.settings.suggestion_limit());
+            self.recent_games = self.db.recent_games(self.settings.suggestion_limit());
+        }
+        if open_integrity {
+            self.show_integrity = true;
+            self.run_integrity_check();
+        }
+        if open_game_settings {
+            self.open_game_settings();
+        }
+        if open_timer_sessions {
+            self.open_timer_sessions();
+        }
+        if open_audit_log {
+            self.open_audit_log();
+        }
+        if open_about {
+            self.open_about();
+        }
+
+        // ===== 计时记录 =====
+        let mut timer_sessions_open = self.timer_sessions.is_some();
+        let mut export_sessions = false;
+        if let Some(sessions) = &self.timer_sessions {
+            egui::Window::new("计时记录")
+                .open(&mut timer_sessions_open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    if sessions.is_empty() {
+                        ui.label(RichText::new("还没有计时记录，结束计时后会自动保存").color(text_secondary));
+                        return;
+                    }
+                    egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                        egui::Grid::new("timer_sessions_grid").num_columns(4).spacing([16.0, 6.0]).striped(true).show(ui, |ui| {
+                            ui.label("开始");
+                            ui.label("结束");
+                            ui.label("时长");
+                            ui.label("关联记录");
+                            ui.end_row();
+                            for session in sessions {
+                                let secs = session.seconds.max(0);
+                                ui.label(&session.start);
+                                ui.label(&session.end);
+                                ui.label(format!("{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60));
+                                ui.label(session.linked_record_id.map(|id| format!("#{}", id)).unwrap_or_else(|| "-".to_string()));
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    if ui.button("导出 CSV").clicked() {
+                        export_sessions = true;
+                    }
+                });
+        }
+        if export_sessions {
+            self.export_timer_sessions();
+        }
+        if !timer_sessions_open {
+            self.timer_sessions = None;
+        }
+
+        // ===== 操作记录 =====
+        let mut audit_log_open = self.audit_log.is_some();
+        let mut purge_audit = false;
+        if let Some(entries) = &self.audit_log {
+            egui::Window::new("操作记录")
+                .open(&mut audit_log_open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    if entries.is_empty() {
+                        ui.label(RichText::new("还没有操作记录").color(text_secondary));
+                    } else {
+                        ui.label(RichText::new(format!("最近 {} 条", entries.len())).size(12.0).color(text_secondary));
+                        egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                            egui::Grid::new("audit_log_grid").num_columns(4).spacing([16.0, 6.0]).striped(true).show(ui, |ui| {
+                                ui.label("时间");
+                                ui.label("操作");
+                                ui.label("记录");
+                                ui.label("内容");
+                                ui.end_row();
+                                for entry in entries {
+                                    ui.label(&entry.ts);
+                                    ui.label(&entry.action);
+                                    ui.label(entry.record_id.map(|id| format!("#{}", id)).unwrap_or_else(|| "-".to_string()));
+                                    ui.label(&entry.details);
+                                    ui.end_row();
                                 }
                             });
+                        });
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("清除");
+                        ui.add(egui::DragValue::new(&mut self.audit_purge_days).range(1..=3650).suffix(" 天"));
+                        ui.label("之前的记录");
+                        if ui.button("清除").clicked() {
+                            purge_audit = true;
+                        }
+                    });
+                });
+        }
+        if purge_audit {
+            self.purge_audit_log();
+        }
+        if !audit_log_open {
+            self.audit_log = None;
+        }
 
-                            // 游戏列
-                            ui.vertical(|ui| {
-                                ui.set_width(game_width);
-                                ui.label(RichText::new("游戏").color(text_secondary).size(label_size));
-                                ui.add_space(4.0);
-                                let game_response = ui.add_sized(
-                                    [game_width, input_height],
-                                    egui::TextEdit::singleline(&mut self.input_game)
-                                        .font(FontId::proportional(input_font_size))
-                                        .margin(egui::Margin::symmetric(8, 8))
-                                );
-                                if game_response.gained_focus() {
-                                    self.show_game_suggestions = true;
-                                }
-                                let mut game_suggestion_clicked = false;
-                                if self.show_game_suggestions && !self.game_list.is_empty() {
-                                    let input_lower = self.input_game.to_lowercase();
-                                    let suggestions: Vec<_> = self.game_list.iter()
-                                        .filter(|g| input_lower.is_empty() || g.to_lowercase().contains(&input_lower))
-                                        .take(6).cloned().collect();
-                                    if !suggestions.is_empty() {
-                                        egui::Area::new(egui::Id::new("game_suggestions"))
-                                            .order(egui::Order::Foreground)
-                                            .fixed_pos(game_response.rect.left_bottom() + Vec2::new(0.0, 4.0))
-                                            .show(ui.ctx(), |ui| {
-                                                egui::Frame::default()
-                                                    .fill(Color32::from_rgb(50, 55, 65))
-                                                    .corner_radius(CornerRadius::same(6))
-                                                    .stroke(Stroke::new(1.0, Color32::from_rgb(70, 75, 85)))
-                                                    .shadow(egui::epaint::Shadow { offset: [0, 2], blur: 8, spread: 0, color: Color32::from_black_alpha(60) })
-                                                    .inner_margin(4)
-                                                    .show(ui, |ui| {
-                                                        ui.set_width(game_width - 8.0);
-                                                        for game in &suggestions {
-                                                            let btn = egui::Button::new(RichText::new(game).size(14.0).color(text_primary))
-                                                                .fill(Color32::TRANSPARENT).stroke(Stroke::NONE).corner_radius(CornerRadius::same(4));
-                                                            if ui.add_sized([game_width - 16.0, 28.0], btn).clicked() {
-                                                                self.input_game = game.clone();
-                                                                game_suggestion_clicked = true;
-                                                            }
-                                                        }
-                                                    });
-                                            });
-                                    }
-                                }
-                                if game_suggestion_clicked {
-                                    self.show_game_suggestions = false;
-                                } else if self.show_game_suggestions && !game_response.has_focus() && ui.ctx().input(|i| i.pointer.any_click()) {
-                                    self.show_game_suggestions = false;
+        // ===== 关于 =====
+        let mut about_open = self.about.is_some();
+        let mut open_data_dir = false;
+        let mut backup = false;
+        if let Some(stats) = &self.about {
+            egui::Window::new("关于")
+                .open(&mut about_open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label(RichText::new(format!("{} v{}", APP_TITLE, env!("CARGO_PKG_VERSION"))).size(16.0).color(text_primary));
+                    ui.add_space(8.0);
+                    egui::Grid::new("about_grid").num_columns(2).spacing([16.0, 6.0]).show(ui, |ui| {
+                        ui.label(RichText::new("记录条数").color(text_secondary));
+                        ui.label(stats.count.to_string());
+                        ui.end_row();
+                        ui.label(RichText::new("日期范围").color(text_secondary));
+                        ui.label(match (&stats.first_date, &stats.last_date) {
+                            (Some(first), Some(last)) => format!("{} 至 {}", first, last),
+                            _ => "-".to_string(),
+                        });
+                        ui.end_row();
+                        ui.label(RichText::new("数据库大小").color(text_secondary));
+                        ui.label(format_file_size(stats.file_size));
+                        ui.end_row();
+                    });
+                    ui.add_space(8.0);
+                    ui.horizontal(|ui| {
+                        open_data_dir = ui.button("打开数据目录").on_hover_text("数据库所在的文件夹，可手动备份").clicked();
+                        backup = ui.button("导出 JSON 备份").clicked();
+                    });
+                    // 打开失败时显示路径，可以选中或复制后手动前往
+                    if let Some(path) = &self.data_dir_failed {
+                        ui.label(RichText::new("无法打开文件管理器，数据目录：").size(12.0).color(danger_color));
+                        ui.horizontal(|ui| {
+                            let mut text = path.as_str();
+                            ui.add(egui::TextEdit::singleline(&mut text).desired_width(280.0));
+                            if ui.small_button("复制").clicked() {
+                                ui.ctx().copy_text(path.clone());
+                            }
+                        });
+                    }
+                });
+        }
+        if open_data_dir {
+            self.open_data_dir();
+        }
+        if backup {
+            self.export_json();
+        }
+        if !about_open {
+            self.about = None;
+        }
+
+        // ===== 游戏设置 =====
+        let mut game_settings_open = self.game_settings.is_some();
+        let mut save_game_defaults = false;
+        if let Some(rows) = &mut self.game_settings {
+            egui::Window::new("游戏设置")
+                .open(&mut game_settings_open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    if rows.is_empty() {
+                        ui.label(RichText::new("还没有带游戏的记录").color(text_secondary));
+                        return;
+                    }
+                    ui.label(RichText::new("选中游戏时预填默认时长；老板未设置时薪时按单价估算收入")
+                        .size(12.0)
+                        .color(text_secondary));
+                    egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                        egui::Grid::new("game_defaults_grid").num_columns(3).spacing([12.0, 8.0]).show(ui, |ui| {
+                            ui.label("游戏");
+                            ui.label("默认时长");
+                            ui.label("单价/h");
+                            ui.end_row();
+                            for row in rows.iter_mut() {
+                                ui.label(&row.game);
+                                ui.add(egui::DragValue::new(&mut row.default.duration).range(0.0..=24.0).speed(0.5).suffix("h"));
+                                ui.add(egui::DragValue::new(&mut row.default.rate).range(0.0..=100_000.0).speed(1.0).prefix(&money.symbol));
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    if ui.button("保存").clicked() {
+                        save_game_defaults = true;
+                    }
+                });
+        }
+        if save_game_defaults {
+            self.save_game_defaults();
+        }
+        if !game_settings_open {
+            self.game_settings = None;
+        }
+        #[cfg(feature = "encryption")]
+        if open_password {
+            self.password_form = Some(PasswordForm::default());
+        }
+
+        // ===== 设置密码 =====
+        #[cfg(feature = "encryption")]
+        {
+            let mut password_open = self.password_form.is_some();
+            let mut submit = false;
+            if let Some(form) = &mut self.password_form {
+                egui::Window::new("设置密码")
+                    .open(&mut password_open)
+                    .collapsible(false)
+                    .resizable(false)
+                    .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                    .show(ctx, |ui| {
+                        ui.set_width(280.0);
+                        egui::Grid::new("password_grid").num_columns(2).spacing([8.0, 8.0]).show(ui, |ui| {
+                            ui.label("新密码");
+                            ui.add(egui::TextEdit::singleline(&mut form.password).password(true));
+                            ui.end_row();
+                            ui.label("确认密码");
+                            ui.add(egui::TextEdit::singleline(&mut form.confirm).password(true));
+                            ui.end_row();
+                        });
+                        ui.label(RichText::new("密码遗忘后数据无法恢复").size(12.0).color(danger_color));
+                        if ui.button("确定").clicked() {
+                            submit = true;
+                        }
+                    });
+            }
+            if !password_open {
+                self.password_form = None;
+            }
+            if submit {
+                self.set_password();
+            }
+        }
+
+        // ===== 待结算 =====
+        let mut report_open = self.unsettled_report.is_some();
+        let mut settle_boss_from_report: Option<String> = None;
+        if let Some(rows) = &self.unsettled_report {
+            egui::Window::new("待结算")
+                .open(&mut report_open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    if rows.is_empty() {
+                        ui.label(RichText::new("所有记录都已结清").color(text_secondary));
+                        return;
+                    }
+                    let total = round2(rows.iter().map(|(_, owed)| owed).sum());
+                    ui.label(RichText::new(format!("{} 位老板 · 共 {}", rows.len(), money.money(total)))
+                        .size(13.0)
+                        .color(text_secondary));
+                    ui.add_space(6.0);
+                    egui::ScrollArea::vertical().max_height(360.0).show(ui, |ui| {
+                        egui::Grid::new("unsettled_grid").num_columns(3).spacing([16.0, 8.0]).striped(true).show(ui, |ui| {
+                            for (boss, owed) in rows {
+                                ui.label(boss);
+                                ui.label(RichText::new(money.money(*owed)).color(theme.warning_color));
+                                if ui.small_button("标记已结清").clicked() {
+                                    settle_boss_from_report = Some(boss.clone());
                                 }
-                            });
+                                ui.end_row();
+                            }
+                        });
+                    });
+                });
+        }
+        if !report_open {
+            self.unsettled_report = None;
+        }
+        if let Some(boss) = settle_boss_from_report {
+            self.pending_settle = Some(PendingSettle { boss: Some(boss), period: None, settled: true });
+        }
 
-                            // 时长列
-                            ui.vertical(|ui| {
-                                ui.set_width(duration_width);
-                                ui.label(RichText::new("时长/h").color(text_secondary).size(label_size));
-                                ui.add_space(4.0);
-                                ui.add_sized([duration_width, input_height],
-                                    egui::TextEdit::singleline(&mut self.input_duration)
-                                        .font(FontId::proportional(input_font_size))
-                                        .margin(egui::Margin::symmetric(6, 8))
-                                        .char_limit(5)
-                                );
+        // ===== 老板详情 =====
+        let mut detail_open = self.boss_detail.is_some();
+        let mut save_note = false;
+        let mut rename = false;
+        let mut export_invoice = false;
+        let mut save_rate = false;
+        let mut settle_boss: Option<bool> = None;
+        if let Some(detail) = &mut self.boss_detail {
+            egui::Window::new("老板详情")
+                .open(&mut detail_open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.spacing_mut().item_spacing.y = 10.0;
+                    ui.label(RichText::new(&detail.name).size(18.0).color(text_primary));
+                    ui.label(RichText::new(format!("共 {} 单 · 累计 {}", detail.count, money.money(detail.total)))
+                        .size(13.0)
+                        .color(text_secondary));
+                    ui.label("备注");
+                    ui.add(egui::TextEdit::multiline(&mut detail.note)
+                        .hint_text("如：只玩国服，晚上在线")
+                        .desired_rows(3)
+                        .desired_width(280.0));
+                    if ui.button("保存备注").clicked() {
+                        save_note = true;
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.label("时薪");
+                        ui.add(egui::DragValue::new(&mut detail.rate.rate).range(0.0..=100_000.0).speed(1.0).prefix(&money.symbol));
+                        ui.label("取整到");
+                        egui::ComboBox::from_id_salt("round_step")
+                            .selected_text(if detail.rate.round_step > 0.0 { format!("{}", detail.rate.round_step) } else { "不取整".to_string() })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut detail.rate.round_step, 0.0, "不取整");
+                                for step in [10.0, 50.0, 100.0] {
+                                    ui.selectable_value(&mut detail.rate.round_step, step, format!("{}", step));
+                                }
                             });
-
-                            // 收入列（必填）
-                            ui.vertical(|ui| {
-                                ui.set_width(income_width);
-                                ui.horizontal(|ui| {
-                                    ui.spacing_mut().item_spacing = Vec2::ZERO;
-                                    ui.label(RichText::new("收入").color(text_secondary).size(label_size));
-                                    ui.label(RichText::new("*").color(danger_color).size(label_size));
-                                });
-                                ui.add_space(4.0);
-                                // 使用 scope 限制样式修改范围
-                                let income_response = ui.scope(|ui| {
-                                    if self.input_income_error {
-                                        ui.visuals_mut().widgets.inactive.bg_stroke = Stroke::new(1.0, danger_color);
-                                        ui.visuals_mut().widgets.hovered.bg_stroke = Stroke::new(1.0, danger_color);
-                                    }
-                                    ui.add_sized(
-                                        [income_width, input_height],
-                                        egui::TextEdit::singleline(&mut self.input_income)
-                                            .font(FontId::proportional(input_font_size))
-                                            .margin(egui::Margin::symmetric(6, 8))
-                                            .char_limit(10)
-                                    )
-                                }).inner;
-                                // 获得焦点时清除错误状态
-                                if income_response.gained_focus() {
-                                    self.input_income_error = false;
+                        if ui.button("保存").clicked() {
+                            save_rate = true;
+                        }
+                    });
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button("全部结清").clicked() {
+                            settle_boss = Some(true);
+                        }
+                        if ui.button("全部取消结清").clicked() {
+                            settle_boss = Some(false);
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("改名");
+                        ui.add(egui::TextEdit::singleline(&mut detail.rename).desired_width(160.0));
+                        let can_rename = !detail.rename.trim().is_empty() && detail.rename.trim() != detail.name;
+                        if ui.add_enabled(can_rename, egui::Button::new("确定")).clicked() {
+                            rename = true;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("月度账单");
+                        egui::ComboBox::from_id_salt("invoice_month")
+                            .selected_text(&detail.invoice_month)
+                            .show_ui(ui, |ui| {
+                                for month in &detail.months {
+                                    ui.selectable_value(&mut detail.invoice_month, month.clone(), month);
                                 }
                             });
+                        if ui.add_enabled(!detail.months.is_empty(), egui::Button::new("导出 PDF")).clicked() {
+                            export_invoice = true;
+                        }
+                    });
+                });
+        }
+        if let Some((name, note, new_name, month, rate)) = self.boss_detail.as_ref()
+            .map(|d| (d.name.clone(), d.note.clone(), d.rename.clone(), d.invoice_month.clone(), d.rate))
+        {
+            if save_note {
+                self.save_boss_note(&name, &note);
+            }
+            if save_rate {
+                self.save_boss_rate(&name, &rate);
+            }
+            if let Some(settled) = settle_boss {
+                self.pending_settle = Some(PendingSettle { boss: Some(name.clone()), period: None, settled });
+            }
+            if rename {
+                self.rename_boss(&name, &new_name);
+            }
+            if export_invoice {
+                self.export_boss_invoice(&name, &month);
+            }
+        }
+        if !detail_open {
+            self.boss_detail = None;
+        }
 
-                            // 结清列
-                            ui.vertical(|ui| {
-                                ui.set_width(checkbox_width);
-                                ui.label(RichText::new("结清").color(text_secondary).size(label_size));
-                                ui.add_space(4.0);
-                                ui.add_space(10.0);
-                                ui.scope(|ui| {
-                                    if self.input_settled {
-                                        ui.visuals_mut().widgets.inactive.bg_fill = danger_color;
-                                        ui.visuals_mut().widgets.inactive.fg_stroke = Stroke::new(2.0, Color32::WHITE);
-                                        ui.visuals_mut().widgets.hovered.bg_fill = danger_color;
-                                        ui.visuals_mut().widgets.hovered.fg_stroke = Stroke::new(2.0, Color32::WHITE);
+        // ===== CSV 导入向导 =====
+        let mut import_open = self.csv_import.is_some();
+        let mut import_confirmed = false;
+        let max_income = self.settings.max_income;
+        if let Some(wizard) = &mut self.csv_import {
+            egui::Window::new("导入 CSV")
+                .open(&mut import_open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.set_width(560.0);
+                    ui.label(RichText::new(&wizard.file_name).color(text_secondary));
+                    ui.checkbox(&mut wizard.has_header, "第一行是表头");
+
+                    // 列映射
+                    let names = wizard.column_names();
+                    let fields: [(&str, &mut Option<usize>, bool); 7] = [
+                        ("日期", &mut wizard.mapping.date, true),
+                        ("老板", &mut wizard.mapping.boss, true),
+                        ("金额", &mut wizard.mapping.income, true),
+                        ("游戏", &mut wizard.mapping.game, false),
+                        ("时长", &mut wizard.mapping.duration, false),
+                        ("币种", &mut wizard.mapping.currency, false),
+                        ("结清", &mut wizard.mapping.settled, false),
+                    ];
+                    egui::Grid::new("csv_mapping").num_columns(2).spacing([12.0, 6.0]).show(ui, |ui| {
+                        for (label, column, required) in fields {
+                            ui.label(if required { format!("{} *", label) } else { label.to_string() });
+                            let selected = column.and_then(|i| names.get(i)).map(String::as_str).unwrap_or("（不导入）");
+                            egui::ComboBox::from_id_salt(("csv_column", label))
+                                .width(200.0)
+                                .selected_text(selected)
+                                .show_ui(ui, |ui| {
+                                    if !required {
+                                        ui.selectable_value(column, None, "（不导入）");
+                                    }
+                                    for (i, name) in names.iter().enumerate() {
+                                        ui.selectable_value(column, Some(i), name);
                                     }
-                                    ui.add_sized([checkbox_width, 20.0], egui::Checkbox::new(&mut self.input_settled, ""));
                                 });
-                            });
-
-                            // 添加按钮
-                            ui.vertical(|ui| {
-                                ui.set_width(btn_width);
-                                ui.add_space(17.0 + 4.0);
-                                let btn = egui::Button::new(RichText::new("添加").font(FontId::proportional(14.0)).color(Color32::WHITE))
-                                    .fill(accent_color)
-                                    .corner_radius(CornerRadius::same(6));
-                                if ui.add_sized([btn_width, input_height], btn).clicked() {
-                                    self.add_record();
+                            ui.end_row();
+                        }
+                        ui.label("日期格式");
+                        egui::ComboBox::from_id_salt("csv_date_format")
+                            .width(200.0)
+                            .selected_text(wizard.date_format.label())
+                            .show_ui(ui, |ui| {
+                                for fmt in DateFormat::ALL {
+                                    ui.selectable_value(&mut wizard.date_format, fmt, fmt.label());
                                 }
                             });
-                            }); // 结束 vertical, horizontal
+                        ui.end_row();
+                    });
 
-                        // 处理日期变化
-                        if set_today {
-                            self.input_date = Local::now().date_naive();
-                        } else {
-                            let max_day = days_in_month(new_year, new_month);
-                            let valid_day = new_day.min(max_day);
-                            if let Some(date) = NaiveDate::from_ymd_opt(new_year, new_month, valid_day) {
-                                self.input_date = date;
+                    // 预览前几行的解析结果
+                    ui.separator();
+                    let parsed = wizard.parse_rows(max_income, &money);
+                    let valid = parsed.iter().filter(|r| r.is_ok()).count();
+                    ui.label(RichText::new(format!("预览（共 {} 行，可导入 {} 行）", parsed.len(), valid)).color(text_secondary));
+                    egui::Grid::new("csv_preview").striped(true).spacing([16.0, 4.0]).show(ui, |ui| {
+                        for title in ["日期", "老板", "游戏", "时长", "金额", "结清"] {
+                            ui.label(RichText::new(title).color(text_secondary));
+                        }
+                        ui.end_row();
+                        for result in parsed.iter().take(5) {
+                            match result {
+                                Ok(r) => {
+                                    ui.label(&r.date);
+                                    ui.label(&r.boss);
+                                    ui.label(r.game.as_deref().unwrap_or("-"));
+                                    ui.label(format_duration(r.duration));
+                                    ui.label(money.money_in(r.income, &r.currency));
+                                    ui.label(if r.settled { "是" } else { "否" });
+                                }
+                                Err(e) => {
+                                    ui.label(RichText::new(e).color(danger_color));
+                                }
                             }
+                            ui.end_row();
                         }
                     });
+
+                    ui.separator();
+                    let can_import = wizard.mapping.is_complete() && valid > 0;
+                    let import_btn = egui::Button::new(RichText::new(format!("导入 {} 条", valid)).color(Color32::WHITE))
+                        .fill(accent_color)
+                        .corner_radius(CornerRadius::same(6));
+                    if ui.add_enabled(can_import, import_btn).clicked() {
+                        import_confirmed = true;
+                    }
                 });
+        }
+        if import_confirmed {
+            self.commit_csv_import();
+        } else if !import_open {
+            self.csv_import = None;
+        }
 
-                ui.add_space(24.0);
+        // ===== 数据检查 =====
+        let mut integrity_open = self.show_integrity;
+        let mut dates_to_fix: Vec<(i64, String)> = Vec::new();
+        let mut jump_to: Option<String> = None;
+        let mut delete_id: Option<i64> = None;
+        let mut recheck = false;
+        egui::Window::new("数据检查")
+            .open(&mut integrity_open)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.set_width(460.0);
+                let fixable: Vec<(i64, String)> = self.integrity_issues.iter()
+                    .filter_map(|issue| match &issue.kind {
+                        IssueKind::BadDate { fixed: Some(date) } => Some((issue.record_id, date.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("发现 {} 个问题", self.integrity_issues.len())).color(text_secondary));
+                    if ui.button("重新检查").clicked() {
+                        recheck = true;
+                    }
+                    if !fixable.is_empty() && ui.button(format!("修复日期格式（{} 条）", fixable.len())).clicked() {
+                        dates_to_fix = fixable.clone();
+                    }
+                });
+                ui.separator();
+                if self.integrity_issues.is_empty() {
+                    ui.label(RichText::new("未发现问题").color(green_color));
+                    return;
+                }
+                egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+                    for issue in &self.integrity_issues {
+                        let desc = match &issue.kind {
+                            IssueKind::EmptyBoss => "老板名为空".to_string(),
+                            IssueKind::InvalidIncome(None) => "收入为空".to_string(),
+                            IssueKind::InvalidIncome(Some(v)) => format!("收入异常：{}", v),
+                            IssueKind::BadDate { fixed: Some(date) } => format!("日期格式错误，可修正为 {}", date),
+                            IssueKind::BadDate { fixed: None } => "日期无法识别".to_string(),
+                        };
+                        ui.horizontal(|ui| {
+                            ui.label(RichText::new(format!("#{} {} {}", issue.record_id, issue.date, issue.boss)).color(text_primary));
+                            ui.label(RichText::new(desc).color(danger_color));
+                            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                                if ui.small_button("删除").clicked() {
+                                    delete_id = Some(issue.record_id);
+                                }
+                                if let IssueKind::BadDate { fixed: Some(date) } = &issue.kind {
+                                    if ui.small_button("修复").clicked() {
+                                        dates_to_fix = vec![(issue.record_id, date.clone())];
+                                    }
+                                }
+                                if let Some(date) = normalize_date(&issue.date) {
+                                    if ui.small_button("定位").clicked() {
+                                        jump_to = Some(date);
+                                    }
+                                }
+                            });
+                        });
+                    }
+                });
+            });
+        self.show_integrity = integrity_open;
+        if !dates_to_fix.is_empty() {
+            self.fix_dates(&dates_to_fix);
+        }
+        if let Some(date) = jump_to {
+            self.jump_to_date(&date);
+        }
+        if let Some(id) = delete_id {
+            self.request_delete(id);
+        }
+        if recheck {
+            self.run_integrity_check();
+        }
 
-                // ===== 表格区域 =====
-                ui.vertical(|ui| {
-                    ui.set_width(cards_width);
-                    egui::Frame::default()
-                        .fill(card_color)
-                        .corner_radius(CornerRadius::same(layout.card_rounding as u8))
-                        .inner_margin(layout.card_inner_margin as i8)
-                        .show(ui, |ui| {
-                            let table_inner_w = cards_width - (layout.card_inner_margin * 2.0);
-                            ui.set_width(table_inner_w);
-                        let table_w = table_inner_w;
-                        // 让表格占据剩余所有高度
-                        let remaining_height = ui.available_height();
-                        ui.set_min_height(remaining_height.max(390.0));
+        // ===== 删除确认 =====
+        if let Some(id) = self.pending_delete {
+            let summary = self.records.iter()
+                .find(|r| r.id == id)
+                .map(|r| format!("{}  {}  {}", r.date, r.boss, money.money_in(r.income, &r.currency)))
+                .unwrap_or_default();
+            let mut confirmed = false;
+            let mut cancelled = false;
+            let modal = egui::Modal::new(egui::Id::new("confirm_delete")).show(ctx, |ui| {
+                ui.set_width(280.0);
+                ui.label(RichText::new("确定删除这条记录？").size(16.0).color(text_primary));
+                ui.add_space(6.0);
+                ui.label(RichText::new(summary).size(13.0).color(text_secondary));
+                ui.add_space(12.0);
+                ui.horizontal(|ui| {
+                    let del_btn = egui::Button::new(RichText::new("删除").color(Color32::WHITE))
+                        .fill(danger_color)
+                        .corner_radius(CornerRadius::same(6));
+                    if ui.add_sized([64.0, 28.0], del_btn).clicked() {
+                        confirmed = true;
+                    }
+                    if ui.add_sized([64.0, 28.0], egui::Button::new("取消")).clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+            if confirmed {
+                self.pending_delete = None;
+                self.delete_record(id);
+            } else if cancelled || modal.should_close() {
+                self.pending_delete = None;
+            }
+        }
 
-                        // 固定列宽
-                        let col_spacing = layout.col_spacing;
-                        let delete_btn_width = 60.0;
-                        let settled_width = 45.0;
-                        let spacing_total = col_spacing * 7.0;  // 8列有7个间距
-                        let data_width = table_w - delete_btn_width - settled_width - spacing_total;
-                        let col_widths = [
-                            data_width * 0.15,  // 日期
-                            data_width * 0.18,  // 老板
-                            data_width * 0.20,  // 游戏
-                            data_width * 0.10,  // 时长
-                            data_width * 0.17,  // 收入
-                            data_width * 0.20,  // 结余
-                            settled_width,      // 结清
-                            delete_btn_width,   // 操作
-                        ];
+        // ===== 修改录入时间 =====
+        let mut created_at_open = self.created_at_edit.is_some();
+        let mut save_created_at = false;
+        if let Some(edit) = &mut self.created_at_edit {
+            egui::Window::new("修改录入时间")
+                .open(&mut created_at_open)
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, Vec2::ZERO)
+                .show(ctx, |ui| {
+                    ui.label(RichText::new(&edit.summary).size(13.0).color(text_secondary));
+                    ui.add_space(8.0);
+                    let response = ui.scope(|ui| {
+                        if edit.error {
+                            ui.visuals_mut().widgets.inactive.bg_stroke = Stroke::new(1.0, danger_color);
+                            ui.visuals_mut().widgets.hovered.bg_stroke = Stroke::new(1.0, danger_color);
+                        }
+                        ui.add(egui::TextEdit::singleline(&mut edit.text)
+                            .hint_text("2024-06-01 21:30:00")
+                            .desired_width(200.0))
+                    }).inner;
+                    if response.changed() {
+                        edit.error = false;
+                    }
+                    if edit.error {
+                        ui.label(RichText::new("请输入有效时间，如 2024-06-01 21:30").size(12.0).color(danger_color));
+                    }
+                    ui.label(RichText::new("影响同一天内的排序，不改变记录日期").size(12.0).color(theme.text_muted));
+                    ui.add_space(8.0);
+                    save_created_at = ui.button("保存").clicked()
+                        || (response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)));
+                });
+        }
+        if save_created_at {
+            self.save_created_at();
+        } else if !created_at_open {
+            self.created_at_edit = None;
+        }
 
-                        // 表头
-                        ui.horizontal(|ui| {
-                            ui.spacing_mut().item_spacing.x = col_spacing;
-                            ui.add_sized([col_widths[0], 22.0], egui::Label::new(
-                                RichText::new("日期").color(text_secondary).size(14.0)
-                            ));
-                            ui.add_sized([col_widths[1], 22.0], egui::Label::new(
-                                RichText::new("老板").color(text_secondary).size(14.0)
-                            ));
-                            ui.add_sized([col_widths[2], 22.0], egui::Label::new(
-                                RichText::new("游戏").color(text_secondary).size(14.0)
-                            ));
-                            ui.add_sized([col_widths[3], 22.0], egui::Label::new(
-                                RichText::new("时长").color(text_secondary).size(14.0)
-                            ));
-                            ui.add_sized([col_widths[4], 22.0], egui::Label::new(
-                                RichText::new("收入").color(text_secondary).size(14.0)
-                            ));
-                            ui.add_sized([col_widths[5], 22.0], egui::Label::new(
-                                RichText::new("结余").color(text_secondary).size(14.0)
-                            ));
-                            ui.add_sized([col_widths[6], 22.0], egui::Label::new(
-                                RichText::new("结清").color(text_secondary).size(14.0)
-                            ));
-                            ui.add_sized([col_widths[7], 22.0], egui::Label::new(
-                                RichText::new("操作").color(text_secondary).size(14.0)
-                            ));
-                        });
+        // ===== 批量结清确认 =====
+        if let Some(pending) = &self.pending_settle {
+            let title = if pending.settled { "确定全部标记为已结清？" } else { "确定全部取消结清？" };
+            let scope = pending.describe();
+            let mut confirmed = false;
+            let mut cancelled = false;
+            let modal = egui::Modal::new(egui::Id::new("confirm_settle")).show(ctx, |ui| {
+                ui.set_width(280.0);
+                ui.label(RichText::new(title).size(16.0).color(text_primary));
+                ui.add_space(6.0);
+                ui.label(RichText::new(scope).size(13.0).color(text_secondary));
+                ui.add_space(12.0);
+                ui.horizontal(|ui| {
+                    let ok_btn = egui::Button::new(RichText::new("确定").color(Color32::WHITE))
+                        .fill(accent_color)
+                        .corner_radius(CornerRadius::same(6));
+                    if ui.add_sized([64.0, 28.0], ok_btn).clicked() {
+                        confirmed = true;
+                    }
+                    if ui.add_sized([64.0, 28.0], egui::Button::new("取消")).clicked() {
+                        cancelled = true;
+                    }
+                });
+            });
+            if confirmed {
+                if let Some(pending) = self.pending_settle.take() {
+                    self.apply_settle(pending);
+                }
+            } else if cancelled || modal.should_close() {
+                self.pending_settle = None;
+            }
+        }
+    }
+}
 
-                        ui.add_space(10.0);
-                        ui.separator();
-                        ui.add_space(6.0);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-                        // 数据列表（显示选中月份的记录）
-                        let selected_month_str = format!("{}-{:02}", self.selected_year, self.selected_month);
-                        let filtered_records: Vec<Record> = self.records.iter()
-                            .filter(|r| r.date.starts_with(&selected_month_str))
-                            .cloned()
-                            .collect();
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
 
-                        // 计算当月累计结余（按时间正序累计，最新记录显示总累计）
-                        let mut running_balances: Vec<f64> = Vec::new();
-                        let total: f64 = filtered_records.iter().map(|r| r.income).sum();
-                        let mut remaining = total;
-                        for r in &filtered_records {
-                            running_balances.push(remaining);
-                            remaining -= r.income;
-                        }
+    /// 粗略的性能说明：10000 行时每帧只布局可见的几十行，且每行严格占一个行距
+    #[test]
+    fn table_rows_are_virtualized_and_fixed_height() {
+        let rows = 10_000;
+        let row_height = 44.0;
+        let ctx = egui::Context::default();
+        let mut tops = Vec::new();
+        let mut spacing = 0.0;
+        let input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, Vec2::new(800.0, 600.0))),
+            ..Default::default()
+        };
+        let _ = ctx.run(input, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                spacing = ui.spacing().item_spacing.y;
+                egui::ScrollArea::vertical().show_rows(ui, row_height, rows, |ui, range| {
+                    for _ in range {
+                        tops.push(ui.cursor().top());
+                        // 内容比行高还高时也不能撑高这一行
+                        fixed_height_row(ui, row_height, |ui| ui.add_space(row_height * 2.0));
+                    }
+                });
+            });
+        });
+        assert!(tops.len() > 1 && tops.len() < 30, "built {} rows", tops.len());
+        for pair in tops.windows(2) {
+            assert!((pair[1] - pair[0] - (row_height + spacing)).abs() < 0.01, "{tops:?}");
+        }
+    }
 
-                        egui::ScrollArea::vertical()
-                            .auto_shrink([false, false])
-                            .show(ui, |ui| {
-                                if filtered_records.is_empty() {
-                                    ui.add_space(80.0);
-                                    ui.vertical_centered(|ui| {
-                                        ui.label(RichText::new("当月暂无记录")
-                                            .color(text_secondary)
-                                            .size(17.0));
-                                        ui.add_space(8.0);
-                                        ui.label(RichText::new("选择其他月份或添加新记录")
-                                            .color(Color32::from_rgb(100, 105, 115))
-                                            .size(13.0));
-                                    });
-                                } else {
-                                    let mut to_delete: Option<i64> = None;
-                                    let mut to_toggle_settled: Option<(i64, bool)> = None;
-                                    let row_height = 44.0;
+    fn default_money() -> MoneyFormat {
+        MoneyFormat::from_settings(&Settings::default())
+    }
 
-                                    for (idx, record) in filtered_records.iter().enumerate() {
-                                        let row_bg = if idx % 2 == 1 {
-                                            Color32::from_rgb(40, 44, 52)
-                                        } else {
-                                            Color32::TRANSPARENT
-                                        };
+    #[test]
+    fn money_hides_float_drift() {
+        let money = default_money();
+        assert_eq!(money.money(0.1 + 0.2), "¥0.30");
+        assert_eq!(money.money(0.3 - 0.1 - 0.2), "¥0.00");
+        let sum: f64 = std::iter::repeat_n(0.1, 85_000).sum();
+        assert_eq!(money.money(sum), "¥8,500.00");
+    }
 
-                                        egui::Frame::default()
-                                            .fill(row_bg)
-                                            .corner_radius(CornerRadius::same(6))
-                                            .inner_margin(egui::Margin::symmetric(4, 6))
-                                            .show(ui, |ui| {
-                                                ui.horizontal(|ui| {
-                                                    ui.spacing_mut().item_spacing.x = col_spacing;
-                                                    let text_height = row_height - 12.0;
+    #[test]
+    fn wan_and_yi_thresholds() {
+        let money = default_money();
+        assert_eq!(money.amount(99_999.0), "99,999.00");
+        assert_eq!(money.amount(100_000.0), "100,000.00");
+        assert_eq!(money.amount(100_000.5), "10.00万");
+        assert_eq!(money.amount(99_999_900.0), "9999.99万");
+        assert_eq!(money.amount(99_999_999.0), "1.00亿");
+        assert_eq!(money.amount(100_000_000.0), "1.00亿");
+    }
 
-                                                    // 日期
-                                                    ui.add_sized([col_widths[0], text_height], egui::Label::new(
-                                                        RichText::new(&record.date)
-                                                            .color(text_primary)
-                                                            .size(14.0)
-                                                    ));
-                                                    // 老板
-                                                    ui.add_sized([col_widths[1], text_height], egui::Label::new(
-                                                        RichText::new(&record.boss)
-                                                            .color(text_primary)
-                                                            .size(14.0)
-                                                    ));
-                                                    // 游戏
-                                                    let game_text = record.game.as_deref().unwrap_or("-");
-                                                    ui.add_sized([col_widths[2], text_height], egui::Label::new(
-                                                        RichText::new(game_text)
-                                                            .color(text_primary)
-                                                            .size(14.0)
-                                                    ));
-                                                    // 时长
-                                                    let duration_text = match record.duration {
-                                                        Some(d) if d > 0.0 => {
-                                                            if d.fract() == 0.0 {
-                                                                format!("{}h", d as i32)
-                                                            } else {
-                                                                format!("{:.1}h", d)
-                                                            }
-                                                        },
-                                                        _ => "-".to_string(),
-                                                    };
-                                                    ui.add_sized([col_widths[3], text_height], egui::Label::new(
-                                                        RichText::new(duration_text)
-                                                            .color(text_secondary)
-                                                            .size(14.0)
-                                                    ));
-                                                    // 收入
-                                                    ui.add_sized([col_widths[4], text_height], egui::Label::new(
-                                                        RichText::new(format_income(record.income))
-                                                            .color(green_color)
-                                                            .size(14.0)
-                                                    ));
-                                                    // 结余
-                                                    let running_balance = running_balances.get(idx).unwrap_or(&0.0);
-                                                    ui.add_sized([col_widths[5], text_height], egui::Label::new(
-                                                        RichText::new(format_money(*running_balance))
-                                                            .color(text_primary)
-                                                            .size(14.0)
-                                                    ));
+    #[test]
+    fn wan_and_yi_follow_decimals() {
+        let money = MoneyFormat { decimals: 0, ..default_money() };
+        assert_eq!(money.amount(100_000.0), "100,000");
+        assert_eq!(money.amount(100_000.4), "100,000");
+        assert_eq!(money.amount(100_001.0), "10万");
+        assert_eq!(money.amount(99_995_000.0), "1亿");
+        let money = MoneyFormat { decimals: 3, ..default_money() };
+        assert_eq!(money.amount(123_456.0), "12.346万");
+        assert_eq!(money.amount(250_000_000.0), "2.500亿");
+    }
 
-                                                    // 结清勾选框（可点击修改）
-                                                    let mut settled = record.settled;
-                                                    let checkbox_response = ui.scope(|ui| {
-                                                        if settled {
-                                                            ui.visuals_mut().widgets.inactive.bg_fill = danger_color;
-                                                            ui.visuals_mut().widgets.inactive.fg_stroke = Stroke::new(2.0, Color32::WHITE);
-                                                            ui.visuals_mut().widgets.hovered.bg_fill = danger_color;
-                                                            ui.visuals_mut().widgets.hovered.fg_stroke = Stroke::new(2.0, Color32::WHITE);
-                                                        }
-                                                        ui.add_sized([col_widths[6], text_height], egui::Checkbox::new(&mut settled, ""))
-                                                    }).inner;
-                                                    if checkbox_response.changed() {
-                                                        to_toggle_settled = Some((record.id, settled));
-                                                    }
+    #[test]
+    fn wan_threshold_follows_max_income() {
+        // 单笔上限提高后，不超过上限的单笔金额仍按元显示
+        let settings = Settings { max_income: 500_000.0, ..Settings::default() };
+        let money = MoneyFormat::from_settings(&settings);
+        assert_eq!(money.amount(300_000.0), "300,000.00");
+        assert_eq!(money.amount(500_000.0), "500,000.00");
+        assert_eq!(money.amount(600_000.0), "60.00万");
+        // 上限低于默认起点时不提前使用万
+        let settings = Settings { max_income: 1_000.0, ..Settings::default() };
+        assert_eq!(MoneyFormat::from_settings(&settings).amount(50_000.0), "50,000.00");
+    }
 
-                                                    // 删除按钮
-                                                    let btn = egui::Button::new(
-                                                        RichText::new("删除")
-                                                            .size(12.0)
-                                                            .color(danger_color)
-                                                    )
-                                                    .fill(Color32::TRANSPARENT)
-                                                    .stroke(Stroke::new(1.0, danger_color))
-                                                    .corner_radius(CornerRadius::same(5))
-                                                    .min_size(Vec2::new(48.0, 26.0));
+    #[test]
+    fn thousands_separators() {
+        let money = default_money();
+        assert_eq!(money.group_thousands(1234.5), "1,234.50");
+        assert_eq!(money.group_thousands(999_999.0), "999,999.00");
+        assert_eq!(money.group_thousands(0.0), "0.00");
+        assert_eq!(money.group_thousands(100.0), "100.00");
+        assert_eq!(money.money(1234.5), "¥1,234.50");
+        assert_eq!(money.money(-1234.5), "-¥1,234.50");
+        assert_eq!(money.money(-999_999.0), "-¥100.00万");
+        let plain = MoneyFormat { chinese_units: false, decimals: 0, ..default_money() };
+        assert_eq!(plain.money(-999_999.0), "-¥999,999");
+        assert_eq!(plain.money(1_234_567.0), "¥1,234,567");
+    }
 
-                                                    if ui.add(btn).clicked() {
-                                                        to_delete = Some(record.id);
-                                                    }
-                                                });
-                                            });
-                                    }
+    #[test]
+    fn week_balance_across_month_boundary() {
+        // 2024-05-27（周一）到 2024-06-02（周日）跨越五月和六月
+        let records = vec![
+            Record::sample("2024-05-26", "小王", 1.0),
+            Record::sample("2024-05-27", "小王", 10.0),
+            Record::sample("2024-05-31", "老李", 20.0),
+            Record::sample("2024-06-01", "小王", 30.5),
+            Record::sample("2024-06-02", "老李", 40.0),
+            Record::sample("2024-06-03", "小王", 100.0),
+        ];
+        assert_eq!(calc_week_balance(&records, date("2024-05-30"), Weekday::Mon), 100.5);
+        assert_eq!(calc_week_balance(&records, date("2024-06-02"), Weekday::Mon), 100.5);
+    }
 
-                                    // 处理结清状态更新
-                                    if let Some((id, new_settled)) = to_toggle_settled {
-                                        if self.db.update_settled(id, new_settled).is_ok() {
-                                            self.refresh_data();
-                                        }
-                                    }
+    #[test]
+    fn record_line_for_sharing() {
+        let money = default_money();
+        let full = Record {
+            game: Some("王者荣耀".to_string()),
+            duration: Some(2.0),
+            ..Record::sample("2024-06-01", "小王", 200.0)
+        };
+        assert_eq!(record_to_line(&full, &money), "2024-06-01 王者荣耀 2h ¥200.00");
 
-                                    if let Some(id) = to_delete {
-                                        self.delete_record(id);
-                                    }
-                                }
-                            });
-                    });
-                }); // vertical for table card
-                    }); // vertical
-                }); // horizontal for centering
-            });
+        // 没有游戏、时长无效时省略对应部分，不留多余空格
+        let bare = Record { game: Some(String::new()), duration: Some(f64::NAN), ..Record::sample("2024-06-02", "小王", 1234.5) };
+        assert_eq!(record_to_line(&bare, &money), "2024-06-02 ¥1,234.50");
+
+        let usd = Record { currency: "USD".to_string(), duration: Some(1.5), ..Record::sample("2024-06-03", "Tom", 30.0) };
+        assert_eq!(record_to_line(&usd, &money), "2024-06-03 1.5h $30.00");
+    }
+
+    #[test]
+    fn round_duration_89_minutes() {
+        let secs = 89 * 60;  // 1.483h
+        assert_eq!(round_duration(secs, DurationRounding::Nearest0_1), 1.5);
+        assert_eq!(round_duration(secs, DurationRounding::Up0_5), 1.5);
+        assert_eq!(round_duration(secs, DurationRounding::Nearest0_5), 1.5);
+        // 91 分钟：向上取整到 2 小时，四舍五入到 0.5 仍为 1.5
+        let secs = 91 * 60;
+        assert_eq!(round_duration(secs, DurationRounding::Nearest0_1), 1.5);
+        assert_eq!(round_duration(secs, DurationRounding::Up0_5), 2.0);
+        assert_eq!(round_duration(secs, DurationRounding::Nearest0_5), 1.5);
+    }
+
+    fn days(list: &[&str]) -> Vec<String> {
+        list.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn streak_across_month_boundary() {
+        let active = days(&["2024-05-30", "2024-05-31", "2024-06-01", "2024-06-02"]);
+        assert_eq!(current_streak(&active, date("2024-06-02")), 4);
+        // 跨年同理
+        let active = days(&["2023-12-31", "2024-01-01"]);
+        assert_eq!(current_streak(&active, date("2024-01-01")), 2);
+    }
+
+    #[test]
+    fn streak_when_today_not_logged_yet() {
+        let active = days(&["2024-05-31", "2024-06-01"]);
+        // 今天还没记录：从昨天往前数
+        assert_eq!(current_streak(&active, date("2024-06-02")), 2);
+        // 昨天也没有：中断
+        assert_eq!(current_streak(&active, date("2024-06-03")), 0);
+        // 中间断了一天：只数最近一段
+        let active = days(&["2024-05-29", "2024-05-31", "2024-06-01"]);
+        assert_eq!(current_streak(&active, date("2024-06-01")), 2);
+        assert_eq!(current_streak(&[], date("2024-06-01")), 0);
+    }
+
+    #[test]
+    fn display_date_presets() {
+        assert_eq!(format_display_date("2024-06-01", DateDisplay::Iso), "2024-06-01");
+        assert_eq!(format_display_date("2024-06-01", DateDisplay::Slash), "06/01");
+        assert_eq!(format_display_date("2024-06-01", DateDisplay::Chinese), "6月1日");
+        assert_eq!(format_display_date("2024-12-25", DateDisplay::Chinese), "12月25日");
+        // 无法解析的日期原样显示
+        assert_eq!(format_display_date("2024-13-01", DateDisplay::Slash), "2024-13-01");
+    }
+
+    #[test]
+    fn histogram_bins() {
+        let records = [Record::sample("2024-06-01", "a", 30.0), Record::sample("2024-06-01", "a", 120.0), Record::sample("2024-06-01", "a", 149.0)];
+        assert_eq!(income_histogram(&records, 50.0), vec![(0.0, 50.0, 1), (50.0, 100.0, 0), (100.0, 150.0, 2)]);
+    }
+
+    #[test]
+    fn histogram_caps_bin_count() {
+        let records = [Record::sample("2024-06-01", "a", 30.0), Record::sample("2024-06-01", "a", 1e9)];
+        let bins = income_histogram(&records, 50.0);
+        assert_eq!(bins.len(), MAX_HISTOGRAM_BINS);
+        assert_eq!(bins[0].2, 1);
+        let last = bins[MAX_HISTOGRAM_BINS - 1];
+        assert_eq!(last.0, (MAX_HISTOGRAM_BINS - 1) as f64 * 50.0);
+        assert!(last.1.is_infinite());
+        assert_eq!(last.2, 1);
+    }
+
+    #[test]
+    fn parse_income_normalizes_input() {
+        assert_eq!(parse_income("１,５００"), Some(1500.0));
+        assert_eq!(parse_income("２００元"), Some(200.0));
+        assert_eq!(parse_income("0"), None);
+        assert_eq!(parse_income("-5"), None);
+        assert_eq!(parse_income("五十"), None);
+    }
+
+    #[test]
+    fn week_bounds_follow_week_start() {
+        // 2024-06-05 是周三
+        let wed = date("2024-06-05");
+        assert_eq!(week_bounds(wed, Weekday::Mon), (date("2024-06-03"), date("2024-06-09")));
+        assert_eq!(week_bounds(wed, Weekday::Sun), (date("2024-06-02"), date("2024-06-08")));
+        // 周日当天：周一开始时是本周最后一天，周日开始时是第一天
+        let sun = date("2024-06-09");
+        assert_eq!(week_bounds(sun, Weekday::Mon), (date("2024-06-03"), date("2024-06-09")));
+        assert_eq!(week_bounds(sun, Weekday::Sun), (date("2024-06-09"), date("2024-06-15")));
+    }
+
+    #[test]
+    fn week_balance_shifts_with_week_start() {
+        let records = vec![
+            Record::sample("2024-06-02", "小王", 10.0),
+            Record::sample("2024-06-05", "小王", 5.0),
+            Record::sample("2024-06-09", "小王", 20.0),
+        ];
+        let wed = date("2024-06-05");
+        assert_eq!(calc_week_balance(&records, wed, Weekday::Mon), 25.0);
+        assert_eq!(calc_week_balance(&records, wed, Weekday::Sun), 15.0);
+    }
+
+    #[test]
+    fn duration_formatting() {
+        assert_eq!(format_duration(None), "-");
+        assert_eq!(format_duration(Some(f64::NAN)), "-");
+        assert_eq!(format_duration(Some(0.0)), "-");
+        assert_eq!(format_duration(Some(-1.0)), "-");
+        assert_eq!(format_duration(Some(0.01)), "<0.1h");
+        assert_eq!(format_duration(Some(1.0)), "1h");
+        assert_eq!(format_duration(Some(1.5)), "1.5h");
+        assert_eq!(format_duration(Some(2.34)), "2.3h");
+        assert_eq!(format_duration(Some(1.96)), "2h");
     }
 }
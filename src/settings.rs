@@ -0,0 +1,303 @@
+use chrono::Weekday;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// PIN 哈希的迭代次数，PIN 位数少，放慢逐个尝试的速度
+const PIN_HASH_ROUNDS: u32 = 100_000;
+
+/// 界面主题
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum ThemeMode {
+    #[default]
+    Auto,  // 跟随系统深色/浅色设置
+    Dark,
+    Light,
+}
+
+/// 计时转换为时长时的取整方式
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DurationRounding {
+    #[default]
+    Nearest0_1,  // 四舍五入到 0.1 小时
+    Up0_5,       // 向上取整到 0.5 小时（按半小时计费）
+    Nearest0_5,  // 四舍五入到 0.5 小时
+}
+
+/// 表格中日期的显示格式（存储始终是 YYYY-MM-DD）
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum DateDisplay {
+    #[default]
+    Iso,      // 2024-06-01
+    Slash,    // 06/01
+    Chinese,  // 6月1日
+}
+
+/// 界面密度：行高、输入框高度和卡片内边距
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Density {
+    Compact,      // 紧凑，适合小屏笔记本
+    #[default]
+    Standard,     // 标准
+    Comfortable,  // 宽松
+}
+
+/// 每周从哪天开始：影响本周统计和月历的列顺序
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum WeekStart {
+    #[default]
+    Monday,  // ISO 周
+    Sunday,
+}
+
+impl WeekStart {
+    pub fn weekday(self) -> Weekday {
+        match self {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        }
+    }
+}
+
+/// 保存的计时器状态，重启后恢复
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedTimer {
+    pub label: String,
+    pub accumulated_secs: u64,          // 之前各段的累计秒数
+    pub running_since: Option<String>,  // 运行中时本段开始的时间（YYYY-MM-DD HH:MM:SS）
+    pub saved_at: Option<String>,       // 运行中时保存的时间，本段只计到这里
+    pub ended: bool,
+    pub started_at: Option<String>,     // 本次计时开始的时间
+}
+
+/// 用户设置，保存在 %LOCALAPPDATA%\jz\settings.json
+/// 缺失字段使用默认值，兼容旧版本的设置文件
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub theme: ThemeMode,        // 界面主题
+    pub confirm_delete: bool,    // 删除前确认
+    pub forbid_future_dates: bool,  // 禁止添加未来日期的记录
+    pub close_to_tray: bool,     // 关闭窗口时隐藏到托盘（仅 Windows）
+    pub global_hotkey: bool,     // 全局快捷键开关
+    pub hotkey_combo: String,    // 全局快捷键组合，如 "Ctrl+Alt+J"
+    pub session_reminder: bool,  // 计时提醒开关
+    pub reminder_minutes: u32,   // 计时提醒间隔（分钟）
+    pub duration_rounding: DurationRounding,  // 计时填入时长的取整方式
+    pub week_start: WeekStart,   // 每周的第一天
+    pub day_start_hour: u32,     // 日切时间：早于该小时的时刻算作前一天（0 为午夜）
+    pub auto_lock: bool,         // 无操作一段时间后锁定
+    pub lock_minutes: u32,       // 自动锁定时间（分钟）
+    /// 旧版本明文保存的 PIN，读取后立即转为哈希，不再写出
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub lock_pin: String,
+    pub lock_pin_hash: String,   // 未加密数据库的解锁 PIN（加密数据库使用数据库密码），"盐$哈希"，均为十六进制
+    pub remember_input: bool,    // 添加后保留固定字段，下次直接使用
+    pub pin_boss: bool,          // 固定老板
+    pub pin_game: bool,          // 固定游戏
+    pub pin_duration: bool,      // 固定时长
+    pub last_boss: String,       // 上次添加的老板（记住上次输入时使用）
+    pub last_game: String,
+    pub last_duration: String,
+    pub show_time: bool,         // 表格日期列显示录入时间
+    pub show_hourly_rate: bool,  // 表格显示时薪列
+    pub zebra_stripes: bool,     // 表格隔行背景
+    pub column_dividers: bool,   // 表格列之间的竖线
+    pub date_display: DateDisplay,  // 表格日期显示格式
+    pub max_income: f64,         // 单笔收入上限
+    pub currency_symbol: String, // 主币种显示符号
+    pub decimals: u8,            // 金额小数位数（0–4）
+    pub chinese_units: bool,     // 大金额以万/亿为单位显示
+    pub income_presets: Vec<f64>,  // 收入框旁的常用金额按钮
+    pub monthly_goal: f64,       // 月收入目标，0 表示不显示进度条
+    pub accent_color: [u8; 3],   // 强调色
+    pub content_width: f32,      // 内容区宽度（关闭自适应时使用）
+    pub auto_width: bool,        // 内容区宽度随窗口自适应
+    pub density: Density,        // 界面密度
+    pub window_size: Option<[f32; 2]>,  // 上次关闭时的窗口尺寸
+    pub window_pos: Option<[f32; 2]>,   // 上次关闭时的窗口位置
+    pub selected_month: Option<(i32, u32)>,  // 上次查看的年月，为空时显示本月
+    pub timers: Vec<SavedTimer>,        // 计时器（可多个），为空时创建一个默认计时器
+    pub suggestion_limit: usize, // 联想列表最多显示的项数（3–20）
+    pub onboarded: bool,         // 已添加过真实数据，不再显示新手引导
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            theme: ThemeMode::Auto,
+            confirm_delete: true,
+            forbid_future_dates: false,
+            close_to_tray: false,
+            global_hotkey: false,
+            hotkey_combo: "Ctrl+Alt+J".to_string(),
+            session_reminder: false,
+            reminder_minutes: 60,
+            duration_rounding: DurationRounding::Nearest0_1,
+            week_start: WeekStart::Monday,
+            day_start_hour: 0,
+            auto_lock: false,
+            lock_minutes: 10,
+            lock_pin: String::new(),
+            lock_pin_hash: String::new(),
+            remember_input: false,
+            pin_boss: true,
+            pin_game: true,
+            pin_duration: true,
+            last_boss: String::new(),
+            last_game: String::new(),
+            last_duration: String::new(),
+            show_time: false,
+            show_hourly_rate: false,
+            zebra_stripes: true,
+            column_dividers: false,
+            date_display: DateDisplay::Iso,
+            max_income: 100_000.0,
+            currency_symbol: "¥".to_string(),
+            decimals: 2,
+            chinese_units: true,
+            income_presets: vec![50.0, 100.0, 200.0],
+            monthly_goal: 0.0,
+            accent_color: [64, 169, 255],
+            content_width: 880.0,
+            auto_width: true,
+            density: Density::Standard,
+            window_size: None,
+            window_pos: None,
+            selected_month: None,
+            timers: Vec::new(),
+            suggestion_limit: 6,
+            onboarded: false,
+        }
+    }
+}
+
+impl Settings {
+    fn get_settings_path() -> PathBuf {
+        let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+        path.push("jz");
+        path.push("settings.json");
+        path
+    }
+
+    /// 读取设置，文件不存在或损坏时使用默认值
+    /// 旧版本的明文 PIN 转为哈希后立即写回，明文不再留在文件中
+    pub fn load() -> Self {
+        let mut settings: Self = std::fs::read_to_string(Self::get_settings_path())
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default();
+        if !settings.lock_pin.is_empty() {
+            let pin = std::mem::take(&mut settings.lock_pin);
+            settings.set_lock_pin(&pin);
+            let _ = settings.save();
+        }
+        settings
+    }
+
+    /// 是否设置了解锁 PIN
+    pub fn has_lock_pin(&self) -> bool {
+        !self.lock_pin_hash.is_empty()
+    }
+
+    /// 设置解锁 PIN（加随机盐后保存哈希），传入空字符串时清除
+    pub fn set_lock_pin(&mut self, pin: &str) {
+        self.lock_pin_hash = if pin.is_empty() {
+            String::new()
+        } else {
+            let mut salt = [0u8; 16];
+            // 取不到系统随机数时退回时间戳，盐只需互不相同，不需要保密
+            if getrandom::getrandom(&mut salt).is_err() {
+                let nanos = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_nanos())
+                    .unwrap_or_default();
+                salt = nanos.to_le_bytes();
+            }
+            format!("{}${}", to_hex(&salt), to_hex(&hash_pin(&salt, pin)))
+        };
+    }
+
+    /// 校验解锁 PIN：用保存的盐重新计算哈希后比较
+    pub fn check_lock_pin(&self, pin: &str) -> bool {
+        let Some((salt, hash)) = self.lock_pin_hash.split_once('$') else {
+            return false;
+        };
+        let Some(salt) = from_hex(salt) else {
+            return false;
+        };
+        to_hex(&hash_pin(&salt, pin)) == hash
+    }
+
+    /// 联想列表项数，限制在 3–20 之间（设置文件可能被手动修改）
+    pub fn suggestion_limit(&self) -> usize {
+        self.suggestion_limit.clamp(3, 20)
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let path = Self::get_settings_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        std::fs::write(path, json)
+    }
+}
+
+/// 盐 + PIN 反复做 SHA-256
+fn hash_pin(salt: &[u8], pin: &str) -> [u8; 32] {
+    let mut hash: [u8; 32] = Sha256::new().chain_update(salt).chain_update(pin.as_bytes()).finalize().into();
+    for _ in 1..PIN_HASH_ROUNDS {
+        hash = Sha256::new().chain_update(salt).chain_update(hash).finalize().into();
+    }
+    hash
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len()).step_by(2).map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_pin_is_stored_hashed() {
+        let mut settings = Settings::default();
+        settings.set_lock_pin("1234");
+        assert!(settings.has_lock_pin());
+        assert!(!settings.lock_pin_hash.contains("1234"));
+        assert!(settings.check_lock_pin("1234"));
+        assert!(!settings.check_lock_pin("4321"));
+        assert!(!settings.check_lock_pin(""));
+        let json = serde_json::to_string(&settings).unwrap();
+        assert!(!json.contains("\"lock_pin\""));
+    }
+
+    #[test]
+    fn lock_pin_salt_differs() {
+        let mut a = Settings::default();
+        let mut b = Settings::default();
+        a.set_lock_pin("1234");
+        b.set_lock_pin("1234");
+        assert_ne!(a.lock_pin_hash, b.lock_pin_hash);
+        assert!(b.check_lock_pin("1234"));
+    }
+
+    #[test]
+    fn clearing_lock_pin() {
+        let mut settings = Settings::default();
+        settings.set_lock_pin("1234");
+        settings.set_lock_pin("");
+        assert!(!settings.has_lock_pin());
+        assert!(!settings.check_lock_pin(""));
+    }
+}
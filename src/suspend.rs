@@ -0,0 +1,66 @@
+//! 系统休眠检测：后台线程每秒醒来一次，两次醒来的间隔远超一秒说明系统睡眠过
+//! Windows 上 Instant 包含睡眠时间，计时器据此扣除睡眠期间的时长
+//! 使用独立线程而不是帧间隔，窗口最小化或隐藏到托盘时不会被误判为睡眠
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TICK: Duration = Duration::from_secs(1);
+/// 间隔超过该值才算睡眠，避免系统繁忙时的调度延迟被扣掉
+const GAP_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// 尚未被取走的睡眠总时长（毫秒）
+static SUSPENDED_MS: AtomicU64 = AtomicU64::new(0);
+static WATCH: Once = Once::new();
+
+/// 启动检测线程，多次调用只启动一次
+pub fn start_watch() {
+    WATCH.call_once(|| {
+        thread::spawn(|| {
+            let mut last = Instant::now();
+            loop {
+                thread::sleep(TICK);
+                let now = Instant::now();
+                let gap = now.duration_since(last);
+                if gap > GAP_THRESHOLD {
+                    SUSPENDED_MS.fetch_add((gap - TICK).as_millis() as u64, Ordering::SeqCst);
+                }
+                last = now;
+            }
+        });
+    });
+}
+
+/// 取出并清零上次调用以来检测到的睡眠时长
+pub fn take_gap() -> Duration {
+    Duration::from_millis(SUSPENDED_MS.swap(0, Ordering::SeqCst))
+}
+
+/// 扣除睡眠时长：把计时段的开始时刻后移，最多移到当前（本段计时归零），返回实际扣除的时长
+pub fn skip_gap(start: &mut Instant, gap: Duration) -> Duration {
+    let gap = gap.min(start.elapsed());
+    *start += gap;
+    gap
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skip_gap_moves_segment_start() {
+        let mut start = Instant::now() - Duration::from_secs(600);
+        assert_eq!(skip_gap(&mut start, Duration::from_secs(240)), Duration::from_secs(240));
+        assert_eq!(start.elapsed().as_secs(), 360);
+    }
+
+    #[test]
+    fn skip_gap_stops_at_now() {
+        let mut start = Instant::now() - Duration::from_secs(60);
+        let skipped = skip_gap(&mut start, Duration::from_secs(3600));
+        assert!(skipped >= Duration::from_secs(60) && skipped < Duration::from_secs(61));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,200 @@
+//! 计时器：可同时开多个（同时陪多个老板），每个有自己的名称和状态
+//! 状态流转：初始 → 运行 ⇄ 暂停 → 结束 → 重置回初始
+
+use crate::settings::SavedTimer;
+use crate::suspend;
+use chrono::{Local, NaiveDateTime};
+use std::time::{Duration, Instant};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub struct TimerInstance {
+    pub label: String,
+    running: bool,
+    start_instant: Option<Instant>,  // 本段开始的时刻（运行中）
+    accumulated: Duration,           // 之前各段的累计时长
+    ended: bool,                     // 是否已结束（结束后才能重置）
+    pub next_reminder: Option<Duration>,  // 下一次计时提醒的累计时长
+    started_at: Option<String>,      // 本次计时开始的时刻（结束时写入计时记录）
+}
+
+impl TimerInstance {
+    pub fn new(label: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            running: false,
+            start_instant: None,
+            accumulated: Duration::ZERO,
+            ended: false,
+            next_reminder: None,
+            started_at: None,
+        }
+    }
+
+    /// 当前累计时长（运行中包含本段已过去的时间）
+    pub fn elapsed(&self) -> Duration {
+        match self.start_instant {
+            Some(start) if self.running => self.accumulated + start.elapsed(),
+            _ => self.accumulated,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn is_initial(&self) -> bool {
+        !self.running && self.accumulated.is_zero() && !self.ended
+    }
+
+    pub fn is_paused(&self) -> bool {
+        !self.running && !self.accumulated.is_zero() && !self.ended
+    }
+
+    pub fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    pub fn start(&mut self) {
+        self.running = true;
+        self.start_instant = Some(Instant::now());
+        self.ended = false;
+        self.started_at = Some(Local::now().format(TIME_FORMAT).to_string());
+    }
+
+    pub fn pause(&mut self) {
+        if let Some(start) = self.start_instant.take() {
+            self.accumulated += start.elapsed();
+        }
+        self.running = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.running = true;
+        self.start_instant = Some(Instant::now());
+    }
+
+    /// 结束计时但保留时间，返回本次计时的开始时刻（写入计时记录用）
+    pub fn end(&mut self) -> Option<String> {
+        self.pause();
+        self.ended = true;
+        self.started_at.take()
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulated = Duration::ZERO;
+        self.ended = false;
+        self.next_reminder = None;
+    }
+
+    /// 扣除系统睡眠的时长：把本段开始时刻后移，最多移到当前（本段计时归零）
+    /// 暂停中不受影响，返回实际扣除的时长
+    pub fn skip(&mut self, gap: Duration) -> Duration {
+        match &mut self.start_instant {
+            Some(start) if self.running => suspend::skip_gap(start, gap),
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// 保存到设置：运行中的计时记下本段开始和保存的时间
+    pub fn to_saved(&self) -> SavedTimer {
+        let now = Local::now();
+        let running_since = self.start_instant
+            .filter(|_| self.running)
+            .map(|start| (now - chrono::Duration::from_std(start.elapsed()).unwrap_or_default()).format(TIME_FORMAT).to_string());
+        let saved_at = running_since.as_ref().map(|_| now.format(TIME_FORMAT).to_string());
+        SavedTimer {
+            label: self.label.clone(),
+            accumulated_secs: self.accumulated.as_secs(),
+            running_since,
+            saved_at,
+            ended: self.ended,
+            started_at: self.started_at.clone(),
+        }
+    }
+
+    /// 从设置恢复；运行中的计时只算到保存时为止，恢复为暂停，由用户继续
+    /// 程序关闭（或睡眠、崩溃）期间不计入；旧版设置没有保存时间，只恢复之前各段
+    pub fn from_saved(saved: &SavedTimer) -> Self {
+        let mut timer = Self::new(&saved.label);
+        timer.accumulated = Duration::from_secs(saved.accumulated_secs);
+        timer.ended = saved.ended;
+        timer.started_at = saved.started_at.clone();
+        let parse = |s: &Option<String>| s.as_deref().and_then(|s| NaiveDateTime::parse_from_str(s, TIME_FORMAT).ok());
+        if let (Some(since), Some(at)) = (parse(&saved.running_since), parse(&saved.saved_at)) {
+            timer.accumulated += (at - since).to_std().unwrap_or_default();
+        }
+        timer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 已运行 secs 秒的计时器
+    fn running_for(secs: u64) -> TimerInstance {
+        let mut timer = TimerInstance::new("计时");
+        timer.start();
+        timer.start_instant = Some(Instant::now() - Duration::from_secs(secs));
+        timer
+    }
+
+    #[test]
+    fn skip_subtracts_gap_from_running_segment() {
+        let mut timer = running_for(600);
+        timer.accumulated = Duration::from_secs(100);
+        assert_eq!(timer.skip(Duration::from_secs(240)), Duration::from_secs(240));
+        assert_eq!(timer.elapsed().as_secs(), 460);
+    }
+
+    #[test]
+    fn skip_never_goes_below_accumulated() {
+        let mut timer = running_for(60);
+        timer.accumulated = Duration::from_secs(100);
+        let skipped = timer.skip(Duration::from_secs(3600));
+        assert!(skipped >= Duration::from_secs(60) && skipped < Duration::from_secs(61));
+        assert_eq!(timer.elapsed().as_secs(), 100);
+    }
+
+    #[test]
+    fn skip_ignores_paused_timer() {
+        let mut timer = running_for(60);
+        timer.pause();
+        let before = timer.elapsed();
+        assert_eq!(timer.skip(Duration::from_secs(30)), Duration::ZERO);
+        assert_eq!(timer.elapsed(), before);
+    }
+
+    #[test]
+    fn from_saved_excludes_time_while_closed() {
+        let saved = SavedTimer {
+            label: "小王".to_string(),
+            accumulated_secs: 600,
+            running_since: Some("2024-06-01 10:00:00".to_string()),
+            saved_at: Some("2024-06-01 10:30:00".to_string()),
+            ended: false,
+            started_at: Some("2024-06-01 09:00:00".to_string()),
+        };
+        let timer = TimerInstance::from_saved(&saved);
+        assert!(timer.is_paused());
+        assert_eq!(timer.elapsed(), Duration::from_secs(600 + 30 * 60));
+
+        // 旧版设置没有保存时间：只恢复之前各段
+        let legacy = SavedTimer { saved_at: None, ..saved };
+        let timer = TimerInstance::from_saved(&legacy);
+        assert!(timer.is_paused());
+        assert_eq!(timer.elapsed(), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn saved_round_trip_keeps_running_segment() {
+        let timer = running_for(90);
+        let saved = timer.to_saved();
+        assert!(saved.running_since.is_some() && saved.saved_at.is_some());
+        let restored = TimerInstance::from_saved(&saved);
+        assert!(!restored.is_running());
+        let secs = restored.elapsed().as_secs();
+        assert!((89..=91).contains(&secs), "{secs}");
+    }
+}
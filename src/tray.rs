@@ -0,0 +1,77 @@
+//! 系统托盘（仅 Windows）：关闭窗口时隐藏到托盘，计时器继续运行
+
+use eframe::egui::{self, ViewportCommand};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem};
+use tray_icon::{Icon, MouseButton, TrayIcon, TrayIconBuilder, TrayIconEvent};
+
+const MENU_SHOW: &str = "tray_show";
+const MENU_QUIT: &str = "tray_quit";
+
+/// 托盘菜单点了“退出”，关闭时不再隐藏到托盘
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+/// 事件回调只能设置一次（库内部为 OnceLock），重新启用托盘时沿用
+static HANDLERS: Once = Once::new();
+
+pub struct Tray {
+    icon: TrayIcon,
+    tooltip: String,  // 当前提示文字，避免每帧重复设置
+}
+
+impl Tray {
+    pub fn new(ctx: &egui::Context) -> Result<Self, Box<dyn std::error::Error>> {
+        let menu = Menu::new();
+        menu.append_items(&[
+            &MenuItem::with_id(MENU_SHOW, "显示", true, None),
+            &MenuItem::with_id(MENU_QUIT, "退出", true, None),
+        ])?;
+        let png = eframe::icon_data::from_png_bytes(include_bytes!("../icon.png"))?;
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip(crate::APP_TITLE)
+            .with_icon(Icon::from_rgba(png.rgba, png.width, png.height)?)
+            .build()?;
+
+        // 窗口隐藏后 update 不一定运行，托盘事件直接向窗口发送命令
+        HANDLERS.call_once(|| {
+            let menu_ctx = ctx.clone();
+            MenuEvent::set_event_handler(Some(move |event: MenuEvent| {
+                if event.id == MENU_SHOW {
+                    show_window(&menu_ctx);
+                } else if event.id == MENU_QUIT {
+                    QUIT_REQUESTED.store(true, Ordering::SeqCst);
+                    menu_ctx.send_viewport_cmd(ViewportCommand::Close);
+                    menu_ctx.request_repaint();
+                }
+            }));
+            let click_ctx = ctx.clone();
+            TrayIconEvent::set_event_handler(Some(move |event: TrayIconEvent| {
+                if let TrayIconEvent::DoubleClick { button: MouseButton::Left, .. } = event {
+                    show_window(&click_ctx);
+                }
+            }));
+        });
+
+        Ok(Self { icon, tooltip: String::new() })
+    }
+
+    /// 是否通过托盘菜单请求退出
+    pub fn quit_requested(&self) -> bool {
+        QUIT_REQUESTED.load(Ordering::SeqCst)
+    }
+
+    /// 更新托盘提示文字（内容不变时不调用系统接口）
+    pub fn set_tooltip(&mut self, text: &str) {
+        if self.tooltip != text {
+            let _ = self.icon.set_tooltip(Some(text));
+            self.tooltip = text.to_string();
+        }
+    }
+}
+
+fn show_window(ctx: &egui::Context) {
+    ctx.send_viewport_cmd(ViewportCommand::Visible(true));
+    ctx.send_viewport_cmd(ViewportCommand::Focus);
+    ctx.request_repaint();
+}